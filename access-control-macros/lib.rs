@@ -0,0 +1,118 @@
+//! The [`macro@access_controlled`] attribute, re-exported by `access-control`.
+//!
+//! Messages of ink! contracts can only be implemented in their `#[ink::contract]` module before
+//! it is expanded, so the implementation of `AccessControlled` is added to the module by an
+//! attribute placed above `#[ink::contract]`, rather than by a macro invoked within it.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Attribute, Ident, Item, ItemMod};
+
+/// Implements `AccessControlled` for the storage of the contract, delegating every message to the
+/// `AccessControl` in the field of the storage named by the argument and passing the caller.
+///
+/// ```ignore
+/// #[access_control::access_controlled(access)]
+/// #[ink::contract]
+/// mod contract {
+///     #[ink(storage)]
+///     pub struct Contract {
+///         access: AccessControl,
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn access_controlled(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let field = parse_macro_input!(attr as Ident);
+    let mut module = parse_macro_input!(item as ItemMod);
+    match implement(&field, &mut module) {
+        Ok(()) => quote!(#module).into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// Appends the implementation to the items of `module`.
+fn implement(field: &Ident, module: &mut ItemMod) -> syn::Result<()> {
+    let span = module.ident.span();
+    let (_, items) = module
+        .content
+        .as_mut()
+        .ok_or_else(|| syn::Error::new(span, "expected an inline `#[ink::contract]` module"))?;
+    let storage = items
+        .iter()
+        .find_map(|item| match item {
+            Item::Struct(item) if item.attrs.iter().any(is_storage) => Some(item.ident.clone()),
+            _ => None,
+        })
+        .ok_or_else(|| syn::Error::new(span, "expected an `#[ink(storage)]` struct"))?;
+    items.push(parse_quote! {
+        impl ::access_control::AccessControlled for #storage {
+            #[ink(message)]
+            fn owner(&self) -> ::ink::primitives::AccountId {
+                self.#field.owner()
+            }
+
+            #[ink(message)]
+            fn pending_owner(&self) -> ::core::option::Option<::ink::primitives::AccountId> {
+                self.#field.pending_owner()
+            }
+
+            #[ink(message)]
+            fn transfer_ownership(
+                &mut self,
+                new_owner: ::ink::primitives::AccountId,
+            ) -> ::access_control::Result<()> {
+                self.#field
+                    .transfer_ownership(self.env().caller(), new_owner)
+            }
+
+            #[ink(message)]
+            fn accept_ownership(&mut self) -> ::access_control::Result<()> {
+                self.#field.accept_ownership(self.env().caller())
+            }
+
+            #[ink(message)]
+            fn set_operator(
+                &mut self,
+                account: ::ink::primitives::AccountId,
+                operator: bool,
+            ) -> ::access_control::Result<()> {
+                self.#field
+                    .set_operator(self.env().caller(), account, operator)
+            }
+
+            #[ink(message)]
+            fn grant_role(
+                &mut self,
+                role: ::access_control::Role,
+                account: ::ink::primitives::AccountId,
+            ) -> ::access_control::Result<()> {
+                self.#field.grant_role(self.env().caller(), role, account)
+            }
+
+            #[ink(message)]
+            fn revoke_role(
+                &mut self,
+                role: ::access_control::Role,
+                account: ::ink::primitives::AccountId,
+            ) -> ::access_control::Result<()> {
+                self.#field.revoke_role(self.env().caller(), role, account)
+            }
+
+            #[ink(message)]
+            fn has_role(
+                &self,
+                role: ::access_control::Role,
+                account: ::ink::primitives::AccountId,
+            ) -> bool {
+                self.#field.has_role(role, account)
+            }
+        }
+    });
+    Ok(())
+}
+
+// Whether `attr` is `#[ink(storage)]`.
+fn is_storage(attr: &Attribute) -> bool {
+    attr.path().is_ident("ink") && attr.parse_args::<Ident>().is_ok_and(|arg| arg == "storage")
+}
@@ -0,0 +1,279 @@
+//! Ownership and roles for restricting messages of contracts, e.g. those spending the balance of
+//! the contract on other chains.
+//!
+//! A contract keeps an [`AccessControl`] in its storage and implements [`AccessControlled`] by
+//! delegating to it, passing the caller, with the [`macro@access_controlled`] attribute placed
+//! above `#[ink::contract]`. Messages requiring a role call [`AccessControl::ensure_role`] first.
+//! The owner and operators have every role, other accounts only the roles granted to them by the
+//! owner. Ownership is transferred in two steps: the owner proposes a new owner, which has to
+//! accept it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use access_control_macros::access_controlled;
+use ink::{env::DefaultEnvironment, primitives::AccountId, storage::Mapping};
+
+/// A role required by messages, defined by each contract.
+pub type Role = u32;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// The caller is not the owner.
+    NotOwner,
+    /// The caller does not have the role.
+    MissingRole(Role),
+    /// The caller is not the pending owner.
+    NotPendingOwner,
+}
+
+/// The messages managing the access to a contract.
+#[ink::trait_definition]
+pub trait AccessControlled {
+    /// Returns the owner.
+    #[ink(message)]
+    fn owner(&self) -> AccountId;
+
+    /// Returns the account ownership is being transferred to, if any.
+    #[ink(message)]
+    fn pending_owner(&self) -> Option<AccountId>;
+
+    /// Proposes `new_owner` as owner, which becomes owner once it accepts. Only callable by the
+    /// owner.
+    #[ink(message)]
+    fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()>;
+
+    /// Accepts the ownership proposed to the caller.
+    #[ink(message)]
+    fn accept_ownership(&mut self) -> Result<()>;
+
+    /// Sets whether `account` is an operator, having every role. Only callable by the owner.
+    #[ink(message)]
+    fn set_operator(&mut self, account: AccountId, operator: bool) -> Result<()>;
+
+    /// Grants `role` to `account`. Only callable by the owner.
+    #[ink(message)]
+    fn grant_role(&mut self, role: Role, account: AccountId) -> Result<()>;
+
+    /// Revokes `role` from `account`. Only callable by the owner.
+    #[ink(message)]
+    fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<()>;
+
+    /// Returns whether `account` has `role`.
+    #[ink(message)]
+    fn has_role(&self, role: Role, account: AccountId) -> bool;
+}
+
+#[ink::event]
+pub struct OwnershipTransferStarted {
+    #[ink(topic)]
+    pub owner: AccountId,
+    #[ink(topic)]
+    pub pending_owner: AccountId,
+}
+
+#[ink::event]
+pub struct OwnershipTransferred {
+    #[ink(topic)]
+    pub previous_owner: AccountId,
+    #[ink(topic)]
+    pub new_owner: AccountId,
+}
+
+#[ink::event]
+pub struct OperatorSet {
+    #[ink(topic)]
+    pub account: AccountId,
+    pub operator: bool,
+}
+
+#[ink::event]
+pub struct RoleGranted {
+    #[ink(topic)]
+    pub role: Role,
+    #[ink(topic)]
+    pub account: AccountId,
+}
+
+#[ink::event]
+pub struct RoleRevoked {
+    #[ink(topic)]
+    pub role: Role,
+    #[ink(topic)]
+    pub account: AccountId,
+}
+
+/// The owner, operators and roles of a contract.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct AccessControl {
+    owner: AccountId,
+    pending_owner: Option<AccountId>,
+    operators: Mapping<AccountId, ()>,
+    roles: Mapping<(Role, AccountId), ()>,
+}
+
+impl AccessControl {
+    pub fn new(owner: AccountId) -> Self {
+        Self {
+            owner,
+            ..Default::default()
+        }
+    }
+
+    pub fn owner(&self) -> AccountId {
+        self.owner
+    }
+
+    pub fn pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner
+    }
+
+    pub fn ensure_owner(&self, caller: AccountId) -> Result<()> {
+        if caller != self.owner {
+            return Err(Error::NotOwner);
+        }
+        Ok(())
+    }
+
+    pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+        account == self.owner
+            || self.operators.contains(account)
+            || self.roles.contains((role, account))
+    }
+
+    pub fn ensure_role(&self, role: Role, caller: AccountId) -> Result<()> {
+        if !self.has_role(role, caller) {
+            return Err(Error::MissingRole(role));
+        }
+        Ok(())
+    }
+
+    pub fn transfer_ownership(&mut self, caller: AccountId, new_owner: AccountId) -> Result<()> {
+        self.ensure_owner(caller)?;
+        self.pending_owner = Some(new_owner);
+        emit(OwnershipTransferStarted {
+            owner: caller,
+            pending_owner: new_owner,
+        });
+        Ok(())
+    }
+
+    pub fn accept_ownership(&mut self, caller: AccountId) -> Result<()> {
+        if self.pending_owner != Some(caller) {
+            return Err(Error::NotPendingOwner);
+        }
+        let previous_owner = core::mem::replace(&mut self.owner, caller);
+        self.pending_owner = None;
+        emit(OwnershipTransferred {
+            previous_owner,
+            new_owner: caller,
+        });
+        Ok(())
+    }
+
+    pub fn set_operator(
+        &mut self,
+        caller: AccountId,
+        account: AccountId,
+        operator: bool,
+    ) -> Result<()> {
+        self.ensure_owner(caller)?;
+        if operator {
+            self.operators.insert(account, &());
+        } else {
+            self.operators.remove(account);
+        }
+        emit(OperatorSet { account, operator });
+        Ok(())
+    }
+
+    pub fn grant_role(&mut self, caller: AccountId, role: Role, account: AccountId) -> Result<()> {
+        self.ensure_owner(caller)?;
+        self.roles.insert((role, account), &());
+        emit(RoleGranted { role, account });
+        Ok(())
+    }
+
+    pub fn revoke_role(&mut self, caller: AccountId, role: Role, account: AccountId) -> Result<()> {
+        self.ensure_owner(caller)?;
+        self.roles.remove((role, account));
+        emit(RoleRevoked { role, account });
+        Ok(())
+    }
+}
+
+fn emit<E: ink::env::Event>(event: E) {
+    ink::env::emit_event::<DefaultEnvironment, E>(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::env::test;
+
+    const ROLE: Role = 1;
+
+    fn accounts() -> test::DefaultAccounts<DefaultEnvironment> {
+        test::default_accounts::<DefaultEnvironment>()
+    }
+
+    #[ink::test]
+    fn roles_work() {
+        let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+        let mut access = AccessControl::new(alice);
+        assert!(access.has_role(ROLE, alice));
+        assert_eq!(access.ensure_role(ROLE, bob), Err(Error::MissingRole(ROLE)));
+
+        // Only the owner grants roles.
+        assert_eq!(access.grant_role(bob, ROLE, bob), Err(Error::NotOwner));
+        assert_eq!(access.grant_role(alice, ROLE, bob), Ok(()));
+        assert_eq!(access.ensure_role(ROLE, bob), Ok(()));
+        assert!(!access.has_role(ROLE + 1, bob));
+        assert_eq!(access.revoke_role(bob, ROLE, bob), Err(Error::NotOwner));
+        assert_eq!(access.revoke_role(alice, ROLE, bob), Ok(()));
+        assert!(!access.has_role(ROLE, bob));
+
+        // Operators have every role.
+        assert_eq!(
+            access.set_operator(bob, charlie, true),
+            Err(Error::NotOwner)
+        );
+        assert_eq!(access.set_operator(alice, charlie, true), Ok(()));
+        assert!(access.has_role(ROLE, charlie) && access.has_role(ROLE + 1, charlie));
+        assert_eq!(access.set_operator(alice, charlie, false), Ok(()));
+        assert!(!access.has_role(ROLE, charlie));
+        assert_eq!(test::recorded_events().count(), 4);
+    }
+
+    #[ink::test]
+    fn transfer_ownership_works() {
+        let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+        let mut access = AccessControl::new(alice);
+        assert_eq!(access.transfer_ownership(bob, bob), Err(Error::NotOwner));
+        assert_eq!(access.transfer_ownership(alice, bob), Ok(()));
+        assert_eq!(access.pending_owner(), Some(bob));
+
+        // The owner only changes once the pending owner accepts.
+        assert_eq!(access.owner(), alice);
+        assert_eq!(
+            access.accept_ownership(charlie),
+            Err(Error::NotPendingOwner)
+        );
+        assert_eq!(access.accept_ownership(bob), Ok(()));
+        assert_eq!((access.owner(), access.pending_owner()), (bob, None));
+        assert_eq!(access.ensure_owner(alice), Err(Error::NotOwner));
+        assert!(!access.has_role(ROLE, alice));
+        assert_eq!(access.accept_ownership(bob), Err(Error::NotPendingOwner));
+
+        let event = test::recorded_events().last().unwrap();
+        let transferred =
+            <OwnershipTransferred as ink::scale::Decode>::decode(&mut &event.data[..]).unwrap();
+        assert_eq!(
+            (transferred.previous_owner, transferred.new_owner),
+            (alice, bob)
+        );
+    }
+}
@@ -1,81 +1,242 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+use access_control::{AccessControl, Role};
+use escrow::Escrow;
 use ink::{
-    env::hash::{Blake2x256, CryptoHash},
     prelude::vec::Vec,
-    scale::{Compact, Encode},
+    primitives::AccountId,
     xcm::{
         prelude::{
-            AccountId32, All, Asset, Junction::Parachain, Location, OriginKind, QueryId,
+            AccountId32, All, Asset, AssetId, Junction::Parachain, Location, OriginKind, QueryId,
             QueryResponseInfo, Weight, WeightLimit, Xcm, XcmHash,
         },
         DoubleEncoded, VersionedXcm,
     },
 };
+use ismp_heights::{Height, Heights};
+use message_ids::MessageIds;
 use pop_api::{
     messaging::{self as api, ismp, ismp::Get, xcm::Response, MessageId, Status},
     StatusCode,
 };
+use upgradeable::{Migrate, StorageVersion, Upgradeable, Version};
+use xcm_versions::{subscribe_xcm, XcmVersions};
+
+pub use repatriation::{hashed_account, repatriate_xcm, ASSET_HUB, POP};
 
 pub type Result<T> = core::result::Result<T, StatusCode>;
 
+/// The role required to transact on the destination, spending the balance of the contract there.
+pub const TRANSACT: Role = 1;
+
+/// Returns the program reserve transferring `asset` to `beneficiary` on `dest`, reporting the
+/// amount deposited to both `reports`, one after the other. The delivery fee of each report is paid
+/// from the holding after it was reported, so the amount deposited is derived from both reports,
+/// see [`Escrow::report`].
+pub fn fund_xcm(
+    asset: Asset,
+    dest: Location,
+    beneficiary: AccountId,
+    reports: (QueryResponseInfo, QueryResponseInfo),
+) -> Xcm<()> {
+    Xcm::builder_unsafe()
+        .withdraw_asset(asset.clone().into())
+        .initiate_reserve_withdraw(
+            asset.clone().into(),
+            dest,
+            Xcm::builder_unsafe()
+                .buy_execution(asset, WeightLimit::Unlimited)
+                .report_holding(reports.0, All.into())
+                .report_holding(reports.1, All.into())
+                .deposit_asset(
+                    All.into(),
+                    Location::new(0, AccountId32 { network: None, id: beneficiary.0 }),
+                )
+                .build(),
+        )
+        .build()
+}
+
+/// Returns the program executing `call` on the destination, reporting its status to `response`
+/// and depositing any surplus to `beneficiary`, reporting the surplus to both `refunds` like
+/// [`fund_xcm`].
+pub fn transact_xcm(
+    call: DoubleEncoded<()>,
+    weight: Weight,
+    fees: Asset,
+    response: QueryResponseInfo,
+    refunds: (QueryResponseInfo, QueryResponseInfo),
+    beneficiary: AccountId,
+) -> Xcm<()> {
+    Xcm::builder_unsafe()
+        .withdraw_asset(fees.clone().into())
+        .buy_execution(fees, WeightLimit::Unlimited)
+        .set_appendix(
+            Xcm::builder_unsafe()
+                .refund_surplus()
+                .report_holding(refunds.0, All.into())
+                .report_holding(refunds.1, All.into())
+                .deposit_asset(
+                    All.into(),
+                    Location::new(0, AccountId32 { network: None, id: beneficiary.0 }),
+                )
+                .build(),
+        )
+        .set_error_handler(Xcm::builder_unsafe().report_error(response.clone()).build())
+        .transact(OriginKind::SovereignAccount, weight, call)
+        .report_transact_status(response)
+        .build()
+}
+
+/// Returns the program withdrawing `asset` from the origin to `beneficiary`, paying the fees with
+/// `asset`.
+pub fn withdraw_xcm(asset: Asset, beneficiary: AccountId) -> Xcm<()> {
+    Xcm::builder_unsafe()
+        .withdraw_asset(asset.clone().into())
+        .buy_execution(asset, WeightLimit::Unlimited)
+        .deposit_asset(
+            All.into(),
+            Location::new(0, AccountId32 { network: None, id: beneficiary.0 }),
+        )
+        .build()
+}
+
+#[access_control::access_controlled(access)]
 #[ink::contract]
 mod messaging {
+    use ink::storage::Mapping;
     use pop_api::messaging::{ismp::StorageValue, Callback};
 
     use super::*;
 
     const UNAUTHORIZED: u32 = u32::MAX;
+    /// The value transferred does not cover the minimum fee of the destination.
+    const INSUFFICIENT_FEE: u32 = u32::MAX - 1;
+    /// The surplus paid could not be refunded to the payer.
+    const REFUND_FAILED: u32 = u32::MAX - 2;
+    /// The deposit of the caller does not cover the amount.
+    const INSUFFICIENT_DEPOSIT: u32 = u32::MAX - 3;
+    /// The program can't be sent in the XCM version of the destination.
+    const UNSUPPORTED_XCM_VERSION: u32 = u32::MAX - 4;
+    /// No height of the destination was reported to the contract yet.
+    const UNKNOWN_HEIGHT: u32 = u32::MAX - 5;
+    /// The height is ahead of the latest height reported to the contract, so the request would
+    /// never be answered.
+    const HEIGHT_AHEAD: u32 = u32::MAX - 6;
+    /// The runtime registered no query for the response.
+    const NO_QUERY: u32 = u32::MAX - 7;
 
     #[ink(storage)]
     #[derive(Default)]
     pub struct Messaging {
+        access: AccessControl,
         para: u32,
-        id: MessageId,
+        /// The minimum fee paid to relayers for requests, by destination.
+        min_fees: Mapping<u32, Balance>,
+        /// The payer and surplus of requests, refunded once they completed.
+        payments: Mapping<MessageId, (AccountId, Balance)>,
+        /// The deposits of callers in the account of the contract on the destination.
+        escrow: Escrow,
+        storage_version: StorageVersion,
+        /// The XCM versions of destinations, which programs are sent in.
+        xcm_versions: XcmVersions,
+        /// The latest heights of destinations with a state commitment on Pop, reported by the
+        /// owner.
+        heights: Heights,
+        /// The ids of the messages sent by the contract.
+        ids: MessageIds,
     }
 
     impl Messaging {
         #[ink(constructor, payable)]
         pub fn new(para: u32) -> Result<Self> {
-            let instance = Self { para, id: 0 };
+            let instance = Self {
+                access: AccessControl::new(Self::env().caller()),
+                para,
+                storage_version: StorageVersion::new(<Self as Migrate>::VERSION),
+                ..Default::default()
+            };
             Ok(instance)
         }
 
+        /// Returns the minimum fee paid to relayers for requests to `dest`.
+        #[ink(message)]
+        pub fn min_fee(&self, dest: u32) -> Balance {
+            self.min_fees.get(dest).unwrap_or_default()
+        }
+
+        /// Sets the minimum fee paid to relayers for requests to `dest`.
+        #[ink(message)]
+        pub fn set_min_fee(&mut self, dest: u32, fee: Balance) -> Result<()> {
+            self.access.ensure_owner(self.env().caller()).map_err(|_| UNAUTHORIZED)?;
+            self.min_fees.insert(dest, &fee);
+            Ok(())
+        }
+
+        /// Returns the latest height of the destination reported by the owner.
         #[ink(message)]
-        pub fn get(&mut self, key: Vec<u8>, height: u32) -> Result<()> {
-            self.id = self.id.saturating_add(1);
-            ismp::get(
-                self.id,
-                Get::new(self.para, height, 0, Vec::default(), Vec::from([key.clone()])),
-                0,
-                Some(Callback::to(0x57ad942b, Weight::from_parts(800_000_000, 500_000))),
-            )?;
-            self.env().emit_event(IsmpRequested { id: self.id, key, height });
+        pub fn latest_height(&self) -> Option<u32> {
+            self.heights.latest(self.para)
+        }
+
+        /// Reports the latest height of the destination with a state commitment on Pop, as seen
+        /// in Pop's `StateMachineUpdated` events. The contract can not read the commitments
+        /// itself, so heights read at are only checked against this report. Lower heights are
+        /// ignored.
+        #[ink(message)]
+        pub fn update_height(&mut self, height: u32) -> Result<()> {
+            self.access.ensure_owner(self.env().caller()).map_err(|_| UNAUTHORIZED)?;
+            self.heights.update(self.para, height);
             Ok(())
         }
 
+        /// Reads `key` at `height`, which must not be ahead of the latest height reported. The
+        /// caller pays the minimum fee of the destination with the value transferred, any surplus
+        /// is refunded once the request completed.
+        #[ink(message, payable)]
+        pub fn get(&mut self, key: Vec<u8>, height: Height) -> Result<()> {
+            let height = self.heights.resolve(self.para, height).map_err(|e| match e {
+                ismp_heights::Error::UnknownHeight => UNKNOWN_HEIGHT,
+                ismp_heights::Error::HeightAhead => HEIGHT_AHEAD,
+            })?;
+            let fee = self.min_fee(self.para);
+            let surplus = self
+                .env()
+                .transferred_value()
+                .checked_sub(fee)
+                .ok_or(StatusCode::from(INSUFFICIENT_FEE))?;
+            let (id, ()) = self.ids.send(|id| {
+                ismp::get(
+                    id,
+                    Get::new(self.para, height, 0, Vec::default(), Vec::from([key.clone()])),
+                    fee,
+                    Some(Callback::to(0x57ad942b, Weight::from_parts(800_000_000, 500_000))),
+                )
+            })?;
+            let payer = self.env().caller();
+            if surplus > 0 {
+                self.payments.insert(id, &(payer, surplus));
+            }
+            self.env().emit_event(FeePaid { id, payer, fee, surplus });
+            self.env().emit_event(IsmpRequested { id, key, height });
+            Ok(())
+        }
+
+        /// Funds the account of the contract on the destination with the value transferred. The
+        /// amount deposited is credited to the caller once reported by the destination, less the
+        /// delivery fee of the report.
         #[ink(message, payable)]
         pub fn fund(&mut self) -> Result<()> {
             let dest = Location::new(1, Parachain(self.para));
+            let (id, report) = self.new_query(dest.clone())?;
+            let (second_id, second) = self.new_query(dest.clone())?;
 
             // Reserve transfer specified assets to contract account on destination.
             let asset: Asset = (Location::parent(), self.env().transferred_value()).into();
-            let beneficiary = hashed_account(4_001, self.env().account_id()); // todo: para id getter
-            let message: Xcm<()> = Xcm::builder_unsafe()
-                .withdraw_asset(asset.clone().into())
-                .initiate_reserve_withdraw(
-                    asset.clone().into(),
-                    dest.clone(),
-                    Xcm::builder_unsafe()
-                        .buy_execution(asset.clone(), WeightLimit::Unlimited)
-                        .deposit_asset(
-                            All.into(),
-                            Location::new(0, AccountId32 { network: None, id: beneficiary.0 }),
-                        )
-                        .build(),
-                )
-                .build();
+            let beneficiary = hashed_account(POP, self.env().account_id());
+            let message = fund_xcm(asset, dest, beneficiary, (report, second));
             api::xcm::execute(&VersionedXcm::V4(message)).unwrap(); // todo: handle error
+            self.escrow.credit_report((id, second_id), self.env().caller());
 
             self.env().emit_event(Funded {
                 account_id: beneficiary,
@@ -84,74 +245,219 @@ mod messaging {
             Ok(())
         }
 
-        #[ink(message, payable)]
-        pub fn transact(&mut self, call: DoubleEncoded<()>, weight: Weight) -> Result<()> {
+        /// Transacts `call` on the destination, paying up to `fees` from the deposit of the caller.
+        /// The fees left are credited back once reported by the destination. Requires the
+        /// `TRANSACT` role, as `call` is dispatched by the account of the contract.
+        #[ink(message)]
+        pub fn transact(
+            &mut self,
+            call: DoubleEncoded<()>,
+            weight: Weight,
+            fees: Balance,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            self.access.ensure_role(TRANSACT, caller).map_err(|_| UNAUTHORIZED)?;
+            self.escrow.debit(caller, fees).map_err(|_| INSUFFICIENT_DEPOSIT)?;
             let dest = Location::new(1, Parachain(self.para));
 
-            // Register a new query for receiving a response, used to report transact status.
-            self.id = self.id.saturating_add(1);
-            let query_id = api::xcm::new_query(
-                self.id,
-                dest.clone(),
-                self.env().block_number().saturating_add(100),
-                Some(Callback::to(0x641b0b03, Weight::from_parts(800_000_000, 500_000))),
-            )?
-                .unwrap(); // TODO: handle error
-
-            // TODO: provide an api function for determining the local para id and max weight value
-            // for processing the reported transact status on the local chain.
-            let response = QueryResponseInfo {
-                // Route back to this parachain.
-                destination: Location::new(1, Parachain(4_001)),
-                query_id,
-                max_weight: Weight::from_parts(1_000_000, 5_000),
-            };
+            // Register new queries for receiving responses, used to report transact status and
+            // the fees left.
+            let (id, response) = self.new_query(dest.clone())?;
+            let query_id = response.query_id;
+            let (refund_id, refund) = self.new_query(dest.clone())?;
+            let (second_id, second) = self.new_query(dest.clone())?;
 
             // Send transact message.
-            let fees: Asset = (Location::parent(), self.env().transferred_value()).into();
-            let message: Xcm<()> = self._transact(call, weight, fees, response);
-            let hash = api::xcm::send(&dest.into_versioned(), &VersionedXcm::V4(message)).unwrap(); // todo: handle error
+            let fees: Asset = (Location::parent(), fees).into();
+            let message: Xcm<()> = self._transact(call, weight, fees, response, (refund, second));
+            let hash = self.send(dest, message)?;
+            self.escrow.credit_report((refund_id, second_id), caller);
+
+            self.env().emit_event(XcmRequested { id, query_id, hash });
+            Ok(())
+        }
+
+        /// Withdraws `amount` from the account of the contract on `dest_chain` back to `beneficiary`
+        /// on Pop. On the destination the account holds the deposits of callers, so `amount` is
+        /// debited from the deposit of the caller. On other chains only the owner withdraws.
+        ///
+        /// Completion is reported by `dest_chain` as `XcmCompleted` of the id emitted by
+        /// `Withdrawn`, with an `ExecutionResult` of `None` on success.
+        #[ink(message)]
+        pub fn withdraw(
+            &mut self,
+            dest_chain: u32,
+            amount: Balance,
+            beneficiary: AccountId,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            if dest_chain == self.para {
+                self.escrow.debit(caller, amount).map_err(|_| INSUFFICIENT_DEPOSIT)?;
+            } else {
+                self.access.ensure_owner(caller).map_err(|_| UNAUTHORIZED)?;
+            }
+            let dest = Location::new(1, Parachain(dest_chain));
+            let (id, response) = self.new_query(dest.clone())?;
+            let message = repatriate_xcm(dest_chain, amount, beneficiary, response);
+            self.send(dest, message)?;
+            self.env().emit_event(Withdrawn { id, dest_chain, amount, beneficiary });
+            Ok(())
+        }
+
+        /// Returns the deposit of `account` in the account of the contract on the destination.
+        #[ink(message)]
+        pub fn deposit_of(&self, account: AccountId) -> Balance {
+            self.escrow.deposit_of(account)
+        }
+
+        /// Withdraws `amount` of the deposit of the caller to their account on the destination,
+        /// paying the fees of the destination with it.
+        #[ink(message)]
+        pub fn withdraw_deposit(&mut self, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            self.escrow.debit(caller, amount).map_err(|_| INSUFFICIENT_DEPOSIT)?;
+            let dest = Location::new(1, Parachain(self.para));
+            let asset: Asset = (Location::parent(), amount).into();
+            let message = withdraw_xcm(asset, caller);
+            self.send(dest, message)?;
+            Ok(())
+        }
+
+        /// Returns the XCM version negotiated with `dest_chain`, if any. Programs are sent to
+        /// destinations without one in the latest version.
+        #[ink(message)]
+        pub fn xcm_version(&self, dest_chain: u32) -> Option<u32> {
+            self.xcm_versions.version_of(&Location::new(1, Parachain(dest_chain)))
+        }
 
-            self.env().emit_event(XcmRequested { id: self.id, query_id, hash });
+        /// Subscribes to the XCM version of `dest_chain`, which is set once reported. Only callable
+        /// by the owner.
+        #[ink(message)]
+        pub fn subscribe_xcm_version(&mut self, dest_chain: u32) -> Result<()> {
+            self.access.ensure_owner(self.env().caller()).map_err(|_| UNAUTHORIZED)?;
+            let dest = Location::new(1, Parachain(dest_chain));
+            let (id, response) = self.new_query(dest.clone())?;
+            self.xcm_versions.subscribe(id, dest.clone());
+            self.send(dest, subscribe_xcm(response.query_id, response.max_weight))?;
+            Ok(())
+        }
+
+        /// Sets the XCM version of `dest_chain`, e.g. if it does not permit subscriptions by the
+        /// contract. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_xcm_version(&mut self, dest_chain: u32, version: u32) -> Result<()> {
+            self.access.ensure_owner(self.env().caller()).map_err(|_| UNAUTHORIZED)?;
+            self.xcm_versions.set_version(Location::new(1, Parachain(dest_chain)), version);
             Ok(())
         }
 
+        #[ink(message)]
+        pub fn poll(&self, id: MessageId) -> Result<Option<Status>> {
+            api::poll((self.env().account_id(), id))
+        }
+
         #[ink(message)]
         pub fn complete(&mut self, id: MessageId) -> Result<()> {
             if let Ok(Some(status)) = api::poll((self.env().account_id(), id)) {
                 if status == Status::Complete {
                     let result = api::get((self.env().account_id(), id))?;
-                    api::remove([id].to_vec())?;
+                    self.ids.reclaim(self.env().account_id(), [id].to_vec())?;
+                    self.refund(id)?;
                     self.env().emit_event(Completed { id, result });
                 }
             }
             Ok(())
         }
 
+        // Registers a query for a response from `dest`, returning the id of its message and where
+        // to report the response to.
+        fn new_query(&mut self, dest: Location) -> Result<(MessageId, QueryResponseInfo)> {
+            let timeout = self.env().block_number().saturating_add(100);
+            let (id, query_id) = self.ids.send(|id| {
+                api::xcm::new_query(
+                    id,
+                    dest.clone(),
+                    timeout,
+                    Some(Callback::to(0x641b0b03, Weight::from_parts(800_000_000, 500_000))),
+                )
+            })?;
+            let query_id = query_id.ok_or(StatusCode::from(NO_QUERY))?;
+
+            // TODO: provide an api function for determining the local para id and max weight value
+            // for processing the reported response on the local chain.
+            let response = QueryResponseInfo {
+                // Route back to this parachain.
+                destination: Location::new(1, Parachain(POP)),
+                query_id,
+                max_weight: Weight::from_parts(1_000_000, 5_000),
+            };
+            Ok((id, response))
+        }
+
+        // Sends `message` to `dest` in the XCM version of `dest`.
+        fn send(&self, dest: Location, message: Xcm<()>) -> Result<XcmHash> {
+            let (dest, message) = self
+                .xcm_versions
+                .versioned(dest, message)
+                .map_err(|_| StatusCode::from(UNSUPPORTED_XCM_VERSION))?;
+            api::xcm::send(&dest, &message)
+        }
+
+        // Refunds the surplus paid for the request `id` to its payer, if any.
+        fn refund(&mut self, id: MessageId) -> Result<()> {
+            if let Some((payer, amount)) = self.payments.take(id) {
+                self.env().transfer(payer, amount).map_err(|_| StatusCode::from(REFUND_FAILED))?;
+                self.env().emit_event(FeeRefunded { id, payer, amount });
+            }
+            Ok(())
+        }
+
         fn _transact(
             &self,
             call: DoubleEncoded<()>,
             weight: Weight,
             fees: Asset,
             response: QueryResponseInfo,
+            refunds: (QueryResponseInfo, QueryResponseInfo),
         ) -> Xcm<()> {
-            let beneficiary = hashed_account(4_001, self.env().account_id()); // todo: para id getter
-            Xcm::builder_unsafe()
-                .withdraw_asset(fees.clone().into())
-                .buy_execution(fees, WeightLimit::Unlimited)
-                .set_appendix(
-                    Xcm::builder_unsafe()
-                        .refund_surplus()
-                        .deposit_asset(
-                            All.into(),
-                            Location::new(0, AccountId32 { network: None, id: beneficiary.0 }),
-                        )
-                        .build(),
-                )
-                .set_error_handler(Xcm::builder_unsafe().report_error(response.clone()).build())
-                .transact(OriginKind::SovereignAccount, weight, call)
-                .report_transact_status(response)
-                .build()
+            let beneficiary = hashed_account(POP, self.env().account_id());
+            transact_xcm(call, weight, fees, response, refunds, beneficiary)
+        }
+    }
+
+    impl Upgradeable for Messaging {
+        #[ink(message)]
+        fn storage_version(&self) -> Version {
+            self.storage_version.get()
+        }
+
+        #[ink(message)]
+        fn set_code(&mut self, code_hash: Hash) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            self.storage_version.set_code(code_hash)
+        }
+
+        #[ink(message)]
+        fn migrate(&mut self) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            upgradeable::migrate(self).map(|_| ())
+        }
+    }
+
+    impl Migrate for Messaging {
+        const VERSION: Version = 1;
+
+        fn storage_version_mut(&mut self) -> &mut StorageVersion {
+            &mut self.storage_version
+        }
+
+        fn migrate_from(&mut self, from: Version) -> upgradeable::Result<()> {
+            // Code before the first version can't be upgraded, so its storage is never migrated.
+            Err(upgradeable::Error::UnsupportedVersion(from))
         }
     }
 
@@ -161,6 +467,7 @@ mod messaging {
             if self.env().caller() != self.env().account_id() {
                 return Err(UNAUTHORIZED.into());
             }
+            self.refund(id)?;
             self.env().emit_event(GetCompleted { id, values });
             Ok(())
         }
@@ -172,6 +479,16 @@ mod messaging {
             if self.env().caller() != self.env().account_id() {
                 return Err(UNAUTHORIZED.into());
             }
+            match &response {
+                // Credit the amount deposited by `fund` or left of the fees of `transact`.
+                Response::Assets(assets) => {
+                    self.escrow.report(id, assets, &AssetId(Location::parent()));
+                }
+                Response::Version(version) => {
+                    self.xcm_versions.report(id, *version);
+                }
+                _ => {}
+            }
             self.env().emit_event(XcmCompleted { id, result: response });
             Ok(())
         }
@@ -185,6 +502,27 @@ mod messaging {
         pub height: BlockNumber,
     }
 
+    #[ink::event]
+    pub struct FeePaid {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        /// The fee paid to relayers.
+        pub fee: Balance,
+        /// The amount paid over the fee, refunded once the request completed.
+        pub surplus: Balance,
+    }
+
+    #[ink::event]
+    pub struct FeeRefunded {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        pub amount: Balance,
+    }
+
     #[ink::event]
     pub struct Funded {
         #[ink(topic)]
@@ -192,6 +530,17 @@ mod messaging {
         pub value: Balance,
     }
 
+    #[ink::event]
+    pub struct Withdrawn {
+        /// The id of the message awaiting the report of completion.
+        #[ink(topic)]
+        pub id: MessageId,
+        pub dest_chain: u32,
+        pub amount: Balance,
+        #[ink(topic)]
+        pub beneficiary: AccountId,
+    }
+
     #[ink::event]
     pub struct XcmRequested {
         #[ink(topic)]
@@ -223,43 +572,539 @@ mod messaging {
         pub values: Vec<StorageValue>,
     }
 
-    // todo: make hasher generic and move to pop-api
-    fn hashed_account(para_id: u32, account_id: AccountId) -> AccountId {
-        let location = (
-            b"SiblingChain",
-            Compact::<u32>::from(para_id),
-            (b"AccountId32", account_id.0).encode(),
-        )
-            .encode();
-        let mut output = [0u8; 32];
-        Blake2x256::hash(&location, &mut output);
-        AccountId::from(output)
-    }
-
     #[cfg(test)]
     mod tests {
         use super::*;
+        use access_control::AccessControlled;
+        use ink::{
+            env::test,
+            scale::{Decode, Encode},
+            xcm::{
+                prelude::{DepositReserveAsset, InitiateReserveWithdraw, SubscribeVersion},
+                VersionedLocation,
+            },
+        };
+        use pop_api_mock::messaging::{Delivery, MockHost};
 
         #[ink::test]
         fn default_works() {
             Messaging::new(1_000);
         }
 
+        #[ink::test]
+        fn upgrade_requires_owner() {
+            let mut contract = Messaging::new(1_000).unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            assert_eq!(contract.storage_version(), 1);
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(contract.set_code(Hash::from([1; 32])), Err(upgradeable::Error::NotOwner));
+            assert_eq!(contract.migrate(), Err(upgradeable::Error::NotOwner));
+
+            // Instances of the code start at its version, so there is nothing to migrate.
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.migrate(), Ok(()));
+            assert_eq!(contract.storage_version(), 1);
+            assert_eq!(test::recorded_events().count(), 0);
+        }
+
+        #[ink::test]
+        fn complete_returns_and_removes_stored_responses() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            contract.escrow.credit(accounts.alice, 10);
+            assert_eq!(contract.withdraw(1_000, 10, accounts.bob), Ok(()));
+            assert_eq!(contract.poll(0), Ok(Some(Status::Pending)));
+
+            // Pending messages are not completed.
+            assert_eq!(contract.complete(0), Ok(()));
+            assert_eq!(contract.poll(0), Ok(Some(Status::Pending)));
+
+            // The response is stored as the callback ran out of weight.
+            host.set_callback_weight(Some(Weight::MAX));
+            let response = Response::ExecutionResult(None);
+            let commitment = host.commitment(0).unwrap();
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &commitment, response.clone()),
+                Ok(Delivery::Failed { .. })
+            ));
+            assert_eq!(contract.poll(0), Ok(Some(Status::Complete)));
+
+            // Completing the message returns the response and removes it, reclaiming its id.
+            assert_eq!(contract.complete(0), Ok(()));
+            let event = test::recorded_events().last().unwrap();
+            let completed = Completed::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((completed.id, completed.result), (0, Some(response.encode())));
+            assert_eq!(contract.poll(0), Ok(None));
+            contract.escrow.credit(accounts.alice, 10);
+            assert_eq!(contract.withdraw(1_000, 10, accounts.bob), Ok(()));
+            assert_eq!(host.requests()[1].id, 0);
+        }
+
+        #[ink::test]
+        fn xcm_version_is_negotiated() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            let withdraw_deposit = |contract: &mut Messaging| contract.withdraw_deposit(1);
+            assert_eq!(contract.xcm_version(1_000), None);
+            contract.escrow.credit(accounts.alice, 10);
+
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(contract.subscribe_xcm_version(1_000).map_err(|e| e.0), Err(UNAUTHORIZED));
+            assert_eq!(contract.set_xcm_version(1_000, 3).map_err(|e| e.0), Err(UNAUTHORIZED));
+
+            // The version reported by the destination is used for later programs.
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.subscribe_xcm_version(1_000).map_err(|e| e.0), Ok(()));
+            assert!(matches!(
+                &host.sent()[0].2,
+                VersionedXcm::V4(message) if matches!(message.0[..], [SubscribeVersion { .. }])
+            ));
+            let commitment = host.commitment(0).unwrap();
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &commitment, Response::Version(3)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.xcm_version(1_000), Some(3));
+            assert!(withdraw_deposit(&mut contract).is_ok());
+            assert!(matches!(host.sent()[1], (_, VersionedLocation::V3(_), VersionedXcm::V3(_))));
+
+            // Programs are not sent to destinations with versions which are not supported.
+            assert_eq!(contract.set_xcm_version(1_000, 2).map_err(|e| e.0), Ok(()));
+            assert_eq!(
+                withdraw_deposit(&mut contract).map_err(|e| e.0),
+                Err(UNSUPPORTED_XCM_VERSION)
+            );
+            assert_eq!(host.sent().len(), 2);
+        }
+
+        #[ink::test]
+        fn transact_requires_role() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            let transact = |contract: &mut Messaging| {
+                let call = Vec::from([0, 0, 4, 1]).into();
+                contract.transact(call, Weight::from_parts(1_000, 1_000), 10)
+            };
+            contract.escrow.credit(accounts.bob, 10);
+
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(transact(&mut contract).map_err(|e| e.0), Err(UNAUTHORIZED));
+            assert!(host.sent().is_empty());
+
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.grant_role(TRANSACT, accounts.bob), Ok(()));
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(transact(&mut contract).map_err(|e| e.0), Ok(()));
+            assert_eq!(host.sent().len(), 1);
+        }
+
+        #[ink::test]
+        fn report_fee_is_not_credited() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let bob = test::default_accounts::<Environment>().bob;
+            let respond = |contract: &mut Messaging, id: MessageId, amount: Balance| {
+                let asset: Asset = (Location::parent(), amount).into();
+                let commitment = host.commitment(id).unwrap();
+                host.respond_xcm(contract, &commitment, Response::Assets(Vec::from([asset]).into()))
+            };
+
+            // The destination pays the delivery fee of each report from the holding reported, so
+            // the second report is less by the fee of the first.
+            test::set_caller::<Environment>(bob);
+            test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.fund().map_err(|e| e.0), Ok(()));
+            assert!(matches!(respond(&mut contract, 0, 90), Ok(Delivery::Executed { .. })));
+            assert_eq!(contract.deposit_of(bob), 0);
+            assert!(matches!(respond(&mut contract, 1, 85), Ok(Delivery::Executed { .. })));
+            assert_eq!(contract.deposit_of(bob), 80);
+        }
+
+        #[ink::test]
+        fn transact_spends_deposit() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let bob = test::default_accounts::<Environment>().bob;
+            let reported = |amount: Balance| {
+                let asset: Asset = (Location::parent(), amount).into();
+                Response::Assets(Vec::from([asset]).into())
+            };
+            let respond = |contract: &mut Messaging, id: MessageId, response: Response| {
+                let commitment = host.commitment(id).unwrap();
+                host.respond_xcm(contract, &commitment, response)
+            };
+            let transact = |contract: &mut Messaging, fees: Balance| {
+                let call = Vec::from([0, 0, 4, 1]).into();
+                contract.transact(call, Weight::from_parts(1_000, 1_000), fees)
+            };
+
+            assert_eq!(contract.grant_role(TRANSACT, bob), Ok(()));
+            test::set_caller::<Environment>(bob);
+
+            // The deposit is credited once the destination reported the amount deposited.
+            test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.fund().map_err(|e| e.0), Ok(()));
+            assert_eq!(host.executed().len(), 1);
+            assert_eq!(contract.deposit_of(bob), 0);
+            assert!(matches!(
+                respond(&mut contract, 0, reported(94)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert!(matches!(
+                respond(&mut contract, 1, reported(92)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.deposit_of(bob), 90);
+
+            // Fees are debited upfront and what is left of them credited back.
+            test::set_value_transferred::<Environment>(0);
+            assert_eq!(transact(&mut contract, 91).map_err(|e| e.0), Err(INSUFFICIENT_DEPOSIT));
+            assert_eq!(transact(&mut contract, 60).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.deposit_of(bob), 30);
+            assert!(matches!(
+                respond(&mut contract, 3, reported(27)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert!(matches!(
+                respond(&mut contract, 4, reported(26)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.deposit_of(bob), 55);
+            // Only the queries awaiting the report are credited.
+            assert!(matches!(
+                respond(&mut contract, 2, reported(25)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.deposit_of(bob), 55);
+
+            // Deposits are withdrawn to the account of the caller on the destination.
+            test::set_caller::<Environment>(test::default_accounts::<Environment>().charlie);
+            assert_eq!(contract.withdraw_deposit(1).map_err(|e| e.0), Err(INSUFFICIENT_DEPOSIT));
+            test::set_caller::<Environment>(bob);
+            assert_eq!(contract.withdraw_deposit(55).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.deposit_of(bob), 0);
+            assert_eq!(host.sent().len(), 2);
+        }
+
+        #[ink::test]
+        fn withdraw_works() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let bob = test::default_accounts::<Environment>().bob;
+            let sent = |index: usize| {
+                let VersionedXcm::V4(message) = host.sent()[index].2.clone() else {
+                    panic!("unexpected version")
+                };
+                message
+            };
+
+            // Withdrawing from the destination spends the deposit of the caller.
+            test::set_caller::<Environment>(bob);
+            test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.fund().map_err(|e| e.0), Ok(()));
+            let assets: Asset = (Location::parent(), 100).into();
+            let response = Response::Assets(Vec::from([assets]).into());
+            for id in [0, 1] {
+                let commitment = host.commitment(id).unwrap();
+                assert!(matches!(
+                    host.respond_xcm(&mut contract, &commitment, response.clone()),
+                    Ok(Delivery::Executed { .. })
+                ));
+            }
+            test::set_value_transferred::<Environment>(0);
+            assert_eq!(
+                contract.withdraw(1_000, 101, bob).map_err(|e| e.0),
+                Err(INSUFFICIENT_DEPOSIT)
+            );
+            assert_eq!(contract.withdraw(1_000, 100, bob).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.deposit_of(bob), 0);
+            // The reserve transfers the assets back directly.
+            assert!(matches!(sent(0).0.last(), Some(DepositReserveAsset { .. })));
+
+            // Only the owner withdraws from other chains.
+            assert_eq!(contract.withdraw(2_034, 100, bob).map_err(|e| e.0), Err(UNAUTHORIZED));
+            test::set_caller::<Environment>(test::default_accounts::<Environment>().alice);
+            assert_eq!(contract.withdraw(2_034, 100, bob).map_err(|e| e.0), Ok(()));
+            assert!(matches!(sent(1).0.last(), Some(InitiateReserveWithdraw { .. })));
+
+            // Completion is reported to the query of the withdrawal.
+            let commitment = host.commitment(3).unwrap();
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &commitment, Response::ExecutionResult(None)),
+                Ok(Delivery::Executed { .. })
+            ));
+        }
+
+        #[ink::test]
+        fn get_pays_fee() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let bob = test::default_accounts::<Environment>().bob;
+            assert_eq!(contract.set_min_fee(1_000, 100).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.update_height(1).map_err(|e| e.0), Ok(()));
+
+            test::set_caller::<Environment>(bob);
+            assert_eq!(contract.set_min_fee(1_000, 0).map_err(|e| e.0), Err(UNAUTHORIZED));
+            test::set_value_transferred::<Environment>(99);
+            assert_eq!(
+                contract.get(b"key".to_vec(), Height::Latest).map_err(|e| e.0),
+                Err(INSUFFICIENT_FEE)
+            );
+
+            // The value transferred is credited to the contract.
+            test::set_value_transferred::<Environment>(120);
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 120);
+            test::set_account_balance::<Environment>(bob, 0);
+            assert_eq!(contract.get(b"key".to_vec(), Height::Latest).map_err(|e| e.0), Ok(()));
+            let request = &host.requests()[0];
+            assert_eq!(request.fee, 100);
+            let event = test::recorded_events().next().unwrap();
+            let paid = FeePaid::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((paid.id, paid.payer, paid.fee, paid.surplus), (0, bob, 100, 20));
+
+            // The surplus is refunded once the response arrived.
+            assert!(matches!(
+                host.respond_get(&mut contract, &request.commitment, Vec::new()),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(test::get_account_balance::<Environment>(bob).unwrap(), 20);
+        }
+
+        #[ink::test]
+        fn get_checks_height() {
+            let host = MockHost::register();
+            let mut contract = Messaging::new(1_000).unwrap();
+            let get = |contract: &mut Messaging, height| {
+                contract.get(b"key".to_vec(), height).map_err(|e| e.0)
+            };
+            assert_eq!(get(&mut contract, Height::Latest), Err(UNKNOWN_HEIGHT));
+
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            // Lower heights are ignored.
+            assert_eq!(contract.update_height(9).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.latest_height(), Some(10));
+            assert_eq!(get(&mut contract, Height::At(11)), Err(HEIGHT_AHEAD));
+            assert_eq!(get(&mut contract, Height::Latest), Ok(()));
+            assert_eq!(get(&mut contract, Height::At(5)), Ok(()));
+            let heights: Vec<_> =
+                host.requests().iter().map(|request| request.get().unwrap().height).collect();
+            assert_eq!(heights, [10, 5]);
+
+            test::set_caller::<Environment>(test::default_accounts::<Environment>().bob);
+            assert_eq!(contract.update_height(11).map_err(|e| e.0), Err(UNAUTHORIZED));
+        }
+
         #[test]
         fn it_works() {
             let account_id: [u8; 32] = [
                 27, 2, 24, 17, 104, 5, 173, 98, 25, 32, 36, 0, 82, 159, 11, 212, 178, 11, 39, 219,
                 14, 178, 226, 179, 216, 62, 19, 85, 226, 17, 80, 179,
             ];
-            let location = (
-                b"SiblingChain",
-                Compact::<u32>::from(4001),
-                (b"AccountId32", account_id).encode(),
-            )
-                .encode();
-            let mut output = [0u8; 32];
-            Blake2x256::hash(&location, &mut output);
+            let output = hashed_account(POP, AccountId::from(account_id));
             println!("{output:?}")
         }
     }
+
+    /// End-to-end tests against a local network of Pop and Asset Hub, see the README.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink::scale::{Decode, Encode};
+        use ink_e2e::{account_id, alice, AccountKeyring, ContractsBackend, E2EBackend};
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        // 1 PAS.
+        const UNIT: Balance = 10_000_000_000;
+
+        /// Decodes the events emitted by the contract as `T`.
+        fn emitted<T: Decode>(events: impl IntoIterator<Item = Vec<u8>>) -> Vec<T> {
+            events
+                .into_iter()
+                .filter_map(|data| T::decode(&mut &data[..]).ok())
+                .collect()
+        }
+
+        async fn instantiate<Client: E2EBackend>(client: &mut Client) -> AccountId {
+            let mut constructor = MessagingRef::new(ASSET_HUB);
+            client
+                .instantiate("messaging", &alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("instantiate failed")
+                .account_id
+        }
+
+        async fn poll<Client: E2EBackend>(
+            client: &mut Client,
+            contract: AccountId,
+            id: MessageId,
+        ) -> Option<Status> {
+            let call_builder = ink_e2e::create_call_builder::<Messaging>(contract);
+            client
+                .call(&alice(), &call_builder.poll(id))
+                .dry_run()
+                .await
+                .expect("poll failed")
+                .return_value()
+                .expect("poll errored")
+        }
+
+        /// Polls message `id` until it is no longer pending, returning its final status.
+        async fn wait_for_response<Client: E2EBackend>(
+            client: &mut Client,
+            contract: AccountId,
+            id: MessageId,
+        ) -> Option<Status> {
+            for _ in 0..50 {
+                let status = poll(client, contract, id).await;
+                if !matches!(status, Some(Status::Pending)) {
+                    return status;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(6)).await;
+            }
+            panic!("no response to message {id}");
+        }
+
+        #[ink_e2e::test]
+        async fn get_works<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let contract = instantiate(&mut client).await;
+            let mut call_builder = ink_e2e::create_call_builder::<Messaging>(contract);
+
+            client
+                .call(&alice(), &call_builder.update_height(1))
+                .submit()
+                .await
+                .expect("update_height failed");
+            let key = b":code".to_vec();
+            let result = client
+                .call(&alice(), &call_builder.get(key.clone(), Height::Latest))
+                .submit()
+                .await
+                .expect("get failed");
+            let requested: Vec<IsmpRequested> = emitted(
+                result
+                    .contract_emitted_events()?
+                    .into_iter()
+                    .map(|e| e.event.data),
+            );
+            assert_eq!(requested.len(), 1);
+            assert_eq!(
+                (requested[0].id, &requested[0].key, requested[0].height),
+                (0, &key, 1)
+            );
+            assert!(matches!(
+                poll(&mut client, contract, 0).await,
+                Some(Status::Pending)
+            ));
+
+            // Without a relayer the request stays pending, so completing it does nothing.
+            let result = client
+                .call(&alice(), &call_builder.complete(0))
+                .submit()
+                .await
+                .expect("complete failed");
+            assert!(emitted::<Completed>(
+                result
+                    .contract_emitted_events()?
+                    .into_iter()
+                    .map(|e| e.event.data)
+            )
+            .is_empty());
+            assert!(matches!(
+                poll(&mut client, contract, 0).await,
+                Some(Status::Pending)
+            ));
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn fund_works<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+            let contract = instantiate(&mut client).await;
+            let mut call_builder = ink_e2e::create_call_builder::<Messaging>(contract);
+
+            let value = 10 * UNIT;
+            let result = client
+                .call(&alice(), &call_builder.fund())
+                .value(value)
+                .submit()
+                .await
+                .expect("fund failed");
+            let funded: Vec<Funded> = emitted(
+                result
+                    .contract_emitted_events()?
+                    .into_iter()
+                    .map(|e| e.event.data),
+            );
+            assert_eq!(funded.len(), 1);
+            assert_eq!(funded[0].account_id, hashed_account(POP, contract));
+            assert_eq!(funded[0].value, value);
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn transact_and_complete_works<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let contract = instantiate(&mut client).await;
+            let mut call_builder = ink_e2e::create_call_builder::<Messaging>(contract);
+            // Fund the contract's account on Asset Hub to pay for execution, credited to alice once
+            // Asset Hub reported the amount deposited to both queries.
+            client
+                .call(&alice(), &call_builder.fund())
+                .value(10 * UNIT)
+                .submit()
+                .await
+                .expect("fund failed");
+            wait_for_response(&mut client, contract, 0).await;
+            wait_for_response(&mut client, contract, 1).await;
+            let deposit = client
+                .call(&alice(), &call_builder.deposit_of(account_id(AccountKeyring::Alice)))
+                .dry_run()
+                .await
+                .expect("deposit_of failed")
+                .return_value();
+            assert!(deposit > UNIT);
+
+            // `System::remark_with_event` on Asset Hub.
+            let call = [&[0u8, 7][..], &b"interpop".to_vec().encode()].concat();
+            let weight = Weight::from_parts(1_000_000_000, 100_000);
+            let result = client
+                .call(&alice(), &call_builder.transact(call.into(), weight, UNIT))
+                .submit()
+                .await
+                .expect("transact failed");
+            let requested: Vec<XcmRequested> = emitted(
+                result
+                    .contract_emitted_events()?
+                    .into_iter()
+                    .map(|e| e.event.data),
+            );
+            assert_eq!(requested.len(), 1);
+            let id = requested[0].id;
+            assert_eq!(id, 2);
+
+            // Asset Hub reports the transact status back to the contract, whose callback handles
+            // it, so the runtime removes the message rather than storing the response.
+            assert!(wait_for_response(&mut client, contract, id).await.is_none());
+            let result = client
+                .call(&alice(), &call_builder.complete(id))
+                .submit()
+                .await
+                .expect("complete failed");
+            let completed: Vec<Completed> = emitted(
+                result
+                    .contract_emitted_events()?
+                    .into_iter()
+                    .map(|e| e.event.data),
+            );
+            // No response is stored, so there is nothing to complete.
+            assert!(completed.is_empty());
+            assert!(poll(&mut client, contract, id).await.is_none());
+            Ok(())
+        }
+    }
 }
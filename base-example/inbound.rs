@@ -0,0 +1,84 @@
+//! Experimental handling of posts sent to a contract by modules on other chains, enabled with the
+//! `experimental-inbound` feature.
+//!
+//! Unlike responses, these posts are not initiated by the contract. `pop_api::messaging` has no
+//! path delivering inbound posts to contracts yet, so the contract of this example does not
+//! implement [`OnPostRequest`]: nothing could call it. The interface assumes the runtime would call
+//! [`OnPostRequest::on_request`] on the contract addressed by the post, as the contract itself like
+//! the callbacks of `pop_api::messaging`, and that a contract only accepts posts from the sources
+//! on its [`Allowlist`]. Replies would be sent as posts to the source, see [`crate::rpc`].
+
+use ink::{prelude::vec::Vec, storage::Mapping};
+use pop_api::messaging::ParaId;
+
+use crate::{rpc::Nonce, Result};
+
+/// A post sent to the contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub struct InboundPost {
+    /// The chain the post was sent from.
+    pub source: ParaId,
+    /// The module which sent the post on the source chain.
+    pub from: Vec<u8>,
+    /// The nonce of the post on the source chain.
+    pub nonce: Nonce,
+    pub body: Vec<u8>,
+}
+
+/// Handles posts sent to the contract.
+#[ink::trait_definition]
+pub trait OnPostRequest {
+    /// Handles `post`, failing if it is rejected.
+    #[ink(message)]
+    fn on_request(&mut self, post: InboundPost) -> Result<()>;
+}
+
+/// The sources permitted to send posts to a contract: either every module of a chain or a single
+/// module on it.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct Allowlist {
+    sources: Mapping<(ParaId, Option<Vec<u8>>), ()>,
+}
+
+impl Allowlist {
+    /// Permits `module` on `source` to send posts, or every module if `None`.
+    pub fn allow(&mut self, source: ParaId, module: Option<Vec<u8>>) {
+        self.sources.insert((source, module), &());
+    }
+
+    /// Revokes a permission granted by [`Allowlist::allow`].
+    pub fn disallow(&mut self, source: ParaId, module: Option<Vec<u8>>) {
+        self.sources.remove((source, module));
+    }
+
+    /// Returns whether `module` on `source` is permitted to send posts.
+    pub fn allows(&self, source: ParaId, module: &[u8]) -> bool {
+        self.sources.contains((source, None::<Vec<u8>>))
+            || self.sources.contains((source, Some(module.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ink::test]
+    fn allowlist_works() {
+        let mut allowlist = Allowlist::default();
+        assert!(!allowlist.allows(2034, b"module"));
+
+        allowlist.allow(2034, Some(b"module".to_vec()));
+        assert!(allowlist.allows(2034, b"module"));
+        assert!(!allowlist.allows(2034, b"other"));
+        assert!(!allowlist.allows(1000, b"module"));
+
+        // Allowing a chain allows every module on it.
+        allowlist.allow(2034, None);
+        assert!(allowlist.allows(2034, b"other"));
+        allowlist.disallow(2034, None);
+        assert!(!allowlist.allows(2034, b"other"));
+        assert!(allowlist.allows(2034, b"module"));
+    }
+}
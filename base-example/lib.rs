@@ -6,23 +6,36 @@ use pop_api::{
         self as api,
         ismp::{Get, Post},
         xcm::{Location, QueryId, Response},
-        Callback, MessageId, Status,
+        Callback, MessageId, ParaId, Status,
     },
     StatusCode,
 };
+use rpc::{ErrorCode, MethodId, Nonce};
+
+#[cfg(feature = "experimental-inbound")]
+pub mod inbound;
+pub mod rpc;
 
 pub type Result<T> = core::result::Result<T, StatusCode>;
 
 #[ink::contract]
 mod messaging {
-    use ink::xcm::prelude::Weight;
+    use ink::{storage::Mapping, xcm::prelude::Weight};
     use pop_api::messaging::ismp::StorageValue;
 
     use super::*;
 
+    /// The response of a remote module could not be decoded or does not match the request.
+    const INVALID_RESPONSE: u32 = u32::MAX;
+
     #[ink(storage)]
     #[derive(Default)]
-    pub struct Contract;
+    pub struct Contract {
+        /// The nonce of the next call of a remote module.
+        nonce: Nonce,
+        /// The method and nonce of pending calls of remote modules.
+        calls: Mapping<MessageId, (MethodId, Nonce)>,
+    }
 
     impl Contract {
         #[ink(constructor, payable)]
@@ -82,6 +95,42 @@ mod messaging {
             Ok(())
         }
 
+        /// Calls `method` of a module on `dest` with the SCALE encoded `payload`, returning the nonce
+        /// of the request. The response is emitted as `IsmpCallCompleted`.
+        #[ink(message)]
+        pub fn ismp_call(
+            &mut self,
+            id: MessageId,
+            dest: ParaId,
+            timeout: u64,
+            method: MethodId,
+            payload: Vec<u8>,
+            fee: Balance,
+        ) -> Result<Nonce> {
+            let nonce = self.nonce;
+            debug_println!(
+                "messaging::ismp_call id={id}, dest={dest}, timeout={timeout}, method={method:?}, \
+                 nonce={nonce}, fee={fee}"
+            );
+            let data = rpc::encode_request(&rpc::Request {
+                method,
+                nonce,
+                payload,
+            });
+            api::ismp::post(
+                id,
+                Post::new(dest, timeout, data),
+                fee,
+                Some(
+                    // See `api::ismp::OnPostResponse` impl below
+                    Callback::to(0xcfb0a1d2, Weight::from_parts(800_000_000, 150_000)),
+                ),
+            )?;
+            self.calls.insert(id, &(method, nonce));
+            self.nonce = nonce.wrapping_add(1);
+            Ok(nonce)
+        }
+
         #[ink(message)]
         pub fn xcm_new_query(
             &mut self,
@@ -121,6 +170,7 @@ mod messaging {
         pub fn remove(&mut self, id: MessageId) -> Result<()> {
             debug_println!("messaging::remove id={id}");
             api::remove([id].to_vec())?;
+            self.calls.remove(id);
             Ok(())
         }
     }
@@ -138,7 +188,22 @@ mod messaging {
         #[ink(message)]
         fn on_response(&mut self, id: MessageId, response: Vec<u8>) -> Result<()> {
             debug_println!("messaging::ismp::post::on_response id={id}, response={response:?});");
-            self.env().emit_event(IsmpPostCompleted { id, response });
+            match self.calls.take(id) {
+                // The response of a remote module, see `ismp_call`. An invalid response fails the
+                // callback, leaving it to be retrieved using `get`.
+                Some((method, nonce)) => {
+                    let response = rpc::decode_response(&response)
+                        .ok()
+                        .filter(|response| response.nonce == nonce)
+                        .ok_or(StatusCode::from(INVALID_RESPONSE))?;
+                    self.env().emit_event(IsmpCallCompleted {
+                        id,
+                        method,
+                        result: response.result,
+                    });
+                }
+                None => self.env().emit_event(IsmpPostCompleted { id, response }),
+            }
             Ok(())
         }
     }
@@ -174,6 +239,14 @@ mod messaging {
         pub response: Vec<u8>,
     }
 
+    #[ink::event]
+    pub struct IsmpCallCompleted {
+        #[ink(topic)]
+        pub id: MessageId,
+        pub method: MethodId,
+        pub result: core::result::Result<Vec<u8>, ErrorCode>,
+    }
+
     #[ink::event]
     pub struct XcmCompleted {
         #[ink(topic)]
@@ -184,10 +257,174 @@ mod messaging {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use ink::scale::{Decode, Encode};
+        use pop_api_mock::messaging::{
+            Delivery, Error as MockError, MockHost, Status as MockStatus, MESSAGE_EXISTS,
+        };
+
+        fn get_request() -> Get {
+            Get::new(2034, 100, 0, Vec::default(), Vec::from([b"key".to_vec()]))
+        }
+
+        fn values() -> Vec<StorageValue> {
+            Vec::from([StorageValue {
+                key: b"key".to_vec(),
+                value: Some(b"value".to_vec()),
+            }])
+        }
 
         #[ink::test]
         fn default_works() {
             Contract::new();
         }
+
+        #[ink::test]
+        fn ismp_get_with_callback_works() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            assert_eq!(contract.ismp_get(1, get_request(), 100, true), Ok(()));
+            let message = &host.requests()[0];
+            let request = message.get().unwrap();
+            assert_eq!(
+                (request.dest, request.height, message.fee),
+                (2034, 100, 100)
+            );
+
+            let commitment = host.commitment(1).unwrap();
+            assert_eq!(
+                host.respond_get(&mut contract, &commitment, values()),
+                Ok(Delivery::Executed {
+                    weight: Weight::from_parts(900_000_000, 150_000)
+                })
+            );
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+            // The message is removed once the callback succeeds.
+            assert_eq!(contract.poll(1).map(|s| s.is_none()), Ok(true));
+        }
+
+        #[ink::test]
+        fn ismp_get_without_callback_stores_response() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            assert_eq!(contract.ismp_get(1, get_request(), 100, false), Ok(()));
+            assert_eq!(
+                contract
+                    .ismp_get(1, get_request(), 100, false)
+                    .map_err(|e| e.0),
+                Err(MESSAGE_EXISTS)
+            );
+
+            let commitment = host.commitment(1).unwrap();
+            assert_eq!(
+                host.respond_get(&mut contract, &commitment, values()),
+                Ok(Delivery::Stored)
+            );
+            assert_eq!(ink::env::test::recorded_events().count(), 0);
+            let account = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let message = host.message(account, 1).unwrap();
+            assert_eq!(message.status, MockStatus::Complete);
+            assert_eq!(contract.get(1), Ok(Some(values().encode())));
+
+            assert_eq!(contract.remove(1), Ok(()));
+            assert_eq!(contract.get(1), Ok(None));
+        }
+
+        #[ink::test]
+        fn ismp_post_callback_out_of_weight_stores_response() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            let request = Post::new(2034, 0, b"data".to_vec());
+            assert_eq!(contract.ismp_post(1, request, 100, true), Ok(()));
+            assert!(host.requests()[0].post().is_some());
+
+            // The callback needs more than the weight registered with the request.
+            host.set_callback_weight(Some(Weight::from_parts(900_000_000, 150_000)));
+            let commitment = host.commitment(1).unwrap();
+            assert_eq!(
+                host.respond_post(&mut contract, &commitment, b"response".to_vec()),
+                Ok(Delivery::Failed {
+                    weight: Weight::from_parts(800_000_000, 150_000)
+                })
+            );
+            assert_eq!(ink::env::test::recorded_events().count(), 0);
+            assert_eq!(contract.get(1), Ok(Some(b"response".to_vec())));
+            assert_eq!(
+                host.weight_consumed(),
+                Weight::from_parts(800_000_000, 150_000)
+            );
+        }
+
+        #[ink::test]
+        fn timed_out_request_can_not_be_responded_to() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            assert_eq!(contract.ismp_get(1, get_request(), 100, true), Ok(()));
+            let commitment = host.commitment(1).unwrap();
+            assert_eq!(host.time_out(&commitment), Ok(()));
+            assert_eq!(
+                host.respond_get(&mut contract, &commitment, values()),
+                Err(MockError::NotPending)
+            );
+        }
+
+        #[ink::test]
+        fn ismp_call_works() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            assert_eq!(
+                contract.ismp_call(1, 2034, 0, *b"ping", b"args".to_vec(), 100),
+                Ok(0)
+            );
+            assert_eq!(
+                contract.ismp_call(2, 2034, 0, *b"ping", Vec::new(), 100),
+                Ok(1)
+            );
+            let request = host.requests()[0].post().unwrap();
+            assert_eq!(
+                rpc::decode_request(&request.data),
+                Ok(rpc::Request {
+                    method: *b"ping",
+                    nonce: 0,
+                    payload: b"args".to_vec(),
+                })
+            );
+
+            let response = rpc::encode_response(&rpc::Response {
+                nonce: 0,
+                result: Ok(b"pong".to_vec()),
+            });
+            let commitment = host.commitment(1).unwrap();
+            assert!(matches!(
+                host.respond_post(&mut contract, &commitment, response),
+                Ok(Delivery::Executed { .. })
+            ));
+            let event = ink::env::test::recorded_events().last().unwrap();
+            let event = IsmpCallCompleted::decode(&mut &event.data[..]).unwrap();
+            assert_eq!(
+                (event.id, event.method, event.result),
+                (1, *b"ping", Ok(b"pong".to_vec()))
+            );
+        }
+
+        #[ink::test]
+        fn ismp_call_rejects_mismatched_response() {
+            let host = MockHost::register();
+            let mut contract = Contract::new();
+            assert_eq!(
+                contract.ismp_call(1, 2034, 0, *b"ping", Vec::new(), 100),
+                Ok(0)
+            );
+            let response = rpc::encode_response(&rpc::Response {
+                nonce: 1,
+                result: Ok(Vec::new()),
+            });
+            let commitment = host.commitment(1).unwrap();
+            assert!(matches!(
+                host.respond_post(&mut contract, &commitment, response.clone()),
+                Ok(Delivery::Failed { .. })
+            ));
+            assert_eq!(ink::env::test::recorded_events().count(), 0);
+            assert_eq!(contract.get(1), Ok(Some(response)));
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,130 @@
+//! A request/response protocol for calling modules on other chains over ISMP.
+//!
+//! A request is sent as the data of a `Post` and the remote module replies with the response as
+//! the body of its `PostResponse`. Both are SCALE encoded and prefixed with the version of the
+//! protocol, so the envelope can evolve without breaking older counterparts.
+
+use ink::{
+    prelude::vec::Vec,
+    scale::{Decode, DecodeAll, Encode},
+};
+
+/// The version of the protocol.
+pub const VERSION: u8 = 1;
+
+/// Identifies the method called on the remote module, e.g. the first bytes of a hash of its name.
+pub type MethodId = [u8; 4];
+/// Correlates a response with its request.
+pub type Nonce = u64;
+/// An error code returned by the remote module.
+pub type ErrorCode = u32;
+
+/// A call of `method` on a remote module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub struct Request {
+    pub method: MethodId,
+    pub nonce: Nonce,
+    /// The SCALE encoded arguments of the method.
+    pub payload: Vec<u8>,
+}
+
+/// The reply of a remote module to the request with `nonce`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub struct Response {
+    pub nonce: Nonce,
+    /// The SCALE encoded output of the method, or the error it failed with.
+    pub result: core::result::Result<Vec<u8>, ErrorCode>,
+}
+
+/// Errors decoding a message of the protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// The message is empty, so has no version.
+    Empty,
+    /// The message uses a version of the protocol which is not supported.
+    UnsupportedVersion(u8),
+    /// The message could not be decoded.
+    DecodingFailed,
+}
+
+/// Encodes `request` as the data of a `Post`.
+pub fn encode_request(request: &Request) -> Vec<u8> {
+    encode(request)
+}
+
+/// Decodes a request from the data of a `Post`.
+pub fn decode_request(data: &[u8]) -> Result<Request, Error> {
+    decode(data)
+}
+
+/// Encodes `response` as the body of a `PostResponse`.
+pub fn encode_response(response: &Response) -> Vec<u8> {
+    encode(response)
+}
+
+/// Decodes a response from the body of a `PostResponse`.
+pub fn decode_response(body: &[u8]) -> Result<Response, Error> {
+    decode(body)
+}
+
+fn encode(message: &impl Encode) -> Vec<u8> {
+    let mut encoded = Vec::from([VERSION]);
+    message.encode_to(&mut encoded);
+    encoded
+}
+
+fn decode<T: Decode>(message: &[u8]) -> Result<T, Error> {
+    match message.split_first() {
+        None => Err(Error::Empty),
+        Some((&VERSION, mut message)) => {
+            T::decode_all(&mut message).map_err(|_| Error::DecodingFailed)
+        }
+        Some((version, _)) => Err(Error::UnsupportedVersion(*version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> Request {
+        Request {
+            method: *b"ping",
+            nonce: 1,
+            payload: b"payload".to_vec(),
+        }
+    }
+
+    #[test]
+    fn request_roundtrips() {
+        let data = encode_request(&request());
+        assert_eq!(data[0], VERSION);
+        assert_eq!(decode_request(&data), Ok(request()));
+    }
+
+    #[test]
+    fn response_roundtrips() {
+        for result in [Ok(b"pong".to_vec()), Err(3)] {
+            let response = Response { nonce: 1, result };
+            assert_eq!(decode_response(&encode_response(&response)), Ok(response));
+        }
+    }
+
+    #[test]
+    fn decode_rejects_invalid_messages() {
+        assert_eq!(decode_response(&[]), Err(Error::Empty));
+        let mut data = encode_request(&request());
+        data[0] = VERSION + 1;
+        assert_eq!(
+            decode_request(&data),
+            Err(Error::UnsupportedVersion(VERSION + 1))
+        );
+        // Trailing bytes are not part of the protocol.
+        let mut data = encode_request(&request());
+        data.push(0);
+        assert_eq!(decode_request(&data), Err(Error::DecodingFailed));
+    }
+}
@@ -1,12 +1,15 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+use access_control::{AccessControl, Role};
 use ink::{
-    env::hash::{Blake2x128, Blake2x256, CryptoHash},
+    env::hash::{Blake2x128, CryptoHash},
     prelude::vec::Vec,
-    scale::{Compact, Encode},
+    scale::Encode,
     storage::Mapping,
     xcm::prelude::*,
 };
+use ismp_heights::{Height, Heights};
+use message_ids::MessageIds;
 use pop_api::{
     messaging::{
         ismp::{self, Get, StorageValue},
@@ -18,9 +21,15 @@ use pop_api::{
     },
     StatusCode,
 };
+use remote_calls::{Dispatch, ASSET_HUB};
+use repatriation::{hashed_account, repatriate_xcm, POP};
+use upgradeable::{Migrate, StorageVersion, Upgradeable, Version};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// The role required to transact on the parachain, spending the balance of the contract there.
+pub const TRANSACT: Role = 1;
+
 #[derive(Debug, PartialEq, Eq)]
 #[ink::scale_derive(Encode, Decode, TypeInfo)]
 pub enum Error {
@@ -31,6 +40,15 @@ pub enum Error {
     Rejected,
     Failed,
     TransferFailed,
+    InvalidBatch,
+    Unauthorized,
+    InsufficientFee,
+    /// No height of the parachain was reported yet.
+    UnknownHeight,
+    /// The height is ahead of the latest height reported, so the request would never be answered.
+    HeightAhead,
+    /// There are no calls to transact.
+    NoCalls,
 }
 
 impl From<StatusCode> for Error {
@@ -39,6 +57,22 @@ impl From<StatusCode> for Error {
     }
 }
 
+impl From<ismp_heights::Error> for Error {
+    fn from(value: ismp_heights::Error) -> Self {
+        match value {
+            ismp_heights::Error::UnknownHeight => Error::UnknownHeight,
+            ismp_heights::Error::HeightAhead => Error::HeightAhead,
+        }
+    }
+}
+
+impl From<access_control::Error> for Error {
+    fn from(_: access_control::Error) -> Self {
+        Error::Unauthorized
+    }
+}
+
+#[access_control::access_controlled(access)]
 #[ink::contract]
 mod dao {
 
@@ -51,12 +85,18 @@ mod dao {
         Used,
     }
 
+    /// The maximum number of items verified by a single request.
+    const MAX_BATCH: usize = 16;
+
     #[ink::storage_item]
     pub struct NftVerifier {
         parachain: ParaId,
         collection: CollectionId,
-        requests: Mapping<MessageId, (AccountId, ItemId)>,
-        next_request: MessageId,
+        requests: Mapping<MessageId, (AccountId, Vec<ItemId>)>,
+        /// The minimum fee paid to relayers for requests, by parachain.
+        min_fees: Mapping<ParaId, Balance>,
+        /// The surplus paid for requests over the fee, refunded once they completed.
+        surpluses: Mapping<MessageId, Balance>,
     }
 
     impl NftVerifier {
@@ -65,53 +105,119 @@ mod dao {
                 parachain,
                 collection,
                 requests: Mapping::default(),
-                next_request: 0,
+                min_fees: Mapping::default(),
+                surpluses: Mapping::default(),
             }
         }
 
-        fn verify(&mut self, height: u32, account: AccountId, item: ItemId) -> Result<()> {
-            self.next_request = self.next_request.saturating_add(1);
-            let key: Vec<u8> = generate_key(account.clone(), self.collection, item);
-            ismp::get(
-                self.next_request,
-                Get::new(self.parachain, height, 0, Vec::default(), Vec::from([key])),
-                0,
-                Some(Callback::to(
-                    0x57ad942b,
-                    Weight::from_parts(2_000_000_000, 500_000),
-                )),
-            )?;
-            self.requests.insert(&self.next_request, &(account, item));
-            Ok(())
+        fn min_fee(&self) -> Balance {
+            self.min_fees.get(self.parachain).unwrap_or_default()
+        }
+
+        // Verifies the ownership of all `items` with a single request, reading one key per item.
+        // The minimum fee is paid from `paid`, returning the id of the request and the fee.
+        fn verify(
+            &mut self,
+            ids: &mut MessageIds,
+            height: u32,
+            account: AccountId,
+            items: Vec<ItemId>,
+            paid: Balance,
+        ) -> Result<(MessageId, Balance)> {
+            let unique = items
+                .iter()
+                .enumerate()
+                .all(|(i, item)| !items[..i].contains(item));
+            if items.is_empty() || items.len() > MAX_BATCH || !unique {
+                return Err(InvalidBatch);
+            }
+            let fee = self.min_fee();
+            let surplus = paid.checked_sub(fee).ok_or(InsufficientFee)?;
+            let keys: Vec<Vec<u8>> = items
+                .iter()
+                .map(|item| generate_key(account.clone(), self.collection, *item))
+                .collect();
+            // The callback may mint a membership for every item.
+            let weight =
+                Weight::from_parts(2_000_000_000, 500_000).saturating_mul(items.len() as u64);
+            let (id, ()) = send(ids, |id| {
+                ismp::get(
+                    id,
+                    Get::new(self.parachain, height, 0, Vec::default(), keys.clone()),
+                    fee,
+                    Some(Callback::to(0x57ad942b, weight)),
+                )
+            })?;
+            self.requests.insert(id, &(account, items));
+            if surplus > 0 {
+                self.surpluses.insert(id, &surplus);
+            }
+            Ok((id, fee))
+        }
+
+        // Takes the request with `id`, returning the account, whether each of its items is owned
+        // by the account and the surplus to refund to the account.
+        fn verified(
+            &mut self,
+            id: MessageId,
+            values: &[StorageValue],
+        ) -> Result<(AccountId, Vec<(ItemId, bool)>, Balance)> {
+            let (account, items) = self.requests.take(id).ok_or(Unknown)?;
+            let surplus = self.surpluses.take(id).unwrap_or_default();
+            let verified = items
+                .into_iter()
+                .map(|item| {
+                    let key = generate_key(account.clone(), self.collection, item);
+                    let owned = values
+                        .iter()
+                        .any(|value| value.key == key && value.value.is_some());
+                    (item, owned)
+                })
+                .collect();
+            Ok((account, verified, surplus))
         }
     }
 
     #[ink(storage)]
     pub struct Dao {
+        access: AccessControl,
         verifier: NftVerifier,
         collection_id: CollectionId,
         next_item_id: ItemId,
         registered_items: Mapping<ItemId, RegistrationStatus>,
+        /// The beneficiary and amount of withdrawals awaiting completion, by the id of their
+        /// message.
+        withdrawals: Mapping<MessageId, (AccountId, Balance)>,
+        storage_version: StorageVersion,
+        /// The latest heights of parachains with a state commitment on Pop, reported by the owner.
+        heights: Heights,
+        /// The ids of the ISMP requests and XCM queries of the contract.
+        ids: MessageIds,
     }
 
     impl Dao {
         #[ink(constructor, payable)]
         pub fn new() -> Result<Self> {
-            let verifier = NftVerifier::new(1000, 0);
+            let verifier = NftVerifier::new(ASSET_HUB.para_id, 0);
             // Create membership token using the non fungibles api.
             let collection_id = create_collection(Self::env().account_id())?;
             let dao = Self {
+                access: AccessControl::new(Self::env().caller()),
                 verifier,
                 collection_id,
                 next_item_id: 0,
                 registered_items: Mapping::default(),
+                withdrawals: Mapping::default(),
+                storage_version: StorageVersion::new(<Self as Migrate>::VERSION),
+                heights: Heights::default(),
+                ids: MessageIds::default(),
             };
 
             // Fund the contract
             let dest = Location::new(1, Parachain(dao.verifier.parachain));
             // Reserve transfer specified assets to contract account on destination.
             let asset: Asset = (Location::parent(), dao.env().transferred_value() / 10).into();
-            let beneficiary = hashed_account(4_001, dao.env().account_id()); // todo: para id getter
+            let beneficiary = hashed_account(POP, dao.env().account_id()); // todo: para id getter
             let message: Xcm<()> = Xcm::builder_unsafe()
                 .withdraw_asset(asset.clone().into())
                 .initiate_reserve_withdraw(
@@ -136,47 +242,120 @@ mod dao {
             Ok(dao)
         }
 
+        /// Returns the minimum fee paid to relayers for verifying items.
+        #[ink(message)]
+        pub fn min_fee(&self) -> Balance {
+            self.verifier.min_fee()
+        }
+
+        /// Sets the minimum fee paid to relayers for requests to `dest`.
+        #[ink(message)]
+        pub fn set_min_fee(&mut self, dest: ParaId, fee: Balance) -> Result<()> {
+            self.access.ensure_owner(self.env().caller())?;
+            self.verifier.min_fees.insert(dest, &fee);
+            Ok(())
+        }
+
+        /// Returns the latest height of the parachain reported by the owner.
+        #[ink(message)]
+        pub fn latest_height(&self) -> Option<u32> {
+            self.heights.latest(self.verifier.parachain)
+        }
+
+        /// Reports the latest height of the parachain with a state commitment on Pop, as seen in
+        /// Pop's `StateMachineUpdated` events. The contract can not read the commitments itself,
+        /// so heights verified at are only checked against this report. Lower heights are
+        /// ignored.
         #[ink(message)]
-        pub fn register(&mut self, height: u32, item: ItemId) -> Result<()> {
+        pub fn update_height(&mut self, height: u32) -> Result<()> {
+            self.access.ensure_owner(self.env().caller())?;
+            self.heights.update(self.verifier.parachain, height);
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn register(&mut self, height: Height, item: ItemId) -> Result<()> {
+            self.register_batch(height, Vec::from([item]))
+        }
+
+        /// Registers the caller with each of `items`, verifying all of them with a single
+        /// request at `height`, which must not be ahead of the latest height reported. The caller
+        /// pays the minimum fee with the value transferred, any surplus is refunded once the
+        /// registration completed.
+        #[ink(message, payable)]
+        pub fn register_batch(&mut self, height: Height, items: Vec<ItemId>) -> Result<()> {
+            let height = self.heights.resolve(self.verifier.parachain, height)?;
             let account = self.env().caller();
-            self.verifier.verify(height, account.clone(), item)?;
-            self.registered_items
-                .insert(item, &RegistrationStatus::Pending);
-            self.env()
-                .emit_event(RegistrationRequested { account, item });
+            let paid = self.env().transferred_value();
+            let (id, fee) = self.verifier.verify(
+                &mut self.ids,
+                height,
+                account.clone(),
+                items.clone(),
+                paid,
+            )?;
+            self.env().emit_event(FeePaid {
+                id,
+                payer: account,
+                fee,
+                surplus: paid - fee,
+            });
+            for item in items {
+                self.registered_items
+                    .insert(item, &RegistrationStatus::Pending);
+                self.env()
+                    .emit_event(RegistrationRequested { account, item });
+            }
             Ok(())
         }
 
+        /// Completes the registration of request `id` with the values read. Only callable by the
+        /// contract itself, as the callback of the request.
         #[ink(message, selector = 0x57ad942b)]
         pub fn complete_registration(
             &mut self,
             id: MessageId,
             values: Vec<StorageValue>,
         ) -> Result<()> {
-            let (account, verified_item) = self.verifier.requests.get(id).ok_or(Unknown)?;
-            let membership = if values[0].value.is_some() {
-                self.next_item_id = self.next_item_id.saturating_add(1);
-                let item = self.next_item_id;
-                nonfungibles::mint(
+            if self.env().caller() != self.env().account_id() {
+                return Err(Unauthorized);
+            }
+            let (account, verified, surplus) = self.verifier.verified(id, &values)?;
+            if surplus > 0 {
+                self.env()
+                    .transfer(account, surplus)
+                    .map_err(|_| TransferFailed)?;
+                self.env().emit_event(FeeRefunded {
+                    id,
+                    payer: account,
+                    amount: surplus,
+                });
+            }
+            for (verified_item, owned) in verified {
+                let membership = if owned {
+                    self.next_item_id = self.next_item_id.saturating_add(1);
+                    let item = self.next_item_id;
+                    nonfungibles::mint(
+                        account,
+                        self.collection_id,
+                        item,
+                        MintWitness {
+                            owned_item: None,
+                            mint_price: None,
+                        },
+                    )?;
+                    self.registered_items
+                        .insert(verified_item, &RegistrationStatus::Used);
+                    Some(item)
+                } else {
+                    None
+                };
+                self.env().emit_event(RegistrationCompleted {
                     account,
-                    self.collection_id,
-                    item,
-                    MintWitness {
-                        owned_item: None,
-                        mint_price: None,
-                    },
-                )?;
-                self.registered_items
-                    .insert(verified_item, &RegistrationStatus::Used);
-                Some(item)
-            } else {
-                None
-            };
-            self.env().emit_event(RegistrationCompleted {
-                account,
-                verified_item,
-                membership,
-            });
+                    verified_item,
+                    membership,
+                });
+            }
             Ok(())
         }
 
@@ -185,33 +364,51 @@ mod dao {
             self.collection_id
         }
 
+        /// Transacts `calls` on Asset Hub with `origin_kind`, paying the fees from the balance of
+        /// the contract. Several calls are batched with `utility.batch_all`, and with
+        /// `Dispatch::Proxy` dispatched with `proxy.proxy` on behalf of an account the contract's
+        /// account is a proxy of, or with `Dispatch::Derivative` with `utility.as_derivative` by an
+        /// account derived from it. Requires the `TRANSACT` role, and `Superuser` requires the
+        /// owner.
         #[ink(message)]
-        pub fn transact(&mut self, call: Vec<u8>) -> Result<()> {
+        pub fn transact(
+            &mut self,
+            calls: Vec<Vec<u8>>,
+            dispatch: Dispatch,
+            origin_kind: OriginKind,
+        ) -> Result<()> {
+            self.access.ensure_role(TRANSACT, self.env().caller())?;
+            if origin_kind == OriginKind::Superuser {
+                self.access.ensure_owner(self.env().caller())?;
+            }
+            let call = remote_calls::wrap(&ASSET_HUB, &calls, dispatch).ok_or(NoCalls)?;
             let dest = Location::new(1, Parachain(self.verifier.parachain));
 
             // Register a new query for receiving a response, used to report transact status.
-            self.verifier.next_request = self.verifier.next_request.saturating_add(1);
-            let query_id = pop_api::messaging::xcm::new_query(
-                self.verifier.next_request,
-                dest.clone(),
-                self.env().block_number().saturating_add(100),
-                Some(Callback::to(
-                    0x641b0b03,
-                    Weight::from_parts(800_000_000, 500_000),
-                )),
-            )?
-            .unwrap();
+            let timeout = self.env().block_number().saturating_add(100);
+            let (id, query_id) = send(&mut self.ids, |id| {
+                pop_api::messaging::xcm::new_query(
+                    id,
+                    dest.clone(),
+                    timeout,
+                    Some(Callback::to(
+                        0x641b0b03,
+                        Weight::from_parts(800_000_000, 500_000),
+                    )),
+                )
+            })?;
+            let query_id = query_id.ok_or(Failed)?;
 
             let response = QueryResponseInfo {
                 // Route back to this parachain.
-                destination: Location::new(1, Parachain(4_001)),
+                destination: Location::new(1, Parachain(POP)),
                 query_id,
                 max_weight: Weight::from_parts(1_000_000, 5_000),
             };
 
             let fees: Asset = (Location::parent(), self.env().balance() / 100).into();
 
-            let beneficiary = hashed_account(4_001, self.env().account_id());
+            let beneficiary = hashed_account(POP, self.env().account_id());
             let message: Xcm<()> = Xcm::builder_unsafe()
                 .withdraw_asset(fees.clone().into())
                 .buy_execution(fees, WeightLimit::Unlimited)
@@ -232,7 +429,7 @@ mod dao {
                 )
                 .set_error_handler(Xcm::builder_unsafe().report_error(response.clone()).build())
                 .transact(
-                    OriginKind::SovereignAccount,
+                    origin_kind,
                     Weight::from_parts(500_000_000, 500_000),
                     call.into(),
                 )
@@ -242,20 +439,71 @@ mod dao {
             let hash =
                 pop_api::messaging::xcm::send(&dest.into_versioned(), &VersionedXcm::V4(message))
                     .unwrap(); // todo: handle error
-            self.env().emit_event(XcmRequested {
-                id: self.verifier.next_request,
-                query_id,
-                hash,
-            });
+            self.env().emit_event(XcmRequested { id, query_id, hash });
             Ok(())
         }
 
-        #[ink(message, selector = 0x641b0b03)]
-        pub fn process_transfer_result(
+        /// Withdraws `amount` of the relay asset from the account of the contract on `dest_chain`
+        /// to `beneficiary` on Pop. The assets are reserve transferred via Asset Hub, the reserve,
+        /// unless `dest_chain` is Asset Hub itself. Only the owner can withdraw.
+        #[ink(message)]
+        pub fn withdraw(
             &mut self,
-            _id: MessageId,
-            response: Response,
+            dest_chain: ParaId,
+            amount: Balance,
+            beneficiary: AccountId,
         ) -> Result<()> {
+            self.access.ensure_owner(self.env().caller())?;
+            let dest = Location::new(1, Parachain(dest_chain));
+
+            // Register a new query for receiving a response, used to report completion.
+            let timeout = self.env().block_number().saturating_add(100);
+            let (id, query_id) = send(&mut self.ids, |id| {
+                pop_api::messaging::xcm::new_query(
+                    id,
+                    dest.clone(),
+                    timeout,
+                    Some(Callback::to(
+                        0x641b0b03,
+                        Weight::from_parts(800_000_000, 500_000),
+                    )),
+                )
+            })?;
+            let query_id = query_id.ok_or(Failed)?;
+
+            let response = QueryResponseInfo {
+                // Route back to this parachain.
+                destination: Location::new(1, Parachain(POP)),
+                query_id,
+                max_weight: Weight::from_parts(1_000_000, 5_000),
+            };
+            let message = repatriate_xcm(dest_chain, amount, beneficiary, response);
+
+            let hash =
+                pop_api::messaging::xcm::send(&dest.into_versioned(), &VersionedXcm::V4(message))?;
+            self.withdrawals.insert(id, &(beneficiary, amount));
+            self.env().emit_event(XcmRequested { id, query_id, hash });
+            Ok(())
+        }
+
+        /// Processes the response to query `id`, reporting the completion of a transfer or a
+        /// withdrawal. Only callable by the contract itself, as the callback of the query.
+        #[ink(message, selector = 0x641b0b03)]
+        pub fn process_transfer_result(&mut self, id: MessageId, response: Response) -> Result<()> {
+            if self.env().caller() != self.env().account_id() {
+                return Err(Unauthorized);
+            }
+            // Withdrawals report an error, if any.
+            if let Some((beneficiary, amount)) = self.withdrawals.take(id) {
+                let success = matches!(response, Response::ExecutionResult(None));
+                self.env().emit_event(WithdrawalCompleted {
+                    id,
+                    beneficiary,
+                    amount,
+                    success,
+                });
+                return Ok(());
+            }
             match response {
                 // Emit an event on a successful xcm transact.
                 Response::DispatchResult(MaybeErrorCode::Success) => {
@@ -267,6 +515,52 @@ mod dao {
         }
     }
 
+    impl Upgradeable for Dao {
+        #[ink(message)]
+        fn storage_version(&self) -> Version {
+            self.storage_version.get()
+        }
+
+        #[ink(message)]
+        fn set_code(&mut self, code_hash: Hash) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            self.storage_version.set_code(code_hash)
+        }
+
+        #[ink(message)]
+        fn migrate(&mut self) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            upgradeable::migrate(self).map(|_| ())
+        }
+    }
+
+    impl Migrate for Dao {
+        const VERSION: Version = 1;
+
+        fn storage_version_mut(&mut self) -> &mut StorageVersion {
+            &mut self.storage_version
+        }
+
+        fn migrate_from(&mut self, from: Version) -> upgradeable::Result<()> {
+            // Code before the first version can't be upgraded, so its storage is never migrated.
+            Err(upgradeable::Error::UnsupportedVersion(from))
+        }
+    }
+
+    // Sends a message with an id allocated by `ids`. `message-ids` builds on another branch of
+    // pop-api, so status codes are converted both ways.
+    fn send<T>(
+        ids: &mut MessageIds,
+        send: impl Fn(MessageId) -> pop_api::Result<T>,
+    ) -> Result<(MessageId, T)> {
+        ids.send(|id| send(id).map_err(|e| e.0.into()))
+            .map_err(|e| Error::StatusCode(e.0))
+    }
+
     // Create a collection using the non fungibles api.
     fn create_collection(owner: AccountId) -> Result<CollectionId> {
         let config = CollectionConfig {
@@ -322,18 +616,6 @@ mod dao {
         result
     }
 
-    fn hashed_account(para_id: u32, account_id: AccountId) -> AccountId {
-        let location = (
-            b"SiblingChain",
-            Compact::<u32>::from(para_id),
-            (b"AccountId32", account_id.0).encode(),
-        )
-            .encode();
-        let mut output = [0u8; 32];
-        Blake2x256::hash(&location, &mut output);
-        AccountId::from(output)
-    }
-
     #[ink::event]
     #[cfg_attr(feature = "std", derive(Debug))]
     pub struct RegistrationRequested {
@@ -353,6 +635,38 @@ mod dao {
     #[cfg_attr(feature = "std", derive(Debug))]
     pub struct TransferCompleted;
 
+    #[ink::event]
+    pub struct FeePaid {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        /// The fee paid to relayers.
+        pub fee: Balance,
+        /// The amount paid over the fee, refunded once the request completed.
+        pub surplus: Balance,
+    }
+
+    #[ink::event]
+    pub struct FeeRefunded {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        pub amount: Balance,
+    }
+
+    #[ink::event]
+    pub struct WithdrawalCompleted {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub beneficiary: AccountId,
+        pub amount: Balance,
+        /// Whether the program ran without error on the parachain withdrawn from.
+        pub success: bool,
+    }
+
     #[ink::event]
     pub struct XcmRequested {
         #[ink(topic)]
@@ -366,11 +680,392 @@ mod dao {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use access_control::AccessControlled;
+        use ink::{env::test, scale::Decode};
+        use pop_api_mock::{messaging::Delivery, Sandbox};
 
         #[ink::test]
         fn default_works() {
             let dao = Dao::default();
             assert_eq!(dao.get(), false);
         }
+
+        fn value(account: AccountId, item: ItemId, value: Option<Vec<u8>>) -> StorageValue {
+            StorageValue {
+                key: generate_key(account, 0, item),
+                value,
+            }
+        }
+
+        fn registration_completed() -> RegistrationCompleted {
+            let event = test::recorded_events().last().unwrap();
+            RegistrationCompleted::decode(&mut &event.data[..]).unwrap()
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.collection_id(), 0);
+            assert_eq!(
+                sandbox.nonfungibles.admin(0),
+                Some(test::callee::<Environment>())
+            );
+            assert_eq!(sandbox.messaging.executed().len(), 1);
+        }
+
+        #[ink::test]
+        fn register_works() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            let bob = test::default_accounts::<Environment>().bob;
+            test::set_caller::<Environment>(bob);
+            assert_eq!(dao.register(Height::Latest, 7), Ok(()));
+
+            let requests = sandbox.messaging.requests();
+            let request = requests[0].get().unwrap();
+            assert_eq!((request.dest, request.height), (1000, 10));
+            assert_eq!(request.keys, [generate_key(bob, 0, 7)]);
+            assert!(matches!(
+                dao.registered_items.get(7),
+                Some(RegistrationStatus::Pending)
+            ));
+
+            assert!(matches!(
+                sandbox.messaging.respond_get_with(
+                    &requests[0].commitment,
+                    Vec::from([value(bob, 7, Some(Vec::from([1])))]),
+                    |id, values| dao.complete_registration(id, values),
+                ),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(sandbox.nonfungibles.owner_of(0, 1), Some(bob));
+            assert!(matches!(
+                dao.registered_items.get(7),
+                Some(RegistrationStatus::Used)
+            ));
+            let event = registration_completed();
+            assert_eq!(
+                (event.account, event.verified_item, event.membership),
+                (bob, 7, Some(1))
+            );
+        }
+
+        #[ink::test]
+        fn complete_registration_rejects_other_callers() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            let bob = test::default_accounts::<Environment>().bob;
+            test::set_caller::<Environment>(bob);
+            assert_eq!(dao.register(Height::Latest, 7), Ok(()));
+
+            // The values read can not be forged by calling the callback directly.
+            let id = sandbox.messaging.requests()[0].id;
+            assert_eq!(
+                dao.complete_registration(id, Vec::from([value(bob, 7, Some(Vec::from([1])))])),
+                Err(Unauthorized)
+            );
+            assert_eq!(sandbox.nonfungibles.owner_of(0, 1), None);
+            assert!(matches!(
+                dao.registered_items.get(7),
+                Some(RegistrationStatus::Pending)
+            ));
+        }
+
+        #[ink::test]
+        fn register_without_nft_grants_no_membership() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            let bob = test::default_accounts::<Environment>().bob;
+            test::set_caller::<Environment>(bob);
+            assert_eq!(dao.register(Height::Latest, 7), Ok(()));
+
+            let commitment = sandbox.messaging.requests()[0].commitment;
+            assert!(matches!(
+                sandbox.messaging.respond_get_with(
+                    &commitment,
+                    Vec::from([value(bob, 7, None)]),
+                    |id, values| dao.complete_registration(id, values)
+                ),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert!(sandbox.nonfungibles.items(0).is_empty());
+            assert_eq!(registration_completed().membership, None);
+        }
+
+        #[ink::test]
+        fn register_batch_works() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            let bob = test::default_accounts::<Environment>().bob;
+            test::set_caller::<Environment>(bob);
+            assert_eq!(
+                dao.register_batch(Height::Latest, Vec::from([7, 8, 9])),
+                Ok(())
+            );
+
+            // A single request reads the keys of all items.
+            let requests = sandbox.messaging.requests();
+            assert_eq!(requests.len(), 1);
+            let keys = [7, 8, 9].map(|item| generate_key(bob, 0, item));
+            assert_eq!(requests[0].get().unwrap().keys, keys);
+
+            // Values are matched to items by key, not by position.
+            let values = Vec::from([
+                value(bob, 9, Some(Vec::from([1]))),
+                value(bob, 8, None),
+                value(bob, 7, Some(Vec::from([1]))),
+            ]);
+            assert!(matches!(
+                sandbox.messaging.respond_get_with(
+                    &requests[0].commitment,
+                    values,
+                    |id, values| dao.complete_registration(id, values)
+                ),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(sandbox.nonfungibles.items(0), [(1, bob), (2, bob)]);
+            let memberships: Vec<_> = test::recorded_events()
+                .filter_map(|event| RegistrationCompleted::decode(&mut &event.data[..]).ok())
+                .map(|event| (event.verified_item, event.membership))
+                .collect();
+            assert_eq!(memberships, [(7, Some(1)), (8, None), (9, Some(2))]);
+            assert!(matches!(
+                dao.registered_items.get(8),
+                Some(RegistrationStatus::Pending)
+            ));
+        }
+
+        #[ink::test]
+        fn register_pays_fee() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            let bob = test::default_accounts::<Environment>().bob;
+            assert_eq!(dao.set_min_fee(1000, 100), Ok(()));
+            assert_eq!(dao.min_fee(), 100);
+
+            test::set_caller::<Environment>(bob);
+            assert_eq!(dao.set_min_fee(1000, 0), Err(Unauthorized));
+            test::set_value_transferred::<Environment>(99);
+            assert_eq!(dao.register(Height::Latest, 7), Err(InsufficientFee));
+
+            // The value transferred is credited to the contract.
+            test::set_value_transferred::<Environment>(130);
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 130);
+            test::set_account_balance::<Environment>(bob, 0);
+            assert_eq!(dao.register(Height::Latest, 7), Ok(()));
+            let requests = sandbox.messaging.requests();
+            assert_eq!(requests[0].fee, 100);
+
+            // The surplus is refunded once the registration completed.
+            assert!(matches!(
+                sandbox.messaging.respond_get_with(
+                    &requests[0].commitment,
+                    Vec::from([value(bob, 7, None)]),
+                    |id, values| dao.complete_registration(id, values)
+                ),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(test::get_account_balance::<Environment>(bob).unwrap(), 30);
+        }
+
+        // `system.remark(0x01)`.
+        fn remark() -> Vec<Vec<u8>> {
+            Vec::from([Vec::from([0, 0, 4, 1])])
+        }
+
+        fn transact(message: &VersionedXcm<()>) -> (OriginKind, Vec<u8>) {
+            let VersionedXcm::V4(message) = message else {
+                panic!("unexpected version")
+            };
+            message
+                .0
+                .iter()
+                .find_map(|instruction| match instruction {
+                    Transact {
+                        origin_kind, call, ..
+                    } => Some((*origin_kind, call.clone().into_encoded())),
+                    _ => None,
+                })
+                .unwrap()
+        }
+
+        #[ink::test]
+        fn transact_requires_role() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            let sovereign = OriginKind::SovereignAccount;
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                dao.transact(remark(), Dispatch::Direct, sovereign),
+                Err(Unauthorized)
+            );
+            assert!(sandbox.messaging.sent().is_empty());
+
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dao.grant_role(TRANSACT, accounts.bob), Ok(()));
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dao.transact(remark(), Dispatch::Direct, sovereign), Ok(()));
+            assert_eq!(sandbox.messaging.sent().len(), 1);
+            // Only the owner can transact as superuser.
+            assert_eq!(
+                dao.transact(remark(), Dispatch::Direct, OriginKind::Superuser),
+                Err(Unauthorized)
+            );
+
+            // Revoked roles no longer permit the message.
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dao.revoke_role(TRANSACT, accounts.bob), Ok(()));
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                dao.transact(remark(), Dispatch::Direct, sovereign),
+                Err(Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn transact_wraps_calls() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(
+                dao.transact(Vec::new(), Dispatch::Direct, OriginKind::Xcm),
+                Err(NoCalls)
+            );
+
+            let calls = Vec::from([Vec::from([0, 0, 4, 1]), Vec::from([0, 0, 4, 2])]);
+            let dispatch = Dispatch::Proxy([1; 32]);
+            assert_eq!(
+                dao.transact(calls.clone(), dispatch, OriginKind::Xcm),
+                Ok(())
+            );
+            assert_eq!(
+                dao.transact(remark(), Dispatch::Direct, OriginKind::Superuser),
+                Ok(())
+            );
+            let sent = sandbox.messaging.sent();
+            assert_eq!(
+                transact(&sent[0].2),
+                (
+                    OriginKind::Xcm,
+                    remote_calls::wrap(&ASSET_HUB, &calls, dispatch).unwrap()
+                )
+            );
+            assert_eq!(
+                transact(&sent[1].2),
+                (OriginKind::Superuser, remark()[0].clone())
+            );
+        }
+
+        #[ink::test]
+        fn withdraw_works() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(dao.withdraw(1000, 100, accounts.bob), Err(Unauthorized));
+
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dao.withdraw(1000, 100, accounts.bob), Ok(()));
+            assert_eq!(dao.withdraw(2034, 100, accounts.bob), Ok(()));
+            let sent = sandbox.messaging.sent();
+            let VersionedXcm::V4(direct) = &sent[0].2 else {
+                panic!("unexpected version")
+            };
+            assert!(matches!(direct.0.last(), Some(DepositReserveAsset { .. })));
+            let VersionedXcm::V4(via_reserve) = &sent[1].2 else {
+                panic!("unexpected version")
+            };
+            assert!(matches!(
+                via_reserve.0.last(),
+                Some(InitiateReserveWithdraw { .. })
+            ));
+
+            // Completion is reported to the callback.
+            let commitment = sandbox.messaging.commitment(1).unwrap();
+            let response = Response::ExecutionResult(Some((1, XcmError::Barrier)));
+            assert!(matches!(
+                sandbox
+                    .messaging
+                    .respond_xcm(&mut dao, &commitment, response),
+                Ok(Delivery::Executed { .. })
+            ));
+            let event = test::recorded_events().last().unwrap();
+            let completed = WithdrawalCompleted::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((completed.id, completed.success), (1, false));
+        }
+
+        #[ink::test]
+        fn process_transfer_result_rejects_other_callers() {
+            let _sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            let bob = test::default_accounts::<Environment>().bob;
+            assert_eq!(dao.withdraw(1000, 100, bob), Ok(()));
+
+            // The completion of a withdrawal can not be forged by calling the callback directly.
+            test::set_caller::<Environment>(bob);
+            assert_eq!(
+                dao.process_transfer_result(1, Response::ExecutionResult(None)),
+                Err(Unauthorized)
+            );
+            assert_eq!(dao.withdrawals.get(1), Some((bob, 100)));
+        }
+
+        #[ink::test]
+        fn upgrade_requires_owner() {
+            let _sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            let accounts = test::default_accounts::<Environment>();
+            assert_eq!(dao.storage_version(), 1);
+            test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                dao.set_code(Hash::from([1; 32])),
+                Err(upgradeable::Error::NotOwner)
+            );
+            assert_eq!(dao.migrate(), Err(upgradeable::Error::NotOwner));
+
+            // Instances of the code start at its version, so there is nothing to migrate.
+            test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(dao.migrate(), Ok(()));
+            assert_eq!(dao.storage_version(), 1);
+        }
+
+        #[ink::test]
+        fn register_checks_height() {
+            let sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.register(Height::Latest, 7), Err(UnknownHeight));
+            assert_eq!(dao.update_height(10), Ok(()));
+            assert_eq!(dao.latest_height(), Some(10));
+            assert_eq!(dao.register(Height::At(11), 7), Err(HeightAhead));
+            assert_eq!(dao.register(Height::At(5), 7), Ok(()));
+            let request = sandbox.messaging.requests()[0].get().unwrap();
+            assert_eq!(request.height, 5);
+
+            test::set_caller::<Environment>(test::default_accounts::<Environment>().bob);
+            assert_eq!(dao.update_height(11), Err(Unauthorized));
+        }
+
+        #[ink::test]
+        fn register_batch_rejects_invalid_batch() {
+            let _sandbox = Sandbox::register();
+            let mut dao = Dao::new().unwrap();
+            assert_eq!(dao.update_height(10), Ok(()));
+            assert_eq!(
+                dao.register_batch(Height::Latest, Vec::new()),
+                Err(InvalidBatch)
+            );
+            assert_eq!(
+                dao.register_batch(Height::Latest, Vec::from([7, 7])),
+                Err(InvalidBatch)
+            );
+            let items = (0..=MAX_BATCH as ItemId).collect();
+            assert_eq!(dao.register_batch(Height::Latest, items), Err(InvalidBatch));
+        }
     }
 }
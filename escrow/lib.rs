@@ -0,0 +1,211 @@
+//! Accounting of the deposits of users in an account shared on another chain, e.g. the sovereign
+//! account of a contract, so users can only spend what they deposited.
+//!
+//! The amount which arrived on the other chain is only known there, so deposits are credited once
+//! the chain reports it: the program depositing into the shared account reports its holding with
+//! `ReportHolding` to a pair of queries registered with [`Escrow::credit_report`], one after the
+//! other, and the responses are passed to [`Escrow::report`]. The chain pays the delivery fee of
+//! each report from the holding after reporting it, so the second report is less than the first by
+//! the fee of the first. The fee of the second report is taken to be the same, and is not credited.
+//! Fees spent from the shared account are debited upfront with [`Escrow::debit`], and whatever is
+//! left of them is credited back the same way.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{
+    env::DefaultEnvironment,
+    primitives::AccountId,
+    storage::Mapping,
+    xcm::prelude::{AssetId, Assets, Fungible},
+};
+
+pub type Balance = u128;
+/// The id of the message of a query, see `pop_api::messaging::MessageId`.
+pub type MessageId = u64;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// The deposit of the account does not cover the amount.
+    InsufficientDeposit,
+}
+
+#[ink::event]
+pub struct Credited {
+    #[ink(topic)]
+    pub account: AccountId,
+    pub amount: Balance,
+}
+
+#[ink::event]
+pub struct Debited {
+    #[ink(topic)]
+    pub account: AccountId,
+    pub amount: Balance,
+}
+
+/// The deposits of users in a shared account.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct Escrow {
+    deposits: Mapping<AccountId, Balance>,
+    /// The accounts credited with the holding reported to a pair of queries and the id of the
+    /// message of the other query of the pair, by the id of its message.
+    reports: Mapping<MessageId, (AccountId, MessageId)>,
+    /// The amount reported to the query of a pair answered first, by the id of the message of the
+    /// query still awaited.
+    reported: Mapping<MessageId, Balance>,
+}
+
+impl Escrow {
+    /// Returns the deposit of `account`.
+    pub fn deposit_of(&self, account: AccountId) -> Balance {
+        self.deposits.get(account).unwrap_or_default()
+    }
+
+    pub fn credit(&mut self, account: AccountId, amount: Balance) {
+        let deposit = self.deposit_of(account).saturating_add(amount);
+        self.deposits.insert(account, &deposit);
+        emit(Credited { account, amount });
+    }
+
+    pub fn debit(&mut self, account: AccountId, amount: Balance) -> Result<()> {
+        let deposit = self
+            .deposit_of(account)
+            .checked_sub(amount)
+            .ok_or(Error::InsufficientDeposit)?;
+        self.deposits.insert(account, &deposit);
+        emit(Debited { account, amount });
+        Ok(())
+    }
+
+    /// Credits `account` with the holding reported to the pair of queries of messages `ids`, once
+    /// both reported. The program reports its holding to both queries, one after the other.
+    pub fn credit_report(&mut self, (first, second): (MessageId, MessageId), account: AccountId) {
+        self.reports.insert(first, &(account, second));
+        self.reports.insert(second, &(account, first));
+    }
+
+    /// Stops awaiting the report of message `id`, e.g. once it timed out so its id can be used
+    /// again. Cancelling either query of a pair before it was answered stops awaiting both.
+    /// Returns the account which would have been credited.
+    pub fn cancel_report(&mut self, id: MessageId) -> Option<AccountId> {
+        let (account, other) = self.reports.take(id)?;
+        self.reports.remove(other);
+        self.reported.remove(id);
+        Some(account)
+    }
+
+    /// Passes the amount of `asset` in `assets` reported to the query of message `id`. Once both
+    /// queries of its pair reported, the account awaiting them is credited with the second amount
+    /// less the delivery fee of the first report. Returns the account, or `None` if no report is
+    /// awaited.
+    pub fn report(&mut self, id: MessageId, assets: &Assets, asset: &AssetId) -> Option<AccountId> {
+        let (account, other) = self.reports.take(id)?;
+        let amount = assets
+            .inner()
+            .iter()
+            .filter(|reported| reported.id == *asset)
+            .map(|reported| match reported.fun {
+                Fungible(amount) => amount,
+                _ => 0,
+            })
+            .fold(0, Balance::saturating_add);
+        match self.reported.take(id) {
+            // Either report may arrive first, the first was made of the larger holding.
+            Some(reported) => {
+                let (first, second) = (reported.max(amount), reported.min(amount));
+                self.credit(account, second.saturating_sub(first - second));
+            }
+            None => {
+                self.reported.insert(other, &amount);
+            }
+        }
+        Some(account)
+    }
+}
+
+fn emit<E: ink::env::Event>(event: E) {
+    ink::env::emit_event::<DefaultEnvironment, E>(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::{
+        env::test,
+        xcm::prelude::{Asset, Location},
+    };
+
+    fn relay() -> AssetId {
+        AssetId(Location::parent())
+    }
+
+    #[ink::test]
+    fn credit_and_debit_work() {
+        let bob = test::default_accounts::<DefaultEnvironment>().bob;
+        let mut escrow = Escrow::default();
+        escrow.credit(bob, 100);
+        assert_eq!(escrow.debit(bob, 101), Err(Error::InsufficientDeposit));
+        assert_eq!(escrow.debit(bob, 60), Ok(()));
+        assert_eq!(escrow.deposit_of(bob), 40);
+        assert_eq!(test::recorded_events().count(), 2);
+    }
+
+    #[ink::test]
+    fn report_credits_reported_asset() {
+        let bob = test::default_accounts::<DefaultEnvironment>().bob;
+        let mut escrow = Escrow::default();
+        let other: Asset = (Location::here(), 1_000).into();
+        let assets = |amount: Balance| -> Assets {
+            Vec::from([(relay(), amount).into(), other.clone()]).into()
+        };
+        assert_eq!(escrow.report(1, &assets(70), &relay()), None);
+
+        // The second report is less than the first by the delivery fee of the first, which the
+        // second report is taken to cost too.
+        escrow.credit_report((1, 2), bob);
+        assert_eq!(escrow.report(1, &assets(70), &relay()), Some(bob));
+        assert_eq!(escrow.deposit_of(bob), 0);
+        assert_eq!(escrow.report(2, &assets(60), &relay()), Some(bob));
+        assert_eq!(escrow.deposit_of(bob), 50);
+        // Reports are only credited once.
+        assert_eq!(escrow.report(2, &assets(60), &relay()), None);
+
+        // Reports may arrive in either order.
+        escrow.credit_report((3, 4), bob);
+        assert_eq!(escrow.report(4, &assets(60), &relay()), Some(bob));
+        assert_eq!(escrow.report(3, &assets(70), &relay()), Some(bob));
+        assert_eq!(escrow.deposit_of(bob), 100);
+
+        // A holding which does not cover the fees is not credited.
+        escrow.credit_report((5, 6), bob);
+        escrow.report(5, &assets(70), &relay());
+        escrow.report(6, &assets(30), &relay());
+        assert_eq!(escrow.deposit_of(bob), 100);
+    }
+
+    #[ink::test]
+    fn cancel_report_works() {
+        let bob = test::default_accounts::<DefaultEnvironment>().bob;
+        let mut escrow = Escrow::default();
+        let assets: Assets = Vec::from([(relay(), 70).into()]).into();
+
+        // Cancelling either query stops awaiting both.
+        escrow.credit_report((1, 2), bob);
+        assert_eq!(escrow.cancel_report(1), Some(bob));
+        assert_eq!(escrow.cancel_report(2), None);
+        assert_eq!(escrow.report(2, &assets, &relay()), None);
+
+        // The query still awaited is cancelled with the amount reported to the other.
+        escrow.credit_report((3, 4), bob);
+        escrow.report(3, &assets, &relay());
+        assert_eq!(escrow.cancel_report(3), None);
+        assert_eq!(escrow.cancel_report(4), Some(bob));
+        assert_eq!(escrow.report(4, &assets, &relay()), None);
+        assert_eq!(escrow.reported.get(4), None);
+        assert_eq!(escrow.deposit_of(bob), 0);
+    }
+}
@@ -1,23 +1,106 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
-use ink::xcm::{
-    prelude::{Asset, Junction::Parachain, Location, OriginKind, Weight, Xcm, XcmHash},
-    VersionedXcm,
+use access_control::{AccessControl, Role};
+use calls::{hydration::AssetId, Dispatch};
+use escrow::Escrow;
+use ink::{
+    prelude::vec::Vec,
+    xcm::{
+        prelude::{Asset, Junction::Parachain, Location, OriginKind, Weight, Xcm, XcmHash},
+        VersionedXcm,
+    },
 };
+use ismp_heights::Heights;
 use pop_api::{
     messaging::{self as api, ismp, ismp::Get, MessageId},
     StatusCode,
 };
+use repatriation::hashed_account;
+use upgradeable::{Migrate, StorageVersion, Upgradeable, Version};
+use xcm_versions::{subscribe_xcm, XcmVersions};
+
+pub use message_ids as ids;
+pub use remote_calls as calls;
+pub mod pallets;
 
 pub type Result<T> = core::result::Result<T, StatusCode>;
 
+/// The role required to dispatch calls on Hydration, spending the balance of the contract there.
+pub const DISPATCH: Role = 1;
+
+/// The height of Hydration at which storage is read, checked against the latest height reported
+/// by the owner with `update_height`.
+pub use ismp_heights::Height;
+
+/// The outcome of a dry run of a program on Hydration, e.g. using its `DryRunApi`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub struct DryRun {
+    pub success: bool,
+    /// The weight the program is predicted to consume.
+    pub ref_time: u64,
+    pub proof_size: u64,
+}
+
+/// The outcome of calls dispatched on Hydration, as reported by Hydration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub enum Outcome {
+    /// No report was received yet.
+    Pending,
+    Success,
+    /// Dispatching the calls failed with the encoded dispatch error.
+    DispatchFailed(Vec<u8>),
+    /// Executing the program failed at the instruction with the index.
+    ExecutionFailed(u32),
+}
+
+#[access_control::access_controlled(access)]
 #[ink::contract]
 mod execute_on_hydra {
     use super::*;
-    use ink::{prelude::vec::Vec, xcm::prelude::*};
-    use pop_api::messaging::{ismp::StorageValue, Callback};
+    use crate::{
+        ids::MessageIds,
+        pallets::{Pallets, RemotePallet},
+    };
+    use ink::{
+        env::hash::Blake2x256,
+        storage::Mapping,
+        xcm::{prelude::*, v3::MaybeErrorCode},
+    };
+    use pop_api::messaging::{ismp::StorageValue, xcm::Response, Callback};
+
+    /// The para id of Pop, where reports of Hydration are sent to.
+    const POP: u32 = 4_001;
 
     const UNAUTHORIZED: u32 = u32::MAX;
+    /// No height of Hydration was reported to the contract yet.
+    const UNKNOWN_HEIGHT: u32 = u32::MAX - 1;
+    /// The height is ahead of the latest height reported to the contract, so the request would
+    /// never be answered.
+    const HEIGHT_AHEAD: u32 = u32::MAX - 2;
+    /// There are no calls to dispatch.
+    const NO_CALLS: u32 = u32::MAX - 3;
+    /// No dry run of the program was reported.
+    const NOT_DRY_RUN: u32 = u32::MAX - 4;
+    /// The dry run of the program failed.
+    const DRY_RUN_FAILED: u32 = u32::MAX - 5;
+    /// The response does not report the outcome of a pending dispatch.
+    const INVALID_RESPONSE: u32 = u32::MAX - 6;
+    /// No query could be registered for the report of the dispatch.
+    const NO_QUERY: u32 = u32::MAX - 7;
+    /// The value transferred does not cover the minimum fee of the destination.
+    const INSUFFICIENT_FEE: u32 = u32::MAX - 10;
+    /// The surplus paid could not be refunded to the payer.
+    const REFUND_FAILED: u32 = u32::MAX - 11;
+    /// The deposit of the caller does not cover the amount.
+    const INSUFFICIENT_DEPOSIT: u32 = u32::MAX - 12;
+    /// The program can't be sent in the XCM version of Hydration.
+    const UNSUPPORTED_XCM_VERSION: u32 = u32::MAX - 13;
+    /// All indices of accounts derived for callers are assigned.
+    const DERIVATIVES_EXHAUSTED: u32 = u32::MAX - 16;
 
     #[ink::event]
     pub struct GetCompleted {
@@ -26,16 +109,319 @@ mod execute_on_hydra {
         pub values: Vec<StorageValue>,
     }
 
+    #[ink::event]
+    pub struct TransactSent {
+        #[ink(topic)]
+        pub id: MessageId,
+        pub query_id: QueryId,
+        pub hash: XcmHash,
+    }
+
+    #[ink::event]
+    pub struct TransactCompleted {
+        #[ink(topic)]
+        pub id: MessageId,
+        pub outcome: Outcome,
+    }
+
+    #[ink::event]
+    pub struct FeePaid {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        /// The fee paid to relayers.
+        pub fee: Balance,
+        /// The amount paid over the fee, refunded once the request completed.
+        pub surplus: Balance,
+    }
+
+    #[ink::event]
+    pub struct FeeRefunded {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        pub amount: Balance,
+    }
+
+    #[ink::event]
+    pub struct Funded {
+        /// The id of the message awaiting the amount deposited on Hydration.
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub account: AccountId,
+        pub value: Balance,
+    }
+
+    #[ink::event]
+    pub struct PalletReported {
+        #[ink(topic)]
+        pub id: MessageId,
+        /// The name of the crate of the pallet queried.
+        pub module_name: Vec<u8>,
+        pub pallet: RemotePallet,
+    }
+
+    #[ink::event]
+    pub struct DryRunReported {
+        #[ink(topic)]
+        pub program: Hash,
+        pub result: DryRun,
+    }
+
     #[ink(storage)]
     #[derive(Default)]
     pub struct ExecuteOnHydra {
-        query_id: u32,
+        access: AccessControl,
+        /// The ids of the messages sent by the contract.
+        ids: MessageIds,
+        /// The latest height of Hydration with a state commitment on Pop, reported by the owner.
+        heights: Heights,
+        /// The account trusted to dry run programs before they are sent. If set, only programs
+        /// with a successful dry run are sent.
+        dry_runner: Option<AccountId>,
+        /// The dry runs reported, by the hash of the program.
+        dry_runs: Mapping<Hash, DryRun>,
+        /// The outcome of each dispatch, by the id of the message reporting it.
+        outcomes: Mapping<MessageId, Outcome>,
+        /// The minimum fee paid to relayers for requests, by destination.
+        min_fees: Mapping<u32, Balance>,
+        /// The payer and surplus of requests, refunded once they completed.
+        payments: Mapping<MessageId, (AccountId, Balance)>,
+        /// The deposits of callers in the account of the contract on Hydration, spent on the fees
+        /// of their dispatches.
+        escrow: Escrow,
+        storage_version: StorageVersion,
+        /// The XCM version of Hydration, which programs are sent in.
+        xcm_versions: XcmVersions,
+        /// The pallets of Hydration reported, which typed calls are verified against.
+        pallets: Pallets,
+        /// The index of the account derived for each caller, see `derive_account`.
+        derivatives: Mapping<AccountId, u16>,
+        /// The number of accounts derived for callers, which is the next index assigned.
+        next_derivative: u32,
     }
 
     impl ExecuteOnHydra {
         #[ink(constructor)]
         pub fn new() -> Self {
-            Self { query_id: 0 }
+            Self {
+                access: AccessControl::new(Self::env().caller()),
+                storage_version: StorageVersion::new(<Self as Migrate>::VERSION),
+                ..Default::default()
+            }
+        }
+
+        /// Returns the latest height of Hydration reported by the owner.
+        #[ink(message)]
+        pub fn latest_height(&self) -> Option<u32> {
+            self.heights.latest(calls::HYDRATION.para_id)
+        }
+
+        /// Reports the latest height of Hydration with a state commitment on Pop, as seen in
+        /// Pop's `StateMachineUpdated` events. The contract can not read the commitments itself,
+        /// so heights are only checked against this report. Lower heights are ignored.
+        #[ink(message)]
+        pub fn update_height(&mut self, height: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.heights.update(calls::HYDRATION.para_id, height);
+            Ok(())
+        }
+
+        /// Sets the account trusted to dry run programs, requiring a successful dry run before
+        /// each program is sent. `None` sends programs without dry runs.
+        #[ink(message)]
+        pub fn set_dry_runner(&mut self, dry_runner: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.dry_runner = dry_runner;
+            Ok(())
+        }
+
+        /// Records the dry run of the program with hash `program`, as returned by
+        /// `preview_on_hydra`.
+        #[ink(message)]
+        pub fn report_dry_run(&mut self, program: Hash, result: DryRun) -> Result<()> {
+            if Some(self.env().caller()) != self.dry_runner {
+                return Err(UNAUTHORIZED.into());
+            }
+            self.dry_runs.insert(program, &result);
+            self.env().emit_event(DryRunReported { program, result });
+            Ok(())
+        }
+
+        /// Returns the dry run reported for the program with hash `program`.
+        #[ink(message)]
+        pub fn dry_run(&self, program: Hash) -> Option<DryRun> {
+            self.dry_runs.get(program)
+        }
+
+        /// Returns the minimum fee paid to relayers for requests to `dest`.
+        #[ink(message)]
+        pub fn min_fee(&self, dest: u32) -> Balance {
+            self.min_fees.get(dest).unwrap_or_default()
+        }
+
+        /// Sets the minimum fee paid to relayers for requests to `dest`, which callers pay with
+        /// the value transferred.
+        #[ink(message)]
+        pub fn set_min_fee(&mut self, dest: u32, fee: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.min_fees.insert(dest, &fee);
+            Ok(())
+        }
+
+        /// Funds the account of the contract on Hydration with the value transferred, reserve
+        /// transferred via Asset Hub. The amount deposited after the fees of both chains is
+        /// credited to the caller once reported by Hydration, less the delivery fee of the report,
+        /// see `deposit_of`.
+        #[ink(message, payable)]
+        pub fn fund(&mut self) -> Result<()> {
+            let value = self.env().transferred_value();
+            let hydration = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            let (id, report) = self.new_query(hydration.clone())?;
+            let (second_id, second) = self.new_query(hydration)?;
+            api::xcm::execute(&VersionedXcm::V4(
+                self.fund_program(value, (report, second)),
+            ))?;
+            let account = self.env().caller();
+            self.escrow.credit_report((id, second_id), account);
+            self.env().emit_event(Funded { id, account, value });
+            Ok(())
+        }
+
+        /// Returns the deposit of `account` in the account of the contract on Hydration.
+        #[ink(message)]
+        pub fn deposit_of(&self, account: AccountId) -> Balance {
+            self.escrow.deposit_of(account)
+        }
+
+        /// Withdraws `amount` of the deposit of the caller to their account on Hydration, paying
+        /// the fees of Hydration with it.
+        #[ink(message)]
+        pub fn withdraw_deposit(&mut self, amount: Balance) -> Result<XcmHash> {
+            let caller = self.env().caller();
+            self.escrow
+                .debit(caller, amount)
+                .map_err(|_| StatusCode::from(INSUFFICIENT_DEPOSIT))?;
+            let asset: Asset = (Location::parent(), amount).into();
+            let program = Xcm::builder()
+                .withdraw_asset(asset.clone().into())
+                .buy_execution(asset, Unlimited)
+                .deposit_asset(
+                    All.into(),
+                    Location::new(
+                        0,
+                        AccountId32 {
+                            network: None,
+                            id: caller.0,
+                        },
+                    ),
+                )
+                .build();
+            let (dest, program) = self.versioned(program)?;
+            api::xcm::send(&dest, &program)
+        }
+
+        /// Returns the XCM version negotiated with Hydration, if any. Programs are sent in the
+        /// latest version until then.
+        #[ink(message)]
+        pub fn xcm_version(&self) -> Option<u32> {
+            let hydration = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            self.xcm_versions.version_of(&hydration)
+        }
+
+        /// Subscribes to the XCM version of Hydration, which is set once reported. Only callable
+        /// by the owner.
+        #[ink(message)]
+        pub fn subscribe_xcm_version(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            let hydration = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            let (id, report) = self.new_query(hydration.clone())?;
+            self.xcm_versions.subscribe(id, hydration);
+            let (dest, program) =
+                self.versioned(subscribe_xcm(report.query_id, report.max_weight))?;
+            api::xcm::send(&dest, &program)?;
+            Ok(())
+        }
+
+        /// Sets the XCM version of Hydration, e.g. if it does not permit subscriptions by the
+        /// contract. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_xcm_version(&mut self, version: u32) -> Result<()> {
+            self.ensure_owner()?;
+            let hydration = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            self.xcm_versions.set_version(hydration, version);
+            Ok(())
+        }
+
+        /// Queries the pallet of the crate `module_name`, e.g. `pallet_omnipool`, on Hydration.
+        /// Once reported, calls encoded by the contract are re-encoded if the index of the pallet
+        /// changed, and blocked if the pallet was removed or its major version changed. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn query_pallet(&mut self, module_name: Vec<u8>) -> Result<MessageId> {
+            self.ensure_owner()?;
+            let hydration = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            let (id, report) = self.new_query(hydration)?;
+            self.pallets.query(id, module_name.clone());
+            let program = Xcm::builder_unsafe()
+                .query_pallet(module_name, report)
+                .build();
+            let (dest, program) = self.versioned(program)?;
+            api::xcm::send(&dest, &program)?;
+            Ok(id)
+        }
+
+        /// Returns the pallet of the crate `module_name` reported by Hydration, if any.
+        #[ink(message)]
+        pub fn pallet(&self, module_name: Vec<u8>) -> Option<RemotePallet> {
+            self.pallets.get(&module_name)
+        }
+
+        /// Accepts the major version of the pallet of the crate `module_name` reported, once the
+        /// calls encoded by the contract are known to be unchanged in it, so they are no longer
+        /// blocked. Only callable by the owner.
+        #[ink(message)]
+        pub fn accept_pallet(&mut self, module_name: Vec<u8>) -> Result<()> {
+            self.ensure_owner()?;
+            self.pallets.accept(&module_name)?;
+            Ok(())
+        }
+
+        /// Returns the outcome of the dispatch reported by the message `id`, as emitted by
+        /// `TransactSent`.
+        #[ink(message)]
+        pub fn outcome(&self, id: MessageId) -> Option<Outcome> {
+            self.outcomes.get(id)
+        }
+
+        /// Returns the program `dispatch_on_hydra` sends for the same arguments and its hash, so
+        /// it can be dry run on Hydration before being sent. The instructions reporting the
+        /// outcome and the fees left are included, with a query id of `0` as the queries are
+        /// registered once sent. With `Dispatch::Derivative` of the index of the caller, see
+        /// `derive_account`, and `SovereignAccount`, it previews the program `dispatch_as_caller`
+        /// sends.
+        #[ink(message)]
+        pub fn preview_on_hydra(
+            &self,
+            calls: Vec<Vec<u8>>,
+            dispatch: Dispatch,
+            origin_kind: OriginKind,
+            fee_max: Balance,
+            ref_time: u64,
+            proof_size: u64,
+        ) -> Result<(Hash, VersionedXcm<()>)> {
+            let (_, program) = self.program(
+                calls,
+                dispatch,
+                origin_kind,
+                fee_max,
+                Weight::from_parts(ref_time, proof_size),
+                preview_reports(),
+            )?;
+            Ok((self.hash(&program), program))
         }
 
         #[ink(message)]
@@ -46,52 +432,1458 @@ mod execute_on_hydra {
             ref_time: u64,
             proof_size: u64,
         ) -> Result<XcmHash> {
+            self.dispatch_on_hydra(
+                Vec::from([encoded_extrinsic]),
+                Dispatch::Direct,
+                OriginKind::SovereignAccount,
+                fee_max,
+                ref_time,
+                proof_size,
+            )
+        }
+
+        /// Dispatches `calls` on Hydration as a single `Transact` with `origin_kind`, batched with
+        /// `utility.batch_all` if there are several and wrapped in `proxy.proxy` when dispatched
+        /// on behalf of another account, or in `utility.as_derivative` when dispatched by a
+        /// derived account, see `dispatch_as_caller`. Whether an origin kind is permitted is up to
+        /// Hydration; `Superuser` can only be requested by the owner of the contract.
+        ///
+        /// Hydration reports the outcome to the contract, which records it under the id of the
+        /// message emitted by `TransactSent`. Fees left are refunded to the account of the
+        /// contract on Hydration.
+        ///
+        /// Requires the `DISPATCH` role, as the fees are paid by the contract. `fee_max` is
+        /// debited from the deposit of the caller, see `fund`, and the fees left are credited
+        /// back once reported by Hydration.
+        #[ink(message)]
+        pub fn dispatch_on_hydra(
+            &mut self,
+            calls: Vec<Vec<u8>>,
+            dispatch: Dispatch,
+            origin_kind: OriginKind,
+            fee_max: Balance,
+            ref_time: u64,
+            proof_size: u64,
+        ) -> Result<XcmHash> {
+            self.ensure_role(DISPATCH)?;
+            self.dispatch(
+                calls,
+                dispatch,
+                origin_kind,
+                fee_max,
+                Weight::from_parts(ref_time, proof_size),
+            )
+        }
+
+        /// Returns the index and the account derived for `account`, if any, see
+        /// `derive_account`.
+        #[ink(message)]
+        pub fn derived_account(&self, account: AccountId) -> Option<(u16, AccountId)> {
+            let index = self.derivatives.get(account)?;
+            Some((index, self.derivative(index)))
+        }
+
+        /// Assigns the caller an account derived from the account of the contract on Hydration,
+        /// unless it has one already, returning its index and the account. Calls dispatched by
+        /// `dispatch_as_caller` are dispatched by this account, which the caller funds on
+        /// Hydration like any other account.
+        ///
+        /// Requires a deposit, see `fund`, so the indices can't be exhausted for free.
+        #[ink(message)]
+        pub fn derive_account(&mut self) -> Result<(u16, AccountId)> {
+            let caller = self.env().caller();
+            if let Some(derived) = self.derived_account(caller) {
+                return Ok(derived);
+            }
+            if self.escrow.deposit_of(caller) == 0 {
+                return Err(INSUFFICIENT_DEPOSIT.into());
+            }
+            let index = u16::try_from(self.next_derivative)
+                .map_err(|_| StatusCode::from(DERIVATIVES_EXHAUSTED))?;
+            self.next_derivative = self.next_derivative.saturating_add(1);
+            self.derivatives.insert(caller, &index);
+            Ok((index, self.derivative(index)))
+        }
+
+        /// Dispatches `calls` on Hydration like `dispatch_on_hydra`, wrapped in
+        /// `utility.as_derivative` so they are dispatched by the account derived for the caller,
+        /// which is assigned if the caller has none yet, see `derive_account`.
+        ///
+        /// Requires no role, as the calls can only spend what the derived account holds. The fees
+        /// are still paid by the account of the contract, so `fee_max` is debited from the deposit
+        /// of the caller.
+        #[ink(message)]
+        pub fn dispatch_as_caller(
+            &mut self,
+            calls: Vec<Vec<u8>>,
+            fee_max: Balance,
+            ref_time: u64,
+            proof_size: u64,
+        ) -> Result<XcmHash> {
+            let (index, _) = self.derive_account()?;
+            self.dispatch(
+                calls,
+                Dispatch::Derivative(index),
+                OriginKind::SovereignAccount,
+                fee_max,
+                Weight::from_parts(ref_time, proof_size),
+            )
+        }
+
+        /// Sells `amount` of `asset_in` for at least `min_buy_amount` of `asset_out` on the
+        /// Omnipool of Hydration.
+        #[ink(message)]
+        pub fn sell_on_hydra(
+            &mut self,
+            asset_in: AssetId,
+            asset_out: AssetId,
+            amount: Balance,
+            min_buy_amount: Balance,
+            fee_max: Balance,
+            ref_time: u64,
+            proof_size: u64,
+        ) -> Result<XcmHash> {
+            let call = calls::hydration::sell(asset_in, asset_out, amount, min_buy_amount);
+            let call = calls::reindex(call, self.pallets.index(&calls::hydration::OMNIPOOL)?);
+            self.execute_on_hydra(call, fee_max, ref_time, proof_size)
+        }
+
+        /// Buys `amount` of `asset_out` for at most `max_sell_amount` of `asset_in` on the
+        /// Omnipool of Hydration.
+        #[ink(message)]
+        pub fn buy_on_hydra(
+            &mut self,
+            asset_out: AssetId,
+            asset_in: AssetId,
+            amount: Balance,
+            max_sell_amount: Balance,
+            fee_max: Balance,
+            ref_time: u64,
+            proof_size: u64,
+        ) -> Result<XcmHash> {
+            let call = calls::hydration::buy(asset_out, asset_in, amount, max_sell_amount);
+            let call = calls::reindex(call, self.pallets.index(&calls::hydration::OMNIPOOL)?);
+            self.execute_on_hydra(call, fee_max, ref_time, proof_size)
+        }
+
+        #[ink(message, payable)]
+        pub fn query_storage_on_hydra(&mut self, key: Vec<u8>, height: Height) -> Result<()> {
+            self.query_storages_on_hydra(Vec::from([key]), height)
+        }
+
+        /// Queries all `keys` with a single request, paying the fee once. The values are emitted
+        /// in a single `GetCompleted` event.
+        ///
+        /// The caller pays the minimum fee of Hydration with the value transferred. Any surplus is
+        /// refunded once the request completed.
+        #[ink(message, payable)]
+        pub fn query_storages_on_hydra(
+            &mut self,
+            keys: Vec<Vec<u8>>,
+            height: Height,
+        ) -> Result<()> {
+            let height = self.resolve(height)?;
+            let dest = calls::HYDRATION.para_id;
+            let fee = self.min_fee(dest);
+            let surplus = self
+                .env()
+                .transferred_value()
+                .checked_sub(fee)
+                .ok_or(StatusCode::from(INSUFFICIENT_FEE))?;
+            let (id, ()) = self.ids.send(|id| {
+                ismp::get(
+                    id,
+                    Get::new(dest, height, 0, Vec::default(), keys.clone()),
+                    fee,
+                    Some(Callback::to(
+                        0x57ad942b,
+                        Weight::from_parts(800_000_000, 500_000),
+                    )),
+                )
+            })?;
+
+            let payer = self.env().caller();
+            if surplus > 0 {
+                self.payments.insert(id, &(payer, surplus));
+            }
+            self.env().emit_event(FeePaid {
+                id,
+                payer,
+                fee,
+                surplus,
+            });
+            Ok(())
+        }
+
+        /// Reclaims the ids of completed messages, so they are used for new messages. Messages
+        /// still stored by the runtime, e.g. as their callback failed, are removed with their
+        /// responses. The surplus paid for requests which timed out is refunded, while deposits
+        /// whose report timed out are no longer credited.
+        #[ink(message)]
+        pub fn reclaim(&mut self, ids: Vec<MessageId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.ids.reclaim(self.env().account_id(), ids.clone())?;
+            for id in ids {
+                self.outcomes.remove(id);
+                self.escrow.cancel_report(id);
+                self.xcm_versions.cancel(id);
+                self.pallets.cancel(id);
+                self.refund(id)?;
+            }
+            Ok(())
+        }
+
+        // Registers a query for a report of `responder`, returning the id of its message and
+        // the instructions to report to it.
+        fn new_query(&mut self, responder: Location) -> Result<(MessageId, QueryResponseInfo)> {
+            let timeout = self.env().block_number().saturating_add(100);
+            let (id, query_id) = self.ids.send(|id| {
+                api::xcm::new_query(
+                    id,
+                    responder.clone(),
+                    timeout,
+                    Some(Callback::to(
+                        0x641b0b03,
+                        Weight::from_parts(800_000_000, 200_000),
+                    )),
+                )
+            })?;
+            let query_id = query_id.ok_or(StatusCode::from(NO_QUERY))?;
+            Ok((id, response_info(query_id)))
+        }
+
+        // Builds the program reserve transferring `amount` of the relay asset via Asset Hub to
+        // the account of the contract on Hydration, reporting the amount deposited to both
+        // `reports`, one after the other. The fees of each chain are paid with up to half of
+        // `amount`, and the delivery fee of each report with the holding after it was reported.
+        fn fund_program(
+            &self,
+            amount: Balance,
+            reports: (QueryResponseInfo, QueryResponseInfo),
+        ) -> Xcm<()> {
+            let asset: Asset = (Location::parent(), amount).into();
+            let fees: Asset = (Location::parent(), amount / 2).into();
+            let account = hashed_account(POP, self.env().account_id());
+            Xcm::builder_unsafe()
+                .withdraw_asset(asset.clone().into())
+                .initiate_reserve_withdraw(
+                    asset.into(),
+                    Location::new(1, Parachain(calls::ASSET_HUB.para_id)),
+                    Xcm::builder_unsafe()
+                        .buy_execution(fees.clone(), Unlimited)
+                        .deposit_reserve_asset(
+                            All.into(),
+                            Location::new(1, Parachain(calls::HYDRATION.para_id)),
+                            Xcm::builder_unsafe()
+                                .buy_execution(fees, Unlimited)
+                                .report_holding(reports.0, All.into())
+                                .report_holding(reports.1, All.into())
+                                .deposit_asset(
+                                    All.into(),
+                                    Location::new(
+                                        0,
+                                        AccountId32 {
+                                            network: None,
+                                            id: account.0,
+                                        },
+                                    ),
+                                )
+                                .build(),
+                        )
+                        .build(),
+                )
+                .build()
+        }
+
+        // Refunds the surplus paid for the request `id` to its payer, if any.
+        fn refund(&mut self, id: MessageId) -> Result<()> {
+            if let Some((payer, amount)) = self.payments.take(id) {
+                self.env()
+                    .transfer(payer, amount)
+                    .map_err(|_| StatusCode::from(REFUND_FAILED))?;
+                self.env().emit_event(FeeRefunded { id, payer, amount });
+            }
+            Ok(())
+        }
+
+        // Builds the program transacting `calls` on Hydration by `account`, reporting the outcome
+        // and the fees left to `reports` if any. Returns it and Hydration in the XCM version of
+        // Hydration.
+        fn program(
+            &self,
+            calls: Vec<Vec<u8>>,
+            dispatch: Dispatch,
+            origin_kind: OriginKind,
+            fee_max: Balance,
+            weight: Weight,
+            (report, refunds): Reports,
+        ) -> Result<(VersionedLocation, VersionedXcm<()>)> {
+            if origin_kind == OriginKind::Superuser {
+                self.ensure_owner()?;
+            }
+            let call = calls::wrap(&self.hydration(&calls, dispatch)?, &calls, dispatch)
+                .ok_or(StatusCode::from(NO_CALLS))?;
+
             let asset: Asset = (Location::parent(), fee_max).into();
-            let hydra = Junctions::from([Parachain(2034)]);
+            let sovereign = hashed_account(POP, self.env().account_id());
+            let message = Xcm::builder()
+                .withdraw_asset(asset.clone().into())
+                .buy_execution(asset.clone(), Unlimited)
+                .set_appendix(
+                    Xcm::builder_unsafe()
+                        .refund_surplus()
+                        .report_holding(refunds.0, All.into())
+                        .report_holding(refunds.1, All.into())
+                        .deposit_asset(
+                            All.into(),
+                            Location::new(
+                                0,
+                                AccountId32 {
+                                    network: None,
+                                    id: sovereign.0,
+                                },
+                            ),
+                        )
+                        .build(),
+                )
+                .set_error_handler(Xcm::builder_unsafe().report_error(report.clone()).build())
+                .transact(origin_kind, weight, call.into())
+                .report_transact_status(report)
+                .build();
+            self.versioned(message)
+        }
+
+        // Sends the program dispatching `calls`, debiting `fee_max` from the deposit of the caller.
+        fn dispatch(
+            &mut self,
+            calls: Vec<Vec<u8>>,
+            dispatch: Dispatch,
+            origin_kind: OriginKind,
+            fee_max: Balance,
+            weight: Weight,
+        ) -> Result<XcmHash> {
+            // The program previewed, which differs from the program sent only in the query ids of
+            // its reports.
+            let (versioned_dest, program) = self.program(
+                calls.clone(),
+                dispatch,
+                origin_kind,
+                fee_max,
+                weight,
+                preview_reports(),
+            )?;
+            if self.dry_runner.is_some() {
+                // A dry run is only valid for a single dispatch.
+                let dry_run = self
+                    .dry_runs
+                    .take(self.hash(&program))
+                    .ok_or(StatusCode::from(NOT_DRY_RUN))?;
+                if !dry_run.success {
+                    return Err(DRY_RUN_FAILED.into());
+                }
+            }
+
+            let hydra = Junctions::from([Parachain(calls::HYDRATION.para_id)]);
             let dest: Location = Location {
                 parents: 1,
                 interior: hydra,
             };
+            let caller = self.env().caller();
+            self.escrow
+                .debit(caller, fee_max)
+                .map_err(|_| StatusCode::from(INSUFFICIENT_DEPOSIT))?;
 
-            let message: Xcm<()> = Xcm::builder()
-                .withdraw_asset(asset.clone().into())
-                .buy_execution(asset.clone(), Unlimited)
-                .transact(
-                    OriginKind::SovereignAccount,
-                    Weight::from_parts(ref_time, proof_size),
-                    encoded_extrinsic.into(),
-                )
-                .build();
+            // Register queries for the reports of the outcome and the fees left.
+            let (id, report) = self.new_query(dest.clone())?;
+            let query_id = report.query_id;
+            let (refund_id, refund) = self.new_query(dest.clone())?;
+            let (second_id, second) = self.new_query(dest.clone())?;
+            let (_, program) = self.program(
+                calls,
+                dispatch,
+                origin_kind,
+                fee_max,
+                weight,
+                (report, (refund, second)),
+            )?;
+
+            let hash = api::xcm::send(&versioned_dest, &program)?;
+            self.outcomes.insert(id, &Outcome::Pending);
+            self.escrow.credit_report((refund_id, second_id), caller);
+            self.env().emit_event(TransactSent { id, query_id, hash });
+            Ok(hash)
+        }
 
-            Ok(api::xcm::send(&VersionedLocation::V4(dest), &VersionedXcm::V4(message)).unwrap())
+        // Returns Hydration with the indices of the pallets wrapping `calls` as reported, failing if
+        // any of them changed.
+        fn hydration(&self, calls: &[Vec<u8>], dispatch: Dispatch) -> Result<calls::Chain> {
+            let mut chain = calls::HYDRATION;
+            if calls.len() > 1 || matches!(dispatch, Dispatch::Derivative(_)) {
+                chain.utility = self.pallets.index(&chain.utility_pallet())?;
+            }
+            if let Dispatch::Proxy(_) = dispatch {
+                chain.proxy = self.pallets.index(&chain.proxy_pallet())?;
+            }
+            Ok(chain)
         }
 
+        // Returns the account derived from the account of the contract on Hydration with `index`.
+        fn derivative(&self, index: u16) -> AccountId {
+            let sovereign = hashed_account(POP, self.env().account_id());
+            AccountId::from(calls::derivative_account(sovereign.0, index))
+        }
+
+        // Returns `program` and Hydration, its destination, in the XCM version of Hydration.
+        fn versioned(&self, program: Xcm<()>) -> Result<(VersionedLocation, VersionedXcm<()>)> {
+            let dest = Location::new(1, Parachain(calls::HYDRATION.para_id));
+            self.xcm_versions
+                .versioned(dest, program)
+                .map_err(|_| UNSUPPORTED_XCM_VERSION.into())
+        }
+
+        fn ensure_owner(&self) -> Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| UNAUTHORIZED.into())
+        }
+
+        fn ensure_role(&self, role: Role) -> Result<()> {
+            self.access
+                .ensure_role(role, self.env().caller())
+                .map_err(|_| UNAUTHORIZED.into())
+        }
+
+        fn hash(&self, program: &VersionedXcm<()>) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(program))
+        }
+
+        // Resolves `height` against the latest height reported by the owner. Heights ahead of it
+        // are rejected, as Pop can not verify a proof at a height it has no commitment for.
+        fn resolve(&self, height: Height) -> Result<u32> {
+            let para = calls::HYDRATION.para_id;
+            self.heights.resolve(para, height).map_err(|e| match e {
+                ismp_heights::Error::UnknownHeight => UNKNOWN_HEIGHT.into(),
+                ismp_heights::Error::HeightAhead => HEIGHT_AHEAD.into(),
+            })
+        }
+    }
+
+    impl Upgradeable for ExecuteOnHydra {
         #[ink(message)]
-        pub fn query_storage_on_hydra(&mut self, key: Vec<u8>, height: u32) -> Result<()> {
-            ismp::get(
-                self.query_id as MessageId,
-                Get::new(2034, height, 0, Vec::default(), Vec::from([key.clone()])),
-                // 1 HDX
-                1000000000000,
-                Some(Callback::to(
-                    0x57ad942b,
-                    Weight::from_parts(800_000_000, 500_000),
-                )),
-            )?;
-            self.query_id = self.query_id.saturating_add(1);
+        fn storage_version(&self) -> Version {
+            self.storage_version.get()
+        }
+
+        #[ink(message)]
+        fn set_code(&mut self, code_hash: Hash) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            self.storage_version.set_code(code_hash)
+        }
+
+        #[ink(message)]
+        fn migrate(&mut self) -> upgradeable::Result<()> {
+            self.access
+                .ensure_owner(self.env().caller())
+                .map_err(|_| upgradeable::Error::NotOwner)?;
+            upgradeable::migrate(self).map(|_| ())
+        }
+    }
+
+    impl Migrate for ExecuteOnHydra {
+        const VERSION: Version = 1;
 
+        fn storage_version_mut(&mut self) -> &mut StorageVersion {
+            &mut self.storage_version
+        }
+
+        fn migrate_from(&mut self, from: Version) -> upgradeable::Result<()> {
+            // Code before the first version can't be upgraded, so its storage is never migrated.
+            Err(upgradeable::Error::UnsupportedVersion(from))
+        }
+    }
+
+    impl api::xcm::OnResponse for ExecuteOnHydra {
+        #[ink(message)]
+        fn on_response(&mut self, id: MessageId, response: Response) -> pop_api::Result<()> {
+            if self.env().caller() != self.env().account_id() {
+                return Err(UNAUTHORIZED.into());
+            }
+            // The amount deposited by `fund`, or the fees left of a dispatch, is reported by
+            // `ReportHolding`.
+            if let Response::Assets(assets) = &response {
+                let relay = ink::xcm::prelude::AssetId(Location::parent());
+                if self.escrow.report(id, assets, &relay).is_some() {
+                    return Ok(());
+                }
+            }
+            if let Response::PalletsInfo(infos) = &response {
+                let (module_name, pallet) = self
+                    .pallets
+                    .report(id, infos)
+                    .ok_or(StatusCode::from(INVALID_RESPONSE))?;
+                self.env().emit_event(PalletReported {
+                    id,
+                    module_name,
+                    pallet,
+                });
+                return Ok(());
+            }
+            if let Response::Version(version) = response {
+                return match self.xcm_versions.report(id, version) {
+                    Some(_) => Ok(()),
+                    None => Err(INVALID_RESPONSE.into()),
+                };
+            }
+            if self.outcomes.get(id) != Some(Outcome::Pending) {
+                return Err(INVALID_RESPONSE.into());
+            }
+            // The outcome is reported by `ReportTransactStatus`, or by `ReportError` if the program
+            // failed.
+            let outcome = match response {
+                Response::DispatchResult(MaybeErrorCode::Success)
+                | Response::ExecutionResult(None) => Outcome::Success,
+                Response::DispatchResult(
+                    MaybeErrorCode::Error(error) | MaybeErrorCode::TruncatedError(error),
+                ) => Outcome::DispatchFailed(error.into_inner()),
+                Response::ExecutionResult(Some((index, _))) => Outcome::ExecutionFailed(index),
+                _ => return Err(INVALID_RESPONSE.into()),
+            };
+            self.outcomes.insert(id, &outcome);
+            self.env().emit_event(TransactCompleted { id, outcome });
             Ok(())
         }
     }
 
+    // Returns the report of a response to the query `query_id` to the contract on Pop.
+    fn response_info(query_id: QueryId) -> QueryResponseInfo {
+        QueryResponseInfo {
+            destination: Location::new(1, Parachain(POP)),
+            query_id,
+            max_weight: Weight::from_parts(1_000_000, 5_000),
+        }
+    }
+
+    // The report of the outcome of a program and the pair of reports of the fees left, see
+    // `Escrow::report`.
+    type Reports = (QueryResponseInfo, (QueryResponseInfo, QueryResponseInfo));
+
+    // Returns the reports of the outcome and the fees left of programs as previewed, with a
+    // query id of `0`, as the queries are only registered once the program is sent.
+    fn preview_reports() -> Reports {
+        (response_info(0), (response_info(0), response_info(0)))
+    }
+
     impl api::ismp::OnGetResponse for ExecuteOnHydra {
         #[ink(message)]
         fn on_response(&mut self, id: MessageId, values: Vec<StorageValue>) -> pop_api::Result<()> {
             if self.env().caller() != self.env().account_id() {
                 return Err(UNAUTHORIZED.into());
             }
+            self.refund(id)?;
             self.env().emit_event(GetCompleted { id, values });
             Ok(())
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::pallets::PALLET_CHANGED;
+        use access_control::AccessControlled;
+        use api::ismp::OnGetResponse;
+        use ink::{scale::Decode, xcm::v3::PalletInfo};
+        use pop_api_mock::messaging::{Delivery, MockHost};
+
+        // Returns a contract with deposits of the accounts dispatching in tests.
+        fn funded() -> ExecuteOnHydra {
+            let mut contract = ExecuteOnHydra::new();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            for account in [accounts.alice, accounts.bob, accounts.charlie] {
+                contract.escrow.credit(account, 1_000);
+            }
+            contract
+        }
+
+        fn values() -> Vec<StorageValue> {
+            Vec::from([StorageValue {
+                key: b"key".to_vec(),
+                value: Some(b"value".to_vec()),
+            }])
+        }
+
+        #[ink::test]
+        fn query_storage_on_hydra_works() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            assert_eq!(contract.update_height(11).map_err(|e| e.0), Ok(()));
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::At(10))
+                    .map_err(|e| e.0),
+                Ok(())
+            );
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::Latest)
+                    .map_err(|e| e.0),
+                Ok(())
+            );
+            let requests = host.requests();
+            assert_eq!(requests.iter().map(|m| m.id).collect::<Vec<_>>(), [0, 1]);
+            let request = requests[1].get().unwrap();
+            assert_eq!((request.dest, request.height), (2034, 11));
+
+            // The callback is called by the contract itself, so it is authorized.
+            assert!(matches!(
+                host.respond_get(&mut contract, &requests[0].commitment, values()),
+                Ok(Delivery::Executed { .. })
+            ));
+            // Both `FeePaid` and the `GetCompleted` of the response.
+            assert_eq!(ink::env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn query_storage_on_hydra_pays_fee() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let contract_id = ink::env::test::callee::<Environment>();
+            let bob = ink::env::test::default_accounts::<Environment>().bob;
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.set_min_fee(2034, 100).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.min_fee(2034), 100);
+
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(99);
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::Latest)
+                    .map_err(|e| e.0),
+                Err(INSUFFICIENT_FEE)
+            );
+            // The value transferred is credited to the contract.
+            ink::env::test::set_value_transferred::<Environment>(150);
+            ink::env::test::set_account_balance::<Environment>(contract_id, 150);
+            ink::env::test::set_account_balance::<Environment>(bob, 0);
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::Latest)
+                    .map_err(|e| e.0),
+                Ok(())
+            );
+            let request = &host.requests()[0];
+            assert_eq!(request.fee, 100);
+            let event = ink::env::test::recorded_events().last().unwrap();
+            let paid = FeePaid::decode(&mut &event.data[..]).unwrap();
+            assert_eq!(
+                (paid.id, paid.payer, paid.fee, paid.surplus),
+                (request.id, bob, 100, 50)
+            );
+
+            // The surplus is refunded once the response arrived.
+            assert!(matches!(
+                host.respond_get(&mut contract, &request.commitment, values()),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(bob).unwrap(),
+                50
+            );
+            // Emitted before `GetCompleted`.
+            let event = ink::env::test::recorded_events().nth(1).unwrap();
+            let refunded = FeeRefunded::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((refunded.payer, refunded.amount), (bob, 50));
+        }
+
+        #[ink::test]
+        fn reclaim_refunds_timed_out_requests() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let bob = ink::env::test::default_accounts::<Environment>().bob;
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            ink::env::test::set_value_transferred::<Environment>(30);
+            ink::env::test::set_account_balance::<Environment>(
+                ink::env::test::callee::<Environment>(),
+                30,
+            );
+            ink::env::test::set_account_balance::<Environment>(bob, 0);
+            ink::env::test::set_caller::<Environment>(bob);
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+
+            assert!(host.time_out(&host.commitment(0).unwrap()).is_ok());
+            ink::env::test::set_caller::<Environment>(
+                ink::env::test::default_accounts::<Environment>().alice,
+            );
+            assert_eq!(contract.reclaim(Vec::from([0])).map_err(|e| e.0), Ok(()));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(bob).unwrap(),
+                30
+            );
+        }
+
+        #[ink::test]
+        fn on_response_rejects_other_callers() {
+            let mut contract = ExecuteOnHydra::new();
+            assert_eq!(
+                contract.on_response(0, values()).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+        }
+
+        #[ink::test]
+        fn query_storages_on_hydra_works() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let keys = Vec::from([b"key".to_vec(), b"other".to_vec()]);
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            assert_eq!(
+                contract
+                    .query_storages_on_hydra(keys.clone(), Height::Latest)
+                    .map_err(|e| e.0),
+                Ok(())
+            );
+            let requests = host.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].get().unwrap().keys, keys);
+        }
+
+        #[ink::test]
+        fn query_storage_on_hydra_rejects_unknown_heights() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::Latest)
+                    .map_err(|e| e.0),
+                Err(UNKNOWN_HEIGHT)
+            );
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            // Lower heights do not move the latest height back.
+            assert_eq!(contract.update_height(5).map_err(|e| e.0), Ok(()));
+            assert_eq!(contract.latest_height(), Some(10));
+            assert_eq!(
+                contract
+                    .query_storage_on_hydra(b"key".to_vec(), Height::At(11))
+                    .map_err(|e| e.0),
+                Err(HEIGHT_AHEAD)
+            );
+            assert!(host.requests().is_empty());
+        }
+
+        #[ink::test]
+        fn update_height_rejects_other_callers() {
+            let mut contract = ExecuteOnHydra::new();
+            ink::env::test::set_caller::<Environment>(
+                ink::env::test::default_accounts::<Environment>().bob,
+            );
+            assert_eq!(
+                contract.update_height(10).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+        }
+
+        fn transact(message: &VersionedXcm<()>) -> (OriginKind, Vec<u8>) {
+            let VersionedXcm::V4(message) = message else {
+                panic!("unexpected version")
+            };
+            message
+                .0
+                .iter()
+                .find_map(|instruction| match instruction {
+                    Transact {
+                        origin_kind, call, ..
+                    } => Some((*origin_kind, call.clone().into_encoded())),
+                    _ => None,
+                })
+                .unwrap()
+        }
+
+        #[ink::test]
+        fn execute_on_hydra_works() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let call = Vec::from([0, 0, 4, 1]);
+            assert!(contract
+                .execute_on_hydra(call.clone(), 100, 1_000, 1_000)
+                .is_ok());
+            let sent = host.sent();
+            assert_eq!(
+                sent[0].1,
+                VersionedLocation::V4(Location::new(1, Parachain(2034)))
+            );
+            assert_eq!(transact(&sent[0].2), (OriginKind::SovereignAccount, call));
+        }
+
+        #[ink::test]
+        fn dispatch_on_hydra_works() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let calls = Vec::from([Vec::from([0, 0, 4, 1]), Vec::from([0, 0, 4, 2])]);
+            let dispatch = Dispatch::Proxy([1; 32]);
+            assert!(contract
+                .dispatch_on_hydra(calls.clone(), dispatch, OriginKind::Xcm, 100, 1_000, 1_000)
+                .is_ok());
+            let call = calls::wrap(&calls::HYDRATION, &calls, dispatch).unwrap();
+            assert_eq!(transact(&host.sent()[0].2), (OriginKind::Xcm, call));
+        }
+
+        #[ink::test]
+        fn dispatch_on_hydra_requires_role() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            for result in [
+                contract.execute_on_hydra(Vec::from([0, 0, 4, 1]), 100, 1_000, 1_000),
+                contract.sell_on_hydra(0, 10, 100, 90, 100, 1_000, 1_000),
+                contract.buy_on_hydra(0, 10, 100, 90, 100, 1_000, 1_000),
+            ] {
+                assert_eq!(result.map_err(|e| e.0), Err(UNAUTHORIZED));
+            }
+            assert_eq!(
+                contract.grant_role(DISPATCH, accounts.bob),
+                Err(access_control::Error::NotOwner)
+            );
+            assert!(host.sent().is_empty());
+
+            // Granted by the owner, or as operator.
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.grant_role(DISPATCH, accounts.bob), Ok(()));
+            assert_eq!(contract.set_operator(accounts.charlie, true), Ok(()));
+            for caller in [accounts.bob, accounts.charlie] {
+                ink::env::test::set_caller::<Environment>(caller);
+                assert!(contract
+                    .execute_on_hydra(Vec::from([0, 0, 4, 1]), 100, 1_000, 1_000)
+                    .is_ok());
+            }
+            // Neither of them is the owner.
+            assert_eq!(
+                contract.update_height(10).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            assert_eq!(host.sent().len(), 2);
+        }
+
+        #[ink::test]
+        fn derive_account_works() {
+            let mut contract = ExecuteOnHydra::new();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                contract.derive_account().map_err(|e| e.0),
+                Err(INSUFFICIENT_DEPOSIT)
+            );
+            contract.escrow.credit(accounts.bob, 1_000);
+            contract.escrow.credit(accounts.charlie, 1_000);
+            assert_eq!(contract.derived_account(accounts.bob), None);
+
+            let sovereign = hashed_account(POP, ink::env::test::callee::<Environment>());
+            let derived = AccountId::from(calls::derivative_account(sovereign.0, 0));
+            assert_eq!(contract.derive_account().ok(), Some((0, derived)));
+            // The index of a caller is assigned once.
+            assert_eq!(contract.derive_account().ok(), Some((0, derived)));
+            assert_eq!(contract.derived_account(accounts.bob), Some((0, derived)));
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(
+                contract.derive_account().map(|(index, _)| index).ok(),
+                Some(1)
+            );
+            contract.next_derivative = u32::from(u16::MAX) + 1;
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            contract.escrow.credit(accounts.alice, 1_000);
+            assert_eq!(
+                contract.derive_account().map_err(|e| e.0),
+                Err(DERIVATIVES_EXHAUSTED)
+            );
+        }
+
+        #[ink::test]
+        fn dispatch_as_caller_works() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            // No role is required, the calls are dispatched by the account derived for the caller.
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            let calls = Vec::from([Vec::from([0, 0, 4, 1])]);
+            assert!(contract
+                .dispatch_as_caller(calls.clone(), 100, 1_000, 1_000)
+                .is_ok());
+            assert_eq!(
+                contract
+                    .derived_account(accounts.charlie)
+                    .map(|(index, _)| index),
+                Some(0)
+            );
+            assert_eq!(contract.deposit_of(accounts.charlie), 900);
+            let call = calls::wrap(&calls::HYDRATION, &calls, Dispatch::Derivative(0)).unwrap();
+            assert_eq!(
+                transact(&host.sent()[0].2),
+                (OriginKind::SovereignAccount, call)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.django);
+            assert_eq!(
+                contract
+                    .dispatch_as_caller(calls, 100, 1_000, 1_000)
+                    .map_err(|e| e.0),
+                Err(INSUFFICIENT_DEPOSIT)
+            );
+            assert_eq!(host.sent().len(), 1);
+        }
+
+        #[ink::test]
+        fn upgrade_requires_owner() {
+            let mut contract = ExecuteOnHydra::new();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            assert_eq!(contract.storage_version(), 1);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                contract.set_code(Hash::from([1; 32])),
+                Err(upgradeable::Error::NotOwner)
+            );
+            assert_eq!(contract.migrate(), Err(upgradeable::Error::NotOwner));
+
+            // Instances of the code start at its version, so there is nothing to migrate.
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.migrate(), Ok(()));
+            assert_eq!(contract.storage_version(), 1);
+            assert_eq!(ink::env::test::recorded_events().count(), 0);
+        }
+
+        #[ink::test]
+        fn xcm_version_is_negotiated() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let dispatch = |contract: &mut ExecuteOnHydra| {
+                contract.dispatch_on_hydra(
+                    Vec::from([Vec::from([0, 0, 4, 1])]),
+                    Dispatch::Direct,
+                    OriginKind::SovereignAccount,
+                    100,
+                    1_000,
+                    1_000,
+                )
+            };
+            assert_eq!(contract.xcm_version(), None);
+            contract.escrow.credit(accounts.alice, 1_000);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                contract.subscribe_xcm_version().map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            assert_eq!(
+                contract.set_xcm_version(3).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+
+            // The version reported by Hydration is used for later programs.
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.subscribe_xcm_version().map_err(|e| e.0), Ok(()));
+            assert!(matches!(
+                &host.sent()[0].2,
+                VersionedXcm::V4(message) if matches!(message.0[..], [SubscribeVersion { .. }])
+            ));
+            let id = host.requests()[0].id;
+            assert!(matches!(
+                host.respond_xcm(
+                    &mut contract,
+                    &host.commitment(id).unwrap(),
+                    Response::Version(3)
+                ),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.xcm_version(), Some(3));
+            assert!(dispatch(&mut contract).is_ok());
+            assert!(matches!(
+                host.sent()[1],
+                (_, VersionedLocation::V3(_), VersionedXcm::V3(_))
+            ));
+
+            // Programs are not sent if Hydration's version is not supported.
+            assert_eq!(contract.set_xcm_version(2).map_err(|e| e.0), Ok(()));
+            assert_eq!(
+                dispatch(&mut contract).map_err(|e| e.0),
+                Err(UNSUPPORTED_XCM_VERSION)
+            );
+            assert_eq!(host.sent().len(), 2);
+        }
+
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let mut contract = ExecuteOnHydra::new();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(()));
+            assert_eq!(contract.pending_owner(), Some(accounts.bob));
+            // The previous owner stays owner until the transfer is accepted.
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+            assert_eq!(contract.owner(), accounts.bob);
+            assert_eq!(contract.update_height(11).map_err(|e| e.0), Ok(()));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                contract.update_height(12).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            assert!(!contract.has_role(DISPATCH, accounts.alice));
+        }
+
+        #[ink::test]
+        fn dispatch_on_hydra_rejects_invalid_dispatch() {
+            let _host = MockHost::register();
+            let mut contract = funded();
+            let call = Vec::from([Vec::from([0, 0, 4, 1])]);
+            assert_eq!(
+                contract
+                    .dispatch_on_hydra(
+                        Vec::new(),
+                        Dispatch::Direct,
+                        OriginKind::Native,
+                        100,
+                        1_000,
+                        1_000
+                    )
+                    .map_err(|e| e.0),
+                Err(NO_CALLS)
+            );
+            ink::env::test::set_caller::<Environment>(
+                ink::env::test::default_accounts::<Environment>().bob,
+            );
+            assert_eq!(
+                contract
+                    .dispatch_on_hydra(
+                        call.clone(),
+                        Dispatch::Direct,
+                        OriginKind::Superuser,
+                        100,
+                        1_000,
+                        1_000
+                    )
+                    .map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            assert!(contract
+                .dispatch_on_hydra(
+                    call,
+                    Dispatch::Direct,
+                    OriginKind::Native,
+                    100,
+                    1_000,
+                    1_000
+                )
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn sell_on_hydra_works() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            assert!(contract
+                .sell_on_hydra(0, 10, 100, 90, 100, 1_000, 1_000)
+                .is_ok());
+            assert!(contract
+                .buy_on_hydra(0, 10, 100, 110, 100, 1_000, 1_000)
+                .is_ok());
+            let sent = host.sent();
+            assert_eq!(
+                transact(&sent[0].2).1,
+                calls::hydration::sell(0, 10, 100, 90)
+            );
+            assert_eq!(
+                transact(&sent[1].2).1,
+                calls::hydration::buy(0, 10, 100, 110)
+            );
+        }
+
+        #[ink::test]
+        fn pallets_are_verified() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let omnipool = calls::hydration::OMNIPOOL.module_name.to_vec();
+            let report = |contract: &mut ExecuteOnHydra, index, major| {
+                let id = contract.query_pallet(omnipool.clone()).unwrap();
+                let infos = Vec::from([PalletInfo::new(
+                    index,
+                    b"Omnipool".to_vec(),
+                    omnipool.clone(),
+                    major,
+                    0,
+                    0,
+                )
+                .unwrap()]);
+                let response = Response::PalletsInfo(infos.try_into().unwrap());
+                assert!(matches!(
+                    host.respond_xcm(contract, &host.commitment(id).unwrap(), response),
+                    Ok(Delivery::Executed { .. })
+                ));
+            };
+            let sell = |contract: &mut ExecuteOnHydra| {
+                contract.sell_on_hydra(0, 10, 100, 90, 100, 1_000, 1_000)
+            };
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                contract.query_pallet(omnipool.clone()).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+
+            // Calls are re-encoded for the index reported.
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            report(&mut contract, 60, 1);
+            assert!(matches!(
+                &host.sent()[0].2,
+                VersionedXcm::V4(message) if matches!(message.0[..], [QueryPallet { .. }])
+            ));
+            assert_eq!(contract.pallet(omnipool.clone()).unwrap().index, Some(60));
+            assert!(sell(&mut contract).is_ok());
+            let call = calls::hydration::sell(0, 10, 100, 90);
+            assert_eq!(transact(&host.sent()[1].2).1, calls::reindex(call, 60));
+
+            // Calls are blocked once the major version changed, until the owner accepts it.
+            report(&mut contract, 60, 2);
+            assert_eq!(sell(&mut contract).map_err(|e| e.0), Err(PALLET_CHANGED));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                contract.accept_pallet(omnipool.clone()).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(contract.accept_pallet(omnipool).map_err(|e| e.0), Ok(()));
+            assert!(sell(&mut contract).is_ok());
+        }
+
+        #[ink::test]
+        fn dispatch_on_hydra_requires_dry_run() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let bob = ink::env::test::default_accounts::<Environment>().bob;
+            assert_eq!(contract.set_dry_runner(Some(bob)).map_err(|e| e.0), Ok(()));
+            let calls = Vec::from([Vec::from([0, 0, 4, 1])]);
+            let dispatch = |contract: &mut ExecuteOnHydra| {
+                contract
+                    .dispatch_on_hydra(
+                        calls.clone(),
+                        Dispatch::Direct,
+                        OriginKind::SovereignAccount,
+                        100,
+                        1_000,
+                        1_000,
+                    )
+                    .map_err(|e| e.0)
+            };
+            assert_eq!(dispatch(&mut contract), Err(NOT_DRY_RUN));
+
+            let (hash, program) = contract
+                .preview_on_hydra(
+                    calls.clone(),
+                    Dispatch::Direct,
+                    OriginKind::SovereignAccount,
+                    100,
+                    1_000,
+                    1_000,
+                )
+                .unwrap();
+            let result = DryRun {
+                success: true,
+                ref_time: 800,
+                proof_size: 900,
+            };
+            // Only the dry runner reports dry runs.
+            assert_eq!(
+                contract.report_dry_run(hash, result).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+            ink::env::test::set_caller::<Environment>(bob);
+            assert_eq!(
+                contract.report_dry_run(hash, result).map_err(|e| e.0),
+                Ok(())
+            );
+            assert_eq!(contract.dry_run(hash), Some(result));
+
+            assert!(dispatch(&mut contract).is_ok());
+            assert_eq!(transact(&host.sent()[0].2), transact(&program));
+            // The program previewed includes the reports, with the query ids of the queries
+            // registered once sent.
+            let sent = host.sent();
+            let (VersionedXcm::V4(sent), VersionedXcm::V4(previewed)) = (&sent[0].2, &program)
+            else {
+                panic!("unexpected version")
+            };
+            assert_eq!(sent.len(), previewed.len());
+            assert!(matches!(
+                previewed.last(),
+                Some(ReportTransactStatus(QueryResponseInfo { query_id: 0, .. }))
+            ));
+            assert!(matches!(sent.last(), Some(ReportTransactStatus(_))));
+            // The dry run is used up by the dispatch.
+            assert_eq!(dispatch(&mut contract), Err(NOT_DRY_RUN));
+
+            let result = DryRun {
+                success: false,
+                ..result
+            };
+            assert_eq!(
+                contract.report_dry_run(hash, result).map_err(|e| e.0),
+                Ok(())
+            );
+            assert_eq!(dispatch(&mut contract), Err(DRY_RUN_FAILED));
+            assert_eq!(host.sent().len(), 1);
+        }
+
+        fn dispatch(contract: &mut ExecuteOnHydra) -> MessageId {
+            assert!(contract
+                .execute_on_hydra(Vec::from([0, 0, 4, 1]), 100, 1_000, 1_000)
+                .is_ok());
+            let event = ink::env::test::recorded_events().last().unwrap();
+            TransactSent::decode(&mut &event.data[..]).unwrap().id
+        }
+
+        #[ink::test]
+        fn dispatch_reports_outcome() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let id = dispatch(&mut contract);
+            assert_eq!(contract.outcome(id), Some(Outcome::Pending));
+
+            let query = host
+                .message(ink::env::test::callee::<Environment>(), id)
+                .unwrap();
+            let (responder, _) = query.query().unwrap();
+            assert_eq!(responder, Location::new(1, Parachain(2034)));
+            let report = QueryResponseInfo {
+                destination: Location::new(1, Parachain(POP)),
+                query_id: query.query_id.unwrap(),
+                max_weight: Weight::from_parts(1_000_000, 5_000),
+            };
+            let VersionedXcm::V4(program) = &host.sent()[0].2 else {
+                panic!("unexpected version")
+            };
+            assert!(matches!(
+                &program.0[..],
+                [
+                    WithdrawAsset(_),
+                    BuyExecution { .. },
+                    SetAppendix(_),
+                    SetErrorHandler(handler),
+                    Transact { .. },
+                    ReportTransactStatus(status),
+                ] if handler.0 == [ReportError(report.clone())] && *status == report
+            ));
+
+            let response = Response::DispatchResult(MaybeErrorCode::Success);
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &query.commitment, response),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.outcome(id), Some(Outcome::Success));
+        }
+
+        #[ink::test]
+        fn dispatch_reports_failures() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            let (failed, errored) = (dispatch(&mut contract), dispatch(&mut contract));
+            let error = MaybeErrorCode::Error(Vec::from([1, 2]).try_into().unwrap());
+            let response = Response::DispatchResult(error);
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &host.commitment(failed).unwrap(), response),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(
+                contract.outcome(failed),
+                Some(Outcome::DispatchFailed(Vec::from([1, 2])))
+            );
+
+            let response = Response::ExecutionResult(Some((4, XcmError::Barrier)));
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &host.commitment(errored).unwrap(), response),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.outcome(errored), Some(Outcome::ExecutionFailed(4)));
+        }
+
+        fn reported(amount: Balance) -> Response {
+            let asset: Asset = (Location::parent(), amount).into();
+            Response::Assets(Vec::from([asset]).into())
+        }
+
+        #[ink::test]
+        fn fund_credits_reported_deposit() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let bob = ink::env::test::default_accounts::<Environment>().bob;
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.fund().map_err(|e| e.0), Ok(()));
+            let event = ink::env::test::recorded_events().last().unwrap();
+            let funded = Funded::decode(&mut &event.data[..]).unwrap();
+            assert_eq!((funded.id, funded.account, funded.value), (0, bob, 100));
+
+            // Reserve transferred via Asset Hub.
+            let VersionedXcm::V4(program) = &host.executed()[0].1 else {
+                panic!("unexpected version")
+            };
+            assert!(matches!(
+                &program.0[..],
+                [WithdrawAsset(_), InitiateReserveWithdraw { reserve, .. }]
+                    if *reserve == Location::new(1, Parachain(1000))
+            ));
+
+            // Credited once Hydration reported the amount deposited.
+            assert_eq!(contract.deposit_of(bob), 0);
+            let query = host
+                .message(ink::env::test::callee::<Environment>(), 0)
+                .unwrap();
+            assert_eq!(query.query().unwrap().0, Location::new(1, Parachain(2034)));
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &query.commitment, reported(80)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.deposit_of(bob), 0);
+            // Hydration pays the delivery fee of each report from the holding reported, so the
+            // second report is less by the fee of the first, which is not credited.
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &host.commitment(1).unwrap(), reported(75)),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert_eq!(contract.deposit_of(bob), 70);
+        }
+
+        #[ink::test]
+        fn dispatch_spends_deposit() {
+            let host = MockHost::register();
+            let mut contract = ExecuteOnHydra::new();
+            let alice = ink::env::test::default_accounts::<Environment>().alice;
+            contract.escrow.credit(alice, 150);
+
+            // `fee_max` is debited upfront.
+            let id = dispatch(&mut contract);
+            assert_eq!(contract.deposit_of(alice), 50);
+            assert_eq!(
+                contract
+                    .execute_on_hydra(Vec::from([0, 0, 4, 1]), 100, 1_000, 1_000)
+                    .map_err(|e| e.0),
+                Err(INSUFFICIENT_DEPOSIT)
+            );
+            assert_eq!(host.sent().len(), 1);
+
+            // The fees left are credited back once reported, independent of the outcome.
+            for (refund, amount) in [(id + 1, 32), (id + 2, 31)] {
+                let refund = host.commitment(refund).unwrap();
+                assert!(matches!(
+                    host.respond_xcm(&mut contract, &refund, reported(amount)),
+                    Ok(Delivery::Executed { .. })
+                ));
+            }
+            assert_eq!(contract.deposit_of(alice), 80);
+            assert_eq!(contract.outcome(id), Some(Outcome::Pending));
+            // Holdings reported to other queries are not credited.
+            assert!(matches!(
+                host.respond_xcm(&mut contract, &host.commitment(id).unwrap(), reported(30)),
+                Ok(Delivery::Failed { .. })
+            ));
+            assert_eq!(contract.deposit_of(alice), 80);
+
+            // Deposits are withdrawn to the account of the caller on Hydration.
+            assert_eq!(
+                contract.withdraw_deposit(81).map_err(|e| e.0),
+                Err(INSUFFICIENT_DEPOSIT)
+            );
+            assert!(contract.withdraw_deposit(80).is_ok());
+            assert_eq!(contract.deposit_of(alice), 0);
+            assert_eq!(host.sent().len(), 2);
+        }
+
+        fn query(contract: &mut ExecuteOnHydra) -> Result<()> {
+            contract.query_storage_on_hydra(b"key".to_vec(), Height::Latest)
+        }
+
+        #[ink::test]
+        fn ids_are_shared_by_ismp_and_xcm() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            // An id in use, e.g. from before an upgrade, is skipped.
+            assert!(ismp::get(
+                1,
+                Get::new(2034, 10, 0, Vec::default(), Vec::new()),
+                0,
+                None
+            )
+            .is_ok());
+
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+            assert_eq!(dispatch(&mut contract), 2);
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+            let requests = host.requests();
+            assert_eq!(
+                requests.iter().map(|m| m.id).collect::<Vec<_>>(),
+                [1, 0, 2, 3, 4, 5]
+            );
+        }
+
+        #[ink::test]
+        fn reclaim_works() {
+            let host = MockHost::register();
+            let mut contract = funded();
+            assert_eq!(contract.update_height(10).map_err(|e| e.0), Ok(()));
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+            let id = dispatch(&mut contract);
+
+            // Pending messages can not be reclaimed.
+            assert_eq!(
+                contract.reclaim(Vec::from([0])).map_err(|e| e.0),
+                Err(pop_api_mock::messaging::REQUEST_PENDING)
+            );
+            // The first message is removed once its callback succeeds, the second is stored as it
+            // timed out.
+            assert!(matches!(
+                host.respond_get(&mut contract, &host.commitment(0).unwrap(), values()),
+                Ok(Delivery::Executed { .. })
+            ));
+            assert!(host.time_out(&host.commitment(1).unwrap()).is_ok());
+            let response = Response::DispatchResult(MaybeErrorCode::Success);
+            assert!(host
+                .respond_xcm(&mut contract, &host.commitment(id).unwrap(), response)
+                .is_ok());
+            assert_eq!(
+                contract.reclaim(Vec::from([0, 1, id])).map_err(|e| e.0),
+                Ok(())
+            );
+            assert_eq!(
+                host.message(ink::env::test::callee::<Environment>(), 1),
+                None
+            );
+            assert_eq!(contract.outcome(id), None);
+
+            // Reclaimed ids are used again, and can not be reclaimed twice.
+            assert_eq!(query(&mut contract).map_err(|e| e.0), Ok(()));
+            assert_eq!(host.requests().last().unwrap().id, id);
+            for reclaimed in [Vec::from([1]), Vec::from([2, 2]), Vec::from([5])] {
+                assert_eq!(
+                    contract.reclaim(reclaimed).map_err(|e| e.0),
+                    Err(ids::NOT_ALLOCATED)
+                );
+            }
+        }
+
+        #[ink::test]
+        fn reclaim_rejects_other_callers() {
+            let mut contract = ExecuteOnHydra::new();
+            ink::env::test::set_caller::<Environment>(
+                ink::env::test::default_accounts::<Environment>().bob,
+            );
+            assert_eq!(
+                contract.reclaim(Vec::from([0])).map_err(|e| e.0),
+                Err(UNAUTHORIZED)
+            );
+        }
+    }
 }
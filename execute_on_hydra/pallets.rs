@@ -0,0 +1,194 @@
+//! Verification of the pallets of Hydration which calls are encoded for. Calls are encoded with the
+//! index of their pallet, which changes when runtime upgrades add or remove pallets, and their
+//! arguments may change with the major version of the crate of the pallet.
+//!
+//! The pallets are queried with `QueryPallet` and the `Response::PalletsInfo` reported is cached.
+//! Calls of a pallet reported at another index are re-encoded for it, while calls of a pallet which
+//! was removed, or whose major version changed since it was first reported, are blocked until the
+//! owner accepts the pallet as reported.
+
+use ink::{prelude::vec::Vec, storage::Mapping, xcm::v3::PalletInfo};
+use pop_api::{messaging::MessageId, StatusCode};
+
+use crate::{calls::Pallet, Result};
+
+/// The pallet was removed, or its major version changed, so its calls may be encoded wrongly.
+pub const PALLET_CHANGED: u32 = u32::MAX - 14;
+/// No report of the pallet was received.
+pub const UNKNOWN_PALLET: u32 = u32::MAX - 15;
+
+/// A pallet of Hydration as reported by `QueryPallet`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub struct RemotePallet {
+    /// The index of the pallet in the runtime of Hydration, or `None` if it has no such pallet.
+    pub index: Option<u8>,
+    /// The version of the crate of the pallet.
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The major version calls are known to be encoded for: the version first reported, or the
+    /// version accepted by the owner since.
+    pub accepted_major: u32,
+}
+
+/// The pallets reported by Hydration.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct Pallets {
+    /// The pallets reported, by the name of their crate.
+    reported: Mapping<Vec<u8>, RemotePallet>,
+    /// The crates awaiting a report, by the id of the message of the query.
+    queries: Mapping<MessageId, Vec<u8>>,
+}
+
+impl Pallets {
+    /// Returns the pallet reported for the crate `module_name`, if any.
+    pub fn get(&self, module_name: &[u8]) -> Option<RemotePallet> {
+        self.reported.get(module_name)
+    }
+
+    /// Awaits the report of the crate `module_name` to the query of message `id`.
+    pub fn query(&mut self, id: MessageId, module_name: Vec<u8>) {
+        self.queries.insert(id, &module_name);
+    }
+
+    /// Stops awaiting the report of message `id`, e.g. once it timed out so its id can be used
+    /// again.
+    pub fn cancel(&mut self, id: MessageId) -> Option<Vec<u8>> {
+        self.queries.take(id)
+    }
+
+    /// Caches the first instance of the pallet in `infos`, reported to the query of message `id`.
+    /// Returns the name of the crate queried and the pallet cached, or `None` if no report is
+    /// awaited.
+    pub fn report(
+        &mut self,
+        id: MessageId,
+        infos: &[PalletInfo],
+    ) -> Option<(Vec<u8>, RemotePallet)> {
+        let module_name = self.queries.take(id)?;
+        let accepted_major = self.get(&module_name).map(|pallet| pallet.accepted_major);
+        let pallet = match infos.first() {
+            Some(info) => RemotePallet {
+                // Indices of pallets are encoded as a single byte in calls.
+                index: u8::try_from(info.index).ok(),
+                major: info.major,
+                minor: info.minor,
+                patch: info.patch,
+                accepted_major: accepted_major.unwrap_or(info.major),
+            },
+            None => RemotePallet {
+                index: None,
+                major: 0,
+                minor: 0,
+                patch: 0,
+                accepted_major: accepted_major.unwrap_or_default(),
+            },
+        };
+        self.reported.insert(&module_name, &pallet);
+        Some((module_name, pallet))
+    }
+
+    /// Accepts the major version of the pallet reported for the crate `module_name`, once its
+    /// calls are known to be encoded the same.
+    pub fn accept(&mut self, module_name: &[u8]) -> Result<RemotePallet> {
+        let mut pallet = self
+            .get(module_name)
+            .ok_or(StatusCode::from(UNKNOWN_PALLET))?;
+        pallet.accepted_major = pallet.major;
+        self.reported.insert(module_name, &pallet);
+        Ok(pallet)
+    }
+
+    /// Returns the index calls of `pallet` are encoded with: the index reported, or the index
+    /// they are encoded for if the pallet was not reported. Fails with [`PALLET_CHANGED`] if the
+    /// pallet was removed or its major version changed.
+    pub fn index(&self, pallet: &Pallet) -> Result<u8> {
+        let Some(reported) = self.get(pallet.module_name) else {
+            return Ok(pallet.index);
+        };
+        match reported.index {
+            Some(index) if reported.major == reported.accepted_major => Ok(index),
+            _ => Err(PALLET_CHANGED.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calls::hydration::OMNIPOOL;
+
+    fn info(index: u32, major: u32) -> PalletInfo {
+        PalletInfo::new(
+            index,
+            b"Omnipool".to_vec(),
+            b"pallet_omnipool".to_vec(),
+            major,
+            0,
+            0,
+        )
+        .unwrap()
+    }
+
+    fn index(pallets: &Pallets) -> core::result::Result<u8, u32> {
+        pallets.index(&OMNIPOOL).map_err(|e| e.0)
+    }
+
+    #[ink::test]
+    fn report_caches_pallet() {
+        let mut pallets = Pallets::default();
+        assert_eq!(pallets.report(1, &[info(60, 1)]), None);
+        assert_eq!(index(&pallets), Ok(59));
+
+        pallets.query(1, OMNIPOOL.module_name.to_vec());
+        let (module_name, pallet) = pallets.report(1, &[info(60, 1)]).unwrap();
+        assert_eq!(module_name, OMNIPOOL.module_name);
+        assert_eq!(pallet.index, Some(60));
+        assert_eq!(pallet.accepted_major, 1);
+        assert_eq!(pallets.get(OMNIPOOL.module_name), Some(pallet));
+        assert_eq!(index(&pallets), Ok(60));
+        // Reports are only applied once.
+        assert_eq!(pallets.report(1, &[info(61, 1)]), None);
+
+        pallets.query(2, OMNIPOOL.module_name.to_vec());
+        assert_eq!(pallets.cancel(2), Some(OMNIPOOL.module_name.to_vec()));
+        assert_eq!(pallets.report(2, &[info(61, 1)]), None);
+    }
+
+    #[ink::test]
+    fn index_blocks_changed_pallets() {
+        let mut pallets = Pallets::default();
+        assert_eq!(
+            pallets.accept(OMNIPOOL.module_name).map_err(|e| e.0),
+            Err(UNKNOWN_PALLET)
+        );
+        pallets.query(1, OMNIPOOL.module_name.to_vec());
+        pallets.report(1, &[info(60, 1)]);
+
+        // Calls are blocked once the major version changed, until it is accepted.
+        pallets.query(2, OMNIPOOL.module_name.to_vec());
+        assert_eq!(
+            pallets.report(2, &[info(60, 2)]).unwrap().1.accepted_major,
+            1
+        );
+        assert_eq!(index(&pallets), Err(PALLET_CHANGED));
+        assert_eq!(
+            pallets
+                .accept(OMNIPOOL.module_name)
+                .map(|p| p.accepted_major)
+                .map_err(|e| e.0),
+            Ok(2)
+        );
+        assert_eq!(index(&pallets), Ok(60));
+
+        // Calls of removed pallets are blocked.
+        pallets.query(3, OMNIPOOL.module_name.to_vec());
+        assert_eq!(pallets.report(3, &[]).unwrap().1.index, None);
+        assert_eq!(index(&pallets), Err(PALLET_CHANGED));
+        assert!(pallets.accept(OMNIPOOL.module_name).is_ok());
+        assert_eq!(index(&pallets), Err(PALLET_CHANGED));
+    }
+}
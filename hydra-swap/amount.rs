@@ -0,0 +1,346 @@
+use ink::xcm::prelude::*;
+use pop_api::messaging::xcm::Location;
+
+use crate::{
+    xcm::{fungible_amount, native_asset, usdt, ASSET_HUB, HYDRATION},
+    Error, Result,
+};
+
+/// Decimals of the relay chain native asset (PAS).
+pub const PAS_DECIMALS: u8 = 10;
+/// Decimals of USDT.
+pub const USDT_DECIMALS: u8 = 6;
+
+/// An asset amount in planck, carrying the decimals of the asset it belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub struct Amount {
+    planck: u128,
+    decimals: u8,
+}
+
+impl Amount {
+    /// Creates an amount from its planck value.
+    pub const fn from_planck(planck: u128, decimals: u8) -> Self {
+        Self { planck, decimals }
+    }
+
+    /// Creates an amount from whole units, e.g. `5` PAS.
+    pub fn from_units(units: u128, decimals: u8) -> Option<Self> {
+        let planck = units.checked_mul(unit(decimals)?)?;
+        Some(Self { planck, decimals })
+    }
+
+    /// Creates an amount from a human readable decimal, e.g. `1.25` USDT is `(1, 25, 2)`.
+    ///
+    /// Fraction digits beyond the asset's decimals are truncated.
+    pub fn from_decimal(
+        whole: u128,
+        fraction: u128,
+        fraction_digits: u8,
+        decimals: u8,
+    ) -> Option<Self> {
+        let fraction = if fraction_digits > decimals {
+            fraction / unit(fraction_digits - decimals)?
+        } else {
+            fraction.checked_mul(unit(decimals - fraction_digits)?)?
+        };
+        if fraction >= unit(decimals)? {
+            return None;
+        }
+        let planck = whole.checked_mul(unit(decimals)?)?.checked_add(fraction)?;
+        Some(Self { planck, decimals })
+    }
+
+    /// Returns the amount in planck.
+    pub fn planck(&self) -> u128 {
+        self.planck
+    }
+
+    /// Returns the decimals of the asset.
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    /// Returns the amount as whole units and the remaining fraction in planck.
+    pub fn to_units(&self) -> (u128, u128) {
+        let unit = unit(self.decimals).unwrap_or(1);
+        (self.planck / unit, self.planck % unit)
+    }
+
+    /// Returns the same value expressed with `decimals`, truncating any lost precision.
+    pub fn rescale(&self, decimals: u8) -> Option<Self> {
+        let planck = if decimals >= self.decimals {
+            self.planck.checked_mul(unit(decimals - self.decimals)?)?
+        } else {
+            self.planck / unit(self.decimals - decimals)?
+        };
+        Some(Self { planck, decimals })
+    }
+
+    /// Returns the amount in planck of an asset with `decimals`, failing with
+    /// [`Error::InexactAmount`] if it can't be expressed exactly with them.
+    pub fn planck_in(&self, decimals: u8) -> Result<u128> {
+        let amount = self.rescale(decimals).ok_or(Error::InexactAmount)?;
+        if amount.rescale(self.decimals) != Some(*self) {
+            return Err(Error::InexactAmount);
+        }
+        Ok(amount.planck)
+    }
+
+    /// Subtracts `other`, which must have the same decimals.
+    pub fn checked_sub(&self, other: &Amount) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Self {
+            planck: self.planck.checked_sub(other.planck)?,
+            decimals: self.decimals,
+        })
+    }
+
+    /// Returns whether the amount is zero.
+    pub fn is_zero(&self) -> bool {
+        self.planck == 0
+    }
+}
+
+/// Returns the planck value of one whole unit.
+fn unit(decimals: u8) -> Option<u128> {
+    10u128.checked_pow(decimals.into())
+}
+
+/// Metadata of an asset known to this contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub decimals: u8,
+    pub existential_deposit: u128,
+}
+
+/// Returns the metadata of the asset `id` on parachain `chain`, if known.
+///
+/// The asset id is expected to be relative to a sibling parachain, as in `native_asset`.
+pub fn asset_info(id: &AssetId, chain: u32) -> Option<AssetInfo> {
+    let usdt = usdt(0).id.0;
+    let existential_deposit = if id.0 == Location::parent() {
+        match chain {
+            // 0.1 PAS.
+            ASSET_HUB => 1_000_000_000,
+            // 0.0017540000 PAS.
+            HYDRATION => 17_540_000,
+            _ => return None,
+        }
+    } else if id.0 == usdt {
+        match chain {
+            // 0.07 USDT.
+            ASSET_HUB => 70_000,
+            // 0.01 USDT.
+            HYDRATION => 10_000,
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+    let decimals = if id.0 == usdt {
+        USDT_DECIMALS
+    } else {
+        PAS_DECIMALS
+    };
+    Some(AssetInfo {
+        decimals,
+        existential_deposit,
+    })
+}
+
+/// Returns the amount of `asset`, rejecting non-fungible or zero amounts.
+pub(crate) fn non_zero_amount(asset: &Asset) -> Result<u128> {
+    match asset.fun {
+        Fungible(0) => Err(Error::ZeroAmount),
+        Fungible(amount) => Ok(amount),
+        _ => Err(Error::NonFungibleAsset),
+    }
+}
+
+/// Ensures `fee` is paid in the same asset as `amount` and leaves something after being paid.
+pub(crate) fn ensure_fee_within(fee: &Asset, amount: &Asset) -> Result<()> {
+    if fee.id != amount.id {
+        return Err(Error::AssetMismatch);
+    }
+    if fungible_amount(fee) >= fungible_amount(amount) {
+        return Err(Error::FeeExceedsAmount);
+    }
+    Ok(())
+}
+
+/// Ensures `asset` can be deposited on `chain` without being below its existential deposit.
+///
+/// Assets unknown to the contract are not checked.
+pub(crate) fn ensure_existential_deposit(asset: &Asset, chain: u32) -> Result<()> {
+    match asset_info(&asset.id, chain) {
+        Some(info) if fungible_amount(asset) < info.existential_deposit => {
+            Err(Error::BelowExistentialDeposit)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Accounting of the native value transferred to the contract for a swap on Hydration.
+///
+/// The transferred value pays for the swap input and a fee on every hop buying execution with
+/// the native asset, anything left over is refunded to the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SwapBudget {
+    /// The amount given to the swap.
+    pub give: u128,
+    /// The fees bought on all hops.
+    pub fees: u128,
+    /// The remainder of the transferred value.
+    pub refund: u128,
+}
+
+impl SwapBudget {
+    /// Reconciles the `transferred` value with the swap input `give` and the `fee` paid on each of
+    /// the `hops`.
+    ///
+    /// Both `give` and `fee` must be the native asset.
+    pub fn new(transferred: u128, give: &Asset, fee: &Asset, hops: u128) -> Result<Self> {
+        if give.id != native_asset(0).id {
+            return Err(Error::AssetMismatch);
+        }
+        let give_amount = non_zero_amount(give)?;
+        ensure_fee_within(fee, give)?;
+        let fees = fungible_amount(fee)
+            .checked_mul(hops)
+            .ok_or(Error::InsufficientValue)?;
+        let total = give_amount
+            .checked_add(fees)
+            .ok_or(Error::InsufficientValue)?;
+        let refund = transferred
+            .checked_sub(total)
+            .ok_or(Error::InsufficientValue)?;
+        Ok(Self {
+            give: give_amount,
+            fees,
+            refund,
+        })
+    }
+
+    /// Returns the amount withdrawn from the contract to execute the swap.
+    pub fn total(&self) -> u128 {
+        self.give.saturating_add(self.fees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_between_units_and_planck_works() {
+        let pas = Amount::from_units(5, PAS_DECIMALS).unwrap();
+        assert_eq!(pas.planck(), 50_000_000_000);
+        assert_eq!(pas.to_units(), (5, 0));
+
+        let usdt = Amount::from_decimal(1, 25, 2, USDT_DECIMALS).unwrap();
+        assert_eq!(usdt.planck(), 1_250_000);
+        assert_eq!(usdt.to_units(), (1, 250_000));
+        // Fraction digits beyond the asset's decimals are truncated.
+        assert_eq!(
+            Amount::from_decimal(0, 1_234_567, 7, USDT_DECIMALS)
+                .unwrap()
+                .planck(),
+            123_456
+        );
+        // Fraction must be less than one unit.
+        assert_eq!(Amount::from_decimal(0, 100, 2, USDT_DECIMALS), None);
+        assert_eq!(Amount::from_units(u128::MAX, PAS_DECIMALS), None);
+    }
+
+    #[test]
+    fn rescale_works() {
+        let pas = Amount::from_planck(12_345_678_901, PAS_DECIMALS);
+        assert_eq!(
+            pas.rescale(USDT_DECIMALS),
+            Some(Amount::from_planck(1_234_567, USDT_DECIMALS))
+        );
+        let usdt = Amount::from_planck(1_234_567, USDT_DECIMALS);
+        assert_eq!(
+            usdt.rescale(PAS_DECIMALS),
+            Some(Amount::from_planck(12_345_670_000, PAS_DECIMALS))
+        );
+        // Amounts with different decimals can't be subtracted.
+        assert_eq!(pas.checked_sub(&usdt), None);
+    }
+
+    #[test]
+    fn planck_in_rejects_inexact_amounts() {
+        let usdt = Amount::from_units(2, 18).unwrap();
+        assert_eq!(usdt.planck_in(USDT_DECIMALS), Ok(2_000_000));
+        assert_eq!(
+            Amount::from_planck(1, 18).planck_in(USDT_DECIMALS),
+            Err(Error::InexactAmount)
+        );
+        assert_eq!(
+            Amount::from_planck(u128::MAX, USDT_DECIMALS).planck_in(PAS_DECIMALS),
+            Err(Error::InexactAmount)
+        );
+    }
+
+    #[test]
+    fn validation_works() {
+        assert_eq!(non_zero_amount(&native_asset(0)), Err(Error::ZeroAmount));
+        assert_eq!(
+            ensure_fee_within(&native_asset(10), &native_asset(10)),
+            Err(Error::FeeExceedsAmount)
+        );
+        assert_eq!(
+            ensure_fee_within(&native_asset(9), &native_asset(10)),
+            Ok(())
+        );
+        assert_eq!(
+            ensure_existential_deposit(&native_asset(999_999_999), ASSET_HUB),
+            Err(Error::BelowExistentialDeposit)
+        );
+        assert_eq!(
+            ensure_existential_deposit(&native_asset(1_000_000_000), ASSET_HUB),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn swap_budget_works() {
+        let budget = SwapBudget::new(1_000, &native_asset(500), &native_asset(100), 2).unwrap();
+        assert_eq!(
+            budget,
+            SwapBudget {
+                give: 500,
+                fees: 200,
+                refund: 300
+            }
+        );
+        assert_eq!(budget.total(), 700);
+        // Exactly enough leaves nothing to refund.
+        assert_eq!(
+            SwapBudget::new(700, &native_asset(500), &native_asset(100), 2).map(|b| b.refund),
+            Ok(0)
+        );
+        // Giving more than what arrives is rejected.
+        assert_eq!(
+            SwapBudget::new(699, &native_asset(500), &native_asset(100), 2),
+            Err(Error::InsufficientValue)
+        );
+        assert_eq!(
+            SwapBudget::new(1_000, &native_asset(0), &native_asset(0), 2),
+            Err(Error::ZeroAmount)
+        );
+        assert_eq!(
+            SwapBudget::new(1_000, &native_asset(100), &native_asset(100), 2),
+            Err(Error::FeeExceedsAmount)
+        );
+        let usdt: Asset = (Location::new(1, [Parachain(ASSET_HUB)]), 500).into();
+        assert_eq!(
+            SwapBudget::new(1_000, &usdt, &native_asset(100), 2),
+            Err(Error::AssetMismatch)
+        );
+    }
+}
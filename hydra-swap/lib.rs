@@ -1,51 +1,106 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
-use api::xcm::{Junctions, Location};
+use access_control::{AccessControl, Role};
 use ink::{
     env::debug_println,
     prelude::vec::Vec,
     xcm::{
-        prelude::{
-            Asset, AssetId,
-            Instruction::DepositAsset,
-            Junction::{GeneralIndex, PalletInstance, Parachain},
-            Reanchorable,
-            WildAsset::All,
-        },
-        v4::{Instruction::WithdrawAsset, Xcm},
+        prelude::{Asset, QueryResponseInfo, Weight},
         VersionedXcm,
     },
 };
+use message_ids::MessageIds;
 use pop_api::{
     messaging::{self as api, MessageId},
     StatusCode,
 };
 use xcm::{
-    fee_amount, local_account, native_asset, DepositedLocation, XcmMessageBuilder, ASSET_HUB,
-    HYDRATION, POP,
+    fund_direct_xcm, fund_indirect_xcm, native_asset, para, repatriate_xcm, transfer_and_swap_xcm,
+    usdt, DepositedLocation, ASSET_HUB, HYDRATION, POP,
 };
 
-mod xcm;
+pub mod amount;
+pub mod xcm;
 
-pub type Result<T> = core::result::Result<T, StatusCode>;
+pub type Result<T> = core::result::Result<T, Error>;
 
+/// The role required to swap on Hydration.
+pub const SWAP: Role = 1;
+/// The role required to fund accounts on other chains.
+pub const FUND: Role = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    StatusCode(u32),
+    /// The amount is zero.
+    ZeroAmount,
+    /// The asset is not fungible.
+    NonFungibleAsset,
+    /// The amount would be below the existential deposit on the destination.
+    BelowExistentialDeposit,
+    /// The fee is equal to or larger than the amount it is paid from.
+    FeeExceedsAmount,
+    /// The fee is not paid in the same asset as the amount it is paid from.
+    AssetMismatch,
+    /// The deposited location is not supported.
+    UnsupportedLocation,
+    /// The transferred value does not cover the amount given and the fees.
+    InsufficientValue,
+    /// The refund to the caller failed.
+    TransferFailed,
+    /// The caller is not permitted to call the message.
+    Unauthorized,
+    /// No query was registered for the response.
+    NoQuery,
+    /// The amount can't be expressed in the decimals of its asset without losing precision.
+    InexactAmount,
+}
+
+impl From<StatusCode> for Error {
+    fn from(value: StatusCode) -> Self {
+        Error::StatusCode(value.0)
+    }
+}
+
+impl From<access_control::Error> for Error {
+    fn from(_: access_control::Error) -> Self {
+        Error::Unauthorized
+    }
+}
+
+#[access_control::access_controlled(access)]
 #[ink::contract]
 mod hydration_swapping {
     use super::*;
-    use xcm::{get_global_context, para};
+    use amount::{ensure_existential_deposit, Amount, SwapBudget, PAS_DECIMALS, USDT_DECIMALS};
+
+    /// The number of hops buying execution with the native fee when swapping on Hydration: the
+    /// intermediary hop and Hydration itself.
+    const NATIVE_FEE_HOPS: u128 = 2;
 
     #[ink(storage)]
     #[derive(Default)]
-    pub struct CrosschainSwap;
+    pub struct CrosschainSwap {
+        access: AccessControl,
+        /// The ids of the messages awaiting a response.
+        ids: MessageIds,
+    }
 
     impl CrosschainSwap {
         #[ink(constructor, payable)]
         pub fn new() -> Self {
-            Default::default()
+            Self {
+                access: AccessControl::new(Self::env().caller()),
+                ids: MessageIds::default(),
+            }
         }
 
         /// Swap USDT on Hydration and send back to the destination location.
         ///
+        /// The transferred value must cover `max_amount_in` plus `fee_amount` for each of the two
+        /// hops, any remainder is refunded to the caller.
+        ///
         /// The method does a few different things:
         /// 1. Transfers from Pop Network to Asset Hub as an intermediate location.
         /// 2. Transfers from Asset Hub to Hydration.
@@ -58,51 +113,52 @@ mod hydration_swapping {
         ///
         /// - `amount_out`: The minimum amount of USDT to receive.
         /// - `max_amount_in`: The maximum amount of PASEO to spend.
-        /// - `fee_amount`: The fee amount to pay.
+        /// - `fee_amount`: The fee amount to pay in PASEO.
         /// - `dest`: The destination location.
+        ///
+        /// Amounts carry their decimals and are converted to planck of their asset, 6 decimals for
+        /// USDT and 10 for PAS, failing with `InexactAmount` if they are more precise than the
+        /// asset. The transferred value is in PAS planck.
+        ///
+        /// Requires the `SWAP` role.
         #[ink(message, payable)]
         pub fn swap_usdt_on_hydra(
             &mut self,
-            amount_out: u128,
-            max_amount_in: u128,
-            fee_amount: u128,
+            amount_out: Amount,
+            max_amount_in: Amount,
+            fee_amount: Amount,
             dest: DepositedLocation,
         ) -> Result<()> {
-            let fee = native_asset(fee_amount);
-            let give = native_asset(max_amount_in);
-            // USDT on Asset Hub.
-            // - 1000: Parachain ID of Asset Hub.
-            // - 50: Pallet Instance ID of Asset Hub.
-            // - 1984: General Index of USDT on Asset Hub.
-            let want = Asset {
-                id: AssetId(Location {
-                    parents: 1,
-                    interior: Junctions::from((
-                        Parachain(1000),
-                        PalletInstance(50),
-                        GeneralIndex(1984),
-                    ))
-                    .into(),
-                }),
-                fun: amount_out.into(),
-            };
+            let fee = native_asset(fee_amount.planck_in(PAS_DECIMALS)?);
+            let give = native_asset(max_amount_in.planck_in(PAS_DECIMALS)?);
+            let want = usdt(amount_out.planck_in(USDT_DECIMALS)?);
             self.transfer_and_swap_on_hydra(POP, ASSET_HUB, give, want, false, fee, dest)
         }
 
         /// Transfer to and swap on Hydration.
         ///
         /// Transfer `give_asset` to Hydration via `intermediary_hop`, swap from `give_asset` to `want_asset` and then transfer to `dest`.
-        /// Destination location `dest` can be a local account on Hydration or an account on another parachain.
+        /// Destination location `dest` can be a local account on Hydration or an account on Asset Hub.
+        ///
+        /// The transferred value pays for `give_asset` and for `fee` on both `intermediary_hop` and
+        /// Hydration. Only what is needed is withdrawn, the remainder is refunded to the caller.
         ///
         /// ## Arguments
         ///
         /// - `from_para`: The parachain ID of the sender.
         /// - `intermediary_hop`: The parachain ID of the intermediary hop.
-        /// - `give_asset`: The asset to be given.
+        /// - `give_asset`: The native asset to be given.
         /// - `want_asset`: The asset to be wanted.
         /// - `is_sell`: Whether the transaction is a sell.
-        /// - `fee`: The fee to be paid.
+        /// - `fee`: The native fee to be paid on each hop.
         /// - `dest`: The destination location.
+        ///
+        /// Fails before anything is executed if `give_asset` is zero, `give_asset` or `fee` are
+        /// not the native asset, `fee` is not smaller than `give_asset`, the transferred value
+        /// doesn't cover `give_asset` and the fees, or `want_asset` is below the existential
+        /// deposit on the destination.
+        ///
+        /// Requires the `SWAP` role.
         #[ink(message, payable)]
         pub fn transfer_and_swap_on_hydra(
             &mut self,
@@ -114,63 +170,41 @@ mod hydration_swapping {
             fee: Asset,
             dest: DepositedLocation,
         ) -> Result<()> {
-            let amount_out = self.env().transferred_value();
-
-            // Swap tokens on `HYDRATION` and then reserve transfer to `intermediary_hop`.
-            let swap_on_hydration = XcmMessageBuilder::default()
-                .set_max_weight_limit()
-                .exchange_asset(give_asset, want_asset.clone(), is_sell, fee);
-
-            let deposit_xcm = match dest {
-                DepositedLocation::ParachainAccount(para_id, beneficiary) => {
-                    // Deposit the destination account on the local `to_para`.
-                    let origin_context = get_global_context(HYDRATION);
-                    let destination_fee = want_asset
-                        .clone()
-                        .reanchored(&para(para_id), &origin_context)
-                        .expect("should reanchor");
-                    XcmMessageBuilder::default()
-                        .set_next_hop(HYDRATION)
-                        .send_to(ASSET_HUB)
-                        .set_max_weight_limit()
-                        .deposit_to_account(beneficiary, false)
-                        .reserve_transfer(
-                            All.into(),
-                            fee_amount(&destination_fee, 2).into(),
-                            Xcm::default(),
-                        )
-                }
-                DepositedLocation::Account(beneficiary) => Xcm([DepositAsset {
-                    assets: All.into(),
-                    beneficiary: local_account(beneficiary),
-                }]
-                .to_vec()),
-                _ => panic!("Unsupported deposited location"),
+            self.access.ensure_role(SWAP, self.env().caller())?;
+            let budget = SwapBudget::new(
+                self.env().transferred_value(),
+                &give_asset,
+                &fee,
+                NATIVE_FEE_HOPS,
+            )?;
+            let dest_chain = match dest {
+                DepositedLocation::Account(_) => HYDRATION,
+                DepositedLocation::ParachainAccount(ASSET_HUB, _) => ASSET_HUB,
+                _ => return Err(Error::UnsupportedLocation),
             };
+            ensure_existential_deposit(&want_asset, dest_chain)?;
 
-            // Transfer from `from_para` to `intermediary_hop` and deposit to `HYDRATION`.
-            let message = XcmMessageBuilder::default()
-                .set_next_hop(from_para)
-                .set_max_weight_limit()
-                .send_to(intermediary_hop)
-                .deposit_to_parachain(HYDRATION)
-                .reserve_transfer(
-                    native_asset(amount_out).into(),
-                    fee_amount(&native_asset(amount_out), 2),
-                    Xcm([swap_on_hydration.0, deposit_xcm.0].concat()),
-                );
-
-            api::xcm::execute(&VersionedXcm::V4(Xcm([
-                [WithdrawAsset(native_asset(amount_out).into())].to_vec(),
-                message.0,
-            ]
-            .concat()
-            .to_vec())))
-            .unwrap();
+            let message = transfer_and_swap_xcm(
+                from_para,
+                intermediary_hop,
+                give_asset,
+                want_asset,
+                is_sell,
+                fee,
+                budget.total(),
+                dest,
+            )?;
+            api::xcm::execute(&VersionedXcm::V4(message))?;
+
+            if budget.refund > 0 {
+                self.env()
+                    .transfer(self.env().caller(), budget.refund)
+                    .map_err(|_| Error::TransferFailed)?;
+            }
             Ok(())
         }
 
-        /// Fund a parachain directly. Only support reserve transferring.
+        /// Fund a parachain directly. Only support reserve transferring. Requires the `FUND` role.
         ///
         /// ## Arguments
         ///
@@ -186,24 +220,10 @@ mod hydration_swapping {
             to_para: u32,
             hashed: bool,
         ) -> Result<()> {
+            self.access.ensure_role(FUND, self.env().caller())?;
             let amount = self.env().transferred_value();
-            let message = XcmMessageBuilder::default()
-                .set_next_hop(from_para)
-                .send_to(to_para)
-                .set_max_weight_limit()
-                .deposit_to_account(account, hashed)
-                .reserve_transfer(
-                    native_asset(amount).into(),
-                    fee_amount(&native_asset(amount), 2),
-                    Xcm::default(),
-                );
-            api::xcm::execute(&VersionedXcm::V4(Xcm([
-                [WithdrawAsset(native_asset(amount).into())].to_vec(),
-                message.0,
-            ]
-            .concat()
-            .to_vec())))
-            .unwrap();
+            let message = fund_direct_xcm(account, from_para, to_para, hashed, amount);
+            api::xcm::execute(&VersionedXcm::V4(message))?;
             self.env().emit_event(ReserveTransferred {
                 account,
                 amount,
@@ -215,7 +235,7 @@ mod hydration_swapping {
 
         /// Fund a parachain indirectly with a native asset.
         ///
-        /// Only support reserve transferring.
+        /// Only support reserve transferring. Requires the `FUND` role.
         /// This method transfers the funds to the intermediary parachain and then to the target parachain.
         #[ink(message, payable)]
         pub fn fund_indirect(
@@ -226,30 +246,17 @@ mod hydration_swapping {
             to_para: u32,
             hashed: bool,
         ) -> Result<()> {
+            self.access.ensure_role(FUND, self.env().caller())?;
             let amount = self.env().transferred_value();
-            let local_intermerdiary_fee = fee_amount(&native_asset(amount), 2);
-            let fund_intermediary_xcm = XcmMessageBuilder::default()
-                .set_next_hop(to_para)
-                .set_max_weight_limit()
-                .deposit_to_account(account, hashed)
-                .deposit_asset(local_intermerdiary_fee);
-            let message = XcmMessageBuilder::default()
-                .set_next_hop(from_para)
-                .send_to(intermediary_hop)
-                .set_max_weight_limit()
-                .deposit_to_parachain(to_para)
-                .reserve_transfer(
-                    native_asset(amount).into(),
-                    fee_amount(&native_asset(amount), 2),
-                    fund_intermediary_xcm,
-                );
-            api::xcm::execute(&VersionedXcm::V4(Xcm([
-                [WithdrawAsset(native_asset(amount).into())].to_vec(),
-                message.0,
-            ]
-            .concat()
-            .to_vec())))
-            .unwrap();
+            let message = fund_indirect_xcm(
+                account,
+                from_para,
+                intermediary_hop,
+                to_para,
+                hashed,
+                amount,
+            );
+            api::xcm::execute(&VersionedXcm::V4(message))?;
             self.env().emit_event(ReserveTransferred {
                 account,
                 amount,
@@ -287,16 +294,60 @@ mod hydration_swapping {
             self.fund_direct(account, POP, ASSET_HUB, hashed)
         }
 
+        /// Withdraw a native asset from the account of the contract on another parachain back to
+        /// Pop, e.g. what is left of funding it. Only callable by the owner.
+        ///
+        /// The response to the message emitted by `Withdrawn` is the `ExecutionResult` of the
+        /// program on `from_para`, `None` once it completed, and is returned by `get`.
+        ///
+        /// ## Arguments
+        ///
+        /// - `from_para`: The parachain ID to withdraw from.
+        /// - `amount`: The amount to withdraw, including the fees of every hop.
+        /// - `beneficiary`: The account on Pop to deposit to.
+        #[ink(message)]
+        pub fn withdraw(
+            &mut self,
+            from_para: u32,
+            amount: u128,
+            beneficiary: AccountId,
+        ) -> Result<()> {
+            self.access.ensure_owner(self.env().caller())?;
+            let dest = para(from_para);
+            let timeout = self.env().block_number().saturating_add(100);
+            let (id, query_id) = self
+                .ids
+                .send(|id| api::xcm::new_query(id, dest.clone(), timeout, None))?;
+            let query_id = query_id.ok_or(Error::NoQuery)?;
+            let response = QueryResponseInfo {
+                destination: para(POP),
+                query_id,
+                max_weight: Weight::from_parts(1_000_000, 5_000),
+            };
+            let message = repatriate_xcm(from_para, amount, beneficiary, response);
+            api::xcm::send(&dest.into_versioned(), &VersionedXcm::V4(message))?;
+            self.env().emit_event(Withdrawn {
+                id,
+                account: beneficiary,
+                amount,
+                from: from_para,
+            });
+            Ok(())
+        }
+
         #[ink(message)]
         pub fn get(&self, id: MessageId) -> Result<Option<Vec<u8>>> {
             debug_println!("messaging::get id={id}");
-            api::get((self.env().account_id(), id))
+            Ok(api::get((self.env().account_id(), id))?)
         }
 
+        /// Removes the response to message `id`, so its id is used again. Only callable by the
+        /// owner.
         #[ink(message)]
         pub fn remove(&mut self, id: MessageId) -> Result<()> {
             debug_println!("messaging::remove id={id}");
-            api::remove([id].to_vec())?;
+            self.access.ensure_owner(self.env().caller())?;
+            self.ids.reclaim(self.env().account_id(), [id].to_vec())?;
             Ok(())
         }
     }
@@ -311,13 +362,237 @@ mod hydration_swapping {
         pub to: u32,
     }
 
+    #[ink::event]
+    pub struct Withdrawn {
+        /// The id of the message awaiting the response of `from`.
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+        pub from: u32,
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
+        use access_control::AccessControlled;
+        use ink::{
+            env::{test, DefaultEnvironment},
+            scale::Encode,
+            xcm::prelude::Response,
+        };
+        use pop_api_mock::messaging::{Delivery, MockHost};
 
         #[ink::test]
         fn default_works() {
             CrosschainSwap::new();
         }
+
+        #[ink::test]
+        fn transfer_and_swap_rejects_insufficient_value() {
+            let mut contract = CrosschainSwap::new();
+            // Giving 500 and paying 100 on both hops requires 700.
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(699);
+            assert_eq!(
+                contract.transfer_and_swap_on_hydra(
+                    POP,
+                    ASSET_HUB,
+                    native_asset(500),
+                    native_asset(1_000_000_000),
+                    false,
+                    native_asset(100),
+                    DepositedLocation::Account(AccountId::from([1; 32])),
+                ),
+                Err(Error::InsufficientValue)
+            );
+        }
+
+        #[ink::test]
+        fn swap_usdt_on_hydra_converts_amounts() {
+            let host = MockHost::register();
+            let mut contract = CrosschainSwap::new();
+            let dest = || DepositedLocation::Account(AccountId::from([1; 32]));
+            // Amounts with 18 decimals: 2 USDT for at most 1 PAS, paying 0.1 PAS on each hop.
+            let amount_out = Amount::from_units(2, 18).unwrap();
+            let max_amount_in = Amount::from_units(1, 18).unwrap();
+            let fee_amount = Amount::from_decimal(0, 1, 1, 18).unwrap();
+            test::set_value_transferred::<DefaultEnvironment>(12_000_000_000);
+            assert_eq!(
+                contract.swap_usdt_on_hydra(amount_out, max_amount_in, fee_amount, dest()),
+                Ok(())
+            );
+
+            let message = transfer_and_swap_xcm(
+                POP,
+                ASSET_HUB,
+                native_asset(10_000_000_000),
+                usdt(2_000_000),
+                false,
+                native_asset(1_000_000_000),
+                12_000_000_000,
+                dest(),
+            )
+            .unwrap();
+            assert_eq!(
+                host.executed(),
+                [(
+                    test::callee::<DefaultEnvironment>(),
+                    VersionedXcm::V4(message)
+                )]
+            );
+
+            // Amounts more precise than their asset are rejected.
+            assert_eq!(
+                contract.swap_usdt_on_hydra(
+                    Amount::from_planck(1, 18),
+                    max_amount_in,
+                    fee_amount,
+                    dest()
+                ),
+                Err(Error::InexactAmount)
+            );
+            assert_eq!(host.executed().len(), 1);
+        }
+
+        #[ink::test]
+        fn messages_require_roles() {
+            let host = MockHost::register();
+            let mut contract = CrosschainSwap::new();
+            let accounts = test::default_accounts::<DefaultEnvironment>();
+            let account = AccountId::from([1; 32]);
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            let swap = |contract: &mut CrosschainSwap| {
+                contract.swap_usdt_on_hydra(
+                    Amount::from_planck(1_000, USDT_DECIMALS),
+                    Amount::from_planck(500, PAS_DECIMALS),
+                    Amount::from_planck(100, PAS_DECIMALS),
+                    DepositedLocation::Account(account),
+                )
+            };
+            for result in [
+                swap(&mut contract),
+                contract.fund_direct(account, POP, ASSET_HUB, false),
+                contract.fund_indirect(account, POP, ASSET_HUB, HYDRATION, false),
+                contract.fund_hydration(account, false),
+                contract.fund_asset_hub(account, false),
+                contract.remove(0),
+                contract.withdraw(HYDRATION, 1_000, account),
+            ] {
+                assert_eq!(result, Err(Error::Unauthorized));
+            }
+            assert!(host.executed().is_empty());
+            assert!(host.sent().is_empty());
+
+            // Roles only permit their messages.
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.grant_role(FUND, accounts.bob), Ok(()));
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.fund_asset_hub(account, false), Ok(()));
+            assert_eq!(swap(&mut contract), Err(Error::Unauthorized));
+            assert_eq!(host.executed().len(), 1);
+        }
+
+        #[ink::test]
+        fn fund_direct_works() {
+            let host = MockHost::register();
+            let mut contract = CrosschainSwap::new();
+            let account = AccountId::from([1; 32]);
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            assert_eq!(contract.fund_direct(account, POP, ASSET_HUB, false), Ok(()));
+
+            let message = fund_direct_xcm(account, POP, ASSET_HUB, false, 1_000);
+            assert_eq!(
+                host.executed(),
+                [(
+                    test::callee::<DefaultEnvironment>(),
+                    VersionedXcm::V4(message)
+                )]
+            );
+            assert_eq!(test::recorded_events().count(), 1);
+        }
+
+        #[ink::test]
+        fn withdraw_works() {
+            let host = MockHost::register();
+            let mut contract = CrosschainSwap::new();
+            let account = AccountId::from([1; 32]);
+            assert_eq!(contract.withdraw(HYDRATION, 1_000, account), Ok(()));
+
+            let [request] = &host.requests()[..] else {
+                panic!("expected a single query")
+            };
+            let response = QueryResponseInfo {
+                destination: para(POP),
+                query_id: request.query_id.unwrap(),
+                max_weight: Weight::from_parts(1_000_000, 5_000),
+            };
+            let message = repatriate_xcm(HYDRATION, 1_000, account, response);
+            assert_eq!(host.sent()[0].2, VersionedXcm::V4(message));
+
+            // The response is stored until removed by the owner.
+            let response = Response::ExecutionResult(None);
+            assert_eq!(
+                host.respond_xcm_with(
+                    &request.commitment,
+                    response.clone(),
+                    |_, _| Ok::<_, ()>(())
+                ),
+                Ok(Delivery::Stored)
+            );
+            assert_eq!(contract.get(0), Ok(Some(response.encode())));
+
+            // Removing the response reclaims its id.
+            assert_eq!(contract.remove(0), Ok(()));
+            assert_eq!(contract.get(0), Ok(None));
+            assert_eq!(contract.withdraw(HYDRATION, 1_000, account), Ok(()));
+            assert_eq!(host.requests()[1].id, 0);
+        }
+
+        #[ink::test]
+        fn transfer_and_swap_refunds_remainder() {
+            let host = MockHost::register();
+            let mut contract = CrosschainSwap::new();
+            let contract_account = test::callee::<DefaultEnvironment>();
+            let caller = test::default_accounts::<DefaultEnvironment>().alice;
+            test::set_account_balance::<DefaultEnvironment>(contract_account, 10_000);
+            let balance = test::get_account_balance::<DefaultEnvironment>(caller).unwrap();
+            let dest = || DepositedLocation::Account(AccountId::from([1; 32]));
+            // Giving 500 and paying 100 on both hops leaves 300 to refund.
+            test::set_value_transferred::<DefaultEnvironment>(1_000);
+            assert_eq!(
+                contract.transfer_and_swap_on_hydra(
+                    POP,
+                    ASSET_HUB,
+                    native_asset(500),
+                    native_asset(1_000_000_000),
+                    false,
+                    native_asset(100),
+                    dest(),
+                ),
+                Ok(())
+            );
+
+            let message = transfer_and_swap_xcm(
+                POP,
+                ASSET_HUB,
+                native_asset(500),
+                native_asset(1_000_000_000),
+                false,
+                native_asset(100),
+                700,
+                dest(),
+            )
+            .unwrap();
+            assert_eq!(
+                host.executed(),
+                [(contract_account, VersionedXcm::V4(message))]
+            );
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(caller).unwrap(),
+                balance + 300
+            );
+        }
     }
 }
@@ -1,14 +1,13 @@
-use ink::{
-    env::hash::{Blake2x256, CryptoHash},
-    primitives::AccountId,
-    scale::{Compact, Encode},
-    xcm::prelude::*,
-};
+use ink::{primitives::AccountId, xcm::prelude::*};
 use pop_api::messaging::xcm::Location;
 
-pub(crate) const ASSET_HUB: u32 = 1000;
-pub(crate) const HYDRATION: u32 = 2034;
-pub(crate) const POP: u32 = 4001;
+use crate::{Error, Result};
+
+pub use repatriation::{hashed_account, repatriate_xcm};
+
+pub const ASSET_HUB: u32 = 1000;
+pub const HYDRATION: u32 = 2034;
+pub const POP: u32 = 4001;
 
 pub fn get_global_context(para: u32) -> Junctions {
     Junctions::from([
@@ -155,19 +154,116 @@ impl XcmMessageBuilder {
     }
 }
 
-/// Returns the hashed account ID for a given parachain ID and account ID.
+/// Returns the program reserve transferring `amount` of the native asset from `from_para` to
+/// `account` on `to_para`.
+pub fn fund_direct_xcm(
+    account: AccountId,
+    from_para: u32,
+    to_para: u32,
+    hashed: bool,
+    amount: u128,
+) -> Xcm<()> {
+    let message = XcmMessageBuilder::default()
+        .set_next_hop(from_para)
+        .send_to(to_para)
+        .set_max_weight_limit()
+        .deposit_to_account(account, hashed)
+        .reserve_transfer(
+            native_asset(amount).into(),
+            fee_amount(&native_asset(amount), 2),
+            Xcm::default(),
+        );
+    withdraw_then(native_asset(amount), message)
+}
+
+/// Returns the program reserve transferring `amount` of the native asset from `from_para` to
+/// `account` on `to_para` via `intermediary_hop`.
+pub fn fund_indirect_xcm(
+    account: AccountId,
+    from_para: u32,
+    intermediary_hop: u32,
+    to_para: u32,
+    hashed: bool,
+    amount: u128,
+) -> Xcm<()> {
+    let local_intermerdiary_fee = fee_amount(&native_asset(amount), 2);
+    let fund_intermediary_xcm = XcmMessageBuilder::default()
+        .set_next_hop(to_para)
+        .set_max_weight_limit()
+        .deposit_to_account(account, hashed)
+        .deposit_asset(local_intermerdiary_fee);
+    let message = XcmMessageBuilder::default()
+        .set_next_hop(from_para)
+        .send_to(intermediary_hop)
+        .set_max_weight_limit()
+        .deposit_to_parachain(to_para)
+        .reserve_transfer(
+            native_asset(amount).into(),
+            fee_amount(&native_asset(amount), 2),
+            fund_intermediary_xcm,
+        );
+    withdraw_then(native_asset(amount), message)
+}
+
+/// Returns the program transferring `amount` of the native asset to Hydration via
+/// `intermediary_hop`, swapping `give` for `want` and depositing the result to `dest`.
 ///
-/// The original `account_id` has a control over its `hashed_account` across the parachains.
-pub(crate) fn hashed_account(para_id: u32, account_id: AccountId) -> AccountId {
-    let location = (
-        b"SiblingChain",
-        Compact::<u32>::from(para_id),
-        (b"AccountId32", account_id.0).encode(),
-    )
-        .encode();
-    let mut output = [0u8; 32];
-    Blake2x256::hash(&location, &mut output);
-    AccountId::from(output)
+/// `dest` must be an account on Hydration or on Asset Hub, the reserve of the swapped asset.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_and_swap_xcm(
+    from_para: u32,
+    intermediary_hop: u32,
+    give: Asset,
+    want: Asset,
+    is_sell: bool,
+    fee: Asset,
+    amount: u128,
+    dest: DepositedLocation,
+) -> Result<Xcm<()>> {
+    // Swap tokens on `HYDRATION` and then reserve transfer to `intermediary_hop`.
+    let swap_on_hydration = XcmMessageBuilder::default()
+        .set_max_weight_limit()
+        .exchange_asset(give, want.clone(), is_sell, fee.clone());
+
+    let deposit_xcm = match dest {
+        DepositedLocation::ParachainAccount(ASSET_HUB, beneficiary) => {
+            // Deposit the destination account on the local `to_para`.
+            let origin_context = get_global_context(HYDRATION);
+            let destination_fee = want
+                .reanchored(&para(ASSET_HUB), &origin_context)
+                .expect("should reanchor");
+            XcmMessageBuilder::default()
+                .set_next_hop(HYDRATION)
+                .send_to(ASSET_HUB)
+                .set_max_weight_limit()
+                .deposit_to_account(beneficiary, false)
+                .reserve_transfer(All.into(), fee_amount(&destination_fee, 2), Xcm::default())
+        }
+        DepositedLocation::Account(beneficiary) => Xcm([DepositAsset {
+            assets: All.into(),
+            beneficiary: local_account(beneficiary),
+        }]
+        .to_vec()),
+        _ => return Err(Error::UnsupportedLocation),
+    };
+
+    // Transfer from `from_para` to `intermediary_hop` and deposit to `HYDRATION`.
+    let message = XcmMessageBuilder::default()
+        .set_next_hop(from_para)
+        .set_max_weight_limit()
+        .send_to(intermediary_hop)
+        .deposit_to_parachain(HYDRATION)
+        .reserve_transfer(
+            native_asset(amount).into(),
+            fee,
+            Xcm([swap_on_hydration.0, deposit_xcm.0].concat()),
+        );
+    Ok(withdraw_then(native_asset(amount), message))
+}
+
+/// Prepends withdrawing `asset` from the local account to `xcm`.
+fn withdraw_then(asset: Asset, xcm: Xcm<()>) -> Xcm<()> {
+    Xcm([[WithdrawAsset(asset.into())].to_vec(), xcm.0].concat())
 }
 
 /// Returns amount if `asset` is fungible, or zero.
@@ -196,10 +292,22 @@ pub(crate) fn para(id: u32) -> Location {
 }
 
 /// Returns the location of the native asset.
-pub(crate) fn native_asset(amount: u128) -> Asset {
+pub fn native_asset(amount: u128) -> Asset {
     (Location::parent(), amount).into()
 }
 
+/// Returns `amount` of USDT on Asset Hub, relative to a sibling parachain.
+pub fn usdt(amount: u128) -> Asset {
+    (
+        Location::new(
+            1,
+            [Parachain(ASSET_HUB), PalletInstance(50), GeneralIndex(1984)],
+        ),
+        amount,
+    )
+        .into()
+}
+
 /// Returns the location of the local account.
 pub(crate) fn local_account(account: AccountId) -> Location {
     Location::new(
@@ -210,3 +318,251 @@ pub(crate) fn local_account(account: AccountId) -> Location {
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Clone, Debug)]
+    enum Route {
+        Direct {
+            from: u32,
+            to: u32,
+            hashed: bool,
+        },
+        Indirect {
+            from: u32,
+            via: u32,
+            to: u32,
+        },
+        Swap {
+            from: u32,
+            via: u32,
+            to_asset_hub: bool,
+        },
+    }
+
+    fn route() -> impl Strategy<Value = Route> {
+        prop_oneof![
+            (any::<u32>(), any::<u32>(), any::<bool>())
+                .prop_map(|(from, to, hashed)| Route::Direct { from, to, hashed }),
+            (any::<u32>(), any::<u32>(), any::<u32>())
+                .prop_map(|(from, via, to)| Route::Indirect { from, via, to }),
+            (any::<u32>(), any::<u32>(), any::<bool>()).prop_map(|(from, via, to_asset_hub)| {
+                Route::Swap {
+                    from,
+                    via,
+                    to_asset_hub,
+                }
+            }),
+        ]
+    }
+
+    /// Returns the program for `route`, carrying `amount` of both the native asset and USDT.
+    fn program(route: &Route, account: AccountId, amount: u128) -> Xcm<()> {
+        match *route {
+            Route::Direct { from, to, hashed } => {
+                fund_direct_xcm(account, from, to, hashed, amount)
+            }
+            Route::Indirect { from, via, to } => {
+                fund_indirect_xcm(account, from, via, to, false, amount)
+            }
+            Route::Swap {
+                from,
+                via,
+                to_asset_hub,
+            } => {
+                let dest = if to_asset_hub {
+                    DepositedLocation::ParachainAccount(ASSET_HUB, account)
+                } else {
+                    DepositedLocation::Account(account)
+                };
+                transfer_and_swap_xcm(
+                    from,
+                    via,
+                    native_asset(amount / 2),
+                    usdt(amount),
+                    false,
+                    native_asset(amount / 4),
+                    amount,
+                    dest,
+                )
+                .expect("supported destination")
+            }
+        }
+    }
+
+    /// Returns the nested program and the location it is sent to, if any.
+    fn next_leg(xcm: &Xcm<()>) -> Option<(&Location, &Xcm<()>)> {
+        xcm.0.iter().find_map(|instruction| match instruction {
+            InitiateReserveWithdraw { reserve, xcm, .. } => Some((reserve, xcm)),
+            DepositReserveAsset { dest, xcm, .. } => Some((dest, xcm)),
+            _ => None,
+        })
+    }
+
+    /// Returns the programs executed on every hop after the local one, with their location.
+    fn legs(xcm: &Xcm<()>) -> Vec<(&Location, &Xcm<()>)> {
+        let mut legs = Vec::new();
+        let mut current = xcm;
+        while let Some((location, leg)) = next_leg(current) {
+            legs.push((location, leg));
+            current = leg;
+        }
+        legs
+    }
+
+    proptest! {
+        #[test]
+        fn every_leg_starts_with_buy_execution(
+            route in route(),
+            account in any::<[u8; 32]>(),
+            amount in 1..=u128::MAX,
+        ) {
+            let xcm = program(&route, AccountId::from(account), amount);
+            prop_assert!(matches!(xcm.0.first(), Some(WithdrawAsset(_))));
+            let legs = legs(&xcm);
+            prop_assert!(!legs.is_empty());
+            for (_, leg) in legs {
+                prop_assert!(matches!(leg.0.first(), Some(BuyExecution { .. })));
+            }
+        }
+
+        #[test]
+        fn fees_never_exceed_carried_amount(
+            route in route(),
+            account in any::<[u8; 32]>(),
+            amount in 1..=u128::MAX,
+        ) {
+            let xcm = program(&route, AccountId::from(account), amount);
+            let usdt_on_asset_hub = Location::new(0, [PalletInstance(50), GeneralIndex(1984)]);
+            for (_, leg) in legs(&xcm) {
+                for instruction in &leg.0 {
+                    if let BuyExecution { fees, .. } = instruction {
+                        prop_assert!(fungible_amount(fees) <= amount);
+                        // Fees are reanchored to the hop buying execution.
+                        prop_assert!(fees.id == native_asset(0).id || fees.id.0 == usdt_on_asset_hub);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn deposits_target_requested_location(
+            route in route(),
+            account in any::<[u8; 32]>(),
+            amount in 1..=u128::MAX,
+        ) {
+            let account = AccountId::from(account);
+            let xcm = program(&route, account, amount);
+            let legs = legs(&xcm);
+            let (expected_route, expected_account) = match route {
+                Route::Direct { from, to, hashed } => {
+                    let account = if hashed { hashed_account(from, account) } else { account };
+                    ([para(to)].to_vec(), account)
+                },
+                Route::Indirect { via, to, .. } => ([para(via), para(to)].to_vec(), account),
+                Route::Swap { via, to_asset_hub: false, .. } =>
+                    ([para(via), para(HYDRATION)].to_vec(), account),
+                Route::Swap { via, to_asset_hub: true, .. } =>
+                    ([para(via), para(HYDRATION), para(ASSET_HUB)].to_vec(), account),
+            };
+            let route: Vec<Location> = legs.iter().map(|(location, _)| (*location).clone()).collect();
+            prop_assert_eq!(route, expected_route);
+            prop_assert_eq!(
+                legs.last().and_then(|(_, leg)| leg.0.last()),
+                Some(&DepositAsset { assets: All.into(), beneficiary: local_account(expected_account) })
+            );
+        }
+
+        #[test]
+        fn withdrawals_return_to_pop(
+            from in prop_oneof![Just(ASSET_HUB), any::<u32>()],
+            account in any::<[u8; 32]>(),
+            amount in 1..=u128::MAX,
+        ) {
+            let account = AccountId::from(account);
+            let response = QueryResponseInfo {
+                destination: para(POP),
+                query_id: 0,
+                max_weight: Weight::zero(),
+            };
+            let xcm = repatriate_xcm(from, amount, account, response);
+            prop_assert!(matches!(&xcm.0[..2], [WithdrawAsset(_), BuyExecution { .. }]));
+            let legs = legs(&xcm);
+            for (_, leg) in &legs {
+                prop_assert!(matches!(leg.0.first(), Some(BuyExecution { .. })));
+            }
+            let expected_route = if from == ASSET_HUB {
+                [para(POP)].to_vec()
+            } else {
+                [para(ASSET_HUB), para(POP)].to_vec()
+            };
+            let route: Vec<Location> = legs.iter().map(|(location, _)| (*location).clone()).collect();
+            prop_assert_eq!(route, expected_route);
+            prop_assert_eq!(
+                legs.last().and_then(|(_, leg)| leg.0.last()),
+                Some(&DepositAsset { assets: All.into(), beneficiary: local_account(account) })
+            );
+        }
+
+        #[test]
+        fn reanchored_destination_fee_never_exceeds_want(amount in any::<u128>()) {
+            let destination_fee = usdt(amount)
+                .reanchored(&para(ASSET_HUB), &get_global_context(HYDRATION))
+                .expect("should reanchor");
+            prop_assert!(fungible_amount(&fee_amount(&destination_fee, 2)) <= amount);
+            prop_assert_eq!(
+                destination_fee.id.0,
+                Location::new(0, [PalletInstance(50), GeneralIndex(1984)])
+            );
+        }
+
+        #[test]
+        fn exchange_asset_buys_execution_first(
+            give in any::<u128>(),
+            want in any::<u128>(),
+            fee in any::<u128>(),
+            is_sell in any::<bool>(),
+        ) {
+            let xcm = XcmMessageBuilder::default().exchange_asset(
+                native_asset(give),
+                usdt(want),
+                is_sell,
+                native_asset(fee),
+            );
+            prop_assert!(matches!(
+                xcm.0.first(),
+                Some(BuyExecution { fees, .. }) if *fees == native_asset(fee)
+            ));
+            prop_assert!(matches!(
+                xcm.0.get(1),
+                Some(ExchangeAsset { maximal, .. }) if *maximal == is_sell
+            ));
+        }
+    }
+
+    #[test]
+    fn unsupported_swap_destinations_are_rejected() {
+        let account = AccountId::from([1; 32]);
+        for dest in [
+            DepositedLocation::Parachain(ASSET_HUB),
+            DepositedLocation::ParachainAccount(POP, account),
+        ] {
+            assert_eq!(
+                transfer_and_swap_xcm(
+                    POP,
+                    ASSET_HUB,
+                    native_asset(2),
+                    usdt(1),
+                    false,
+                    native_asset(1),
+                    4,
+                    dest,
+                ),
+                Err(Error::UnsupportedLocation)
+            );
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! The heights of other chains which ISMP reads are made at. Pop verifies the proof of a read
+//! against its commitment of the state of the chain at the height, so a read at a height Pop has
+//! no commitment for is never answered.
+//!
+//! Contracts can not read Pop's state machine commitments, pop-api has no query for them. The
+//! latest height of a chain with a commitment is reported to the contract instead, e.g. by its
+//! owner as seen in Pop's `StateMachineUpdated` events, and requested heights are checked against
+//! it with [`Heights::resolve`]. A height reported ahead of Pop's commitments is not detected.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::storage::Mapping;
+
+/// The para id of a chain.
+pub type ParaId = u32;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// No height of the chain was reported yet.
+    UnknownHeight,
+    /// The height is ahead of the latest height reported, so the read would never be answered.
+    HeightAhead,
+}
+
+/// The height of a chain at which storage is read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Height {
+    /// The latest height reported to the contract.
+    Latest,
+    /// A specific height, which must not be ahead of the latest height reported to the contract.
+    At(u32),
+}
+
+/// Resolves `height` against the `latest` height reported.
+pub fn resolve(latest: Option<u32>, height: Height) -> Result<u32> {
+    let latest = latest.ok_or(Error::UnknownHeight)?;
+    match height {
+        Height::Latest => Ok(latest),
+        Height::At(height) if height > latest => Err(Error::HeightAhead),
+        Height::At(height) => Ok(height),
+    }
+}
+
+/// The latest heights reported, by chain.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct Heights {
+    latest: Mapping<ParaId, u32>,
+}
+
+impl Heights {
+    /// Returns the latest height of `para` reported.
+    pub fn latest(&self, para: ParaId) -> Option<u32> {
+        self.latest.get(para)
+    }
+
+    /// Reports `height` of `para` to have a state commitment on Pop. Lower heights than the
+    /// latest are ignored. Returns the latest height.
+    pub fn update(&mut self, para: ParaId, height: u32) -> u32 {
+        let latest = self
+            .latest(para)
+            .map_or(height, |latest| latest.max(height));
+        self.latest.insert(para, &latest);
+        latest
+    }
+
+    /// Resolves `height` of `para` against the latest height reported.
+    pub fn resolve(&self, para: ParaId, height: Height) -> Result<u32> {
+        resolve(self.latest(para), height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ink::test]
+    fn resolve_works() {
+        let mut heights = Heights::default();
+        assert_eq!(
+            heights.resolve(1, Height::Latest),
+            Err(Error::UnknownHeight)
+        );
+        assert_eq!(heights.resolve(1, Height::At(1)), Err(Error::UnknownHeight));
+
+        assert_eq!(heights.update(1, 10), 10);
+        // Lower heights are ignored.
+        assert_eq!(heights.update(1, 9), 10);
+        assert_eq!(heights.resolve(1, Height::Latest), Ok(10));
+        assert_eq!(heights.resolve(1, Height::At(10)), Ok(10));
+        assert_eq!(heights.resolve(1, Height::At(11)), Err(Error::HeightAhead));
+        // Heights are reported by chain.
+        assert_eq!(
+            heights.resolve(2, Height::Latest),
+            Err(Error::UnknownHeight)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Allocation of the ids of messages sent through the messaging API, shared by ISMP requests and
+//! XCM queries as both are identified by a `MessageId` per contract.
+//!
+//! Ids are allocated once and only allocated again once reclaimed, rather than incremented with
+//! saturating or wrapping arithmetic, which would reuse ids still in use once they ran out. The
+//! status codes [`EXHAUSTED`] and [`NOT_ALLOCATED`] are reserved by contracts using it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{
+    prelude::vec::Vec,
+    primitives::AccountId,
+    storage::{traits::ManualKey, Lazy},
+};
+use pop_api::{
+    messaging::{self as api, MessageId},
+    StatusCode,
+};
+
+pub type Result<T> = core::result::Result<T, StatusCode>;
+
+/// The status code of the runtime for a message id already in use by the contract, i.e.
+/// `Module { index: 152, error: 0 }` of the messaging pallet.
+pub const MESSAGE_EXISTS: u32 = u32::from_le_bytes([3, 152, 0, 0]);
+/// All message ids are allocated.
+pub const EXHAUSTED: u32 = u32::MAX - 8;
+/// The message id was not allocated, or was already reclaimed.
+pub const NOT_ALLOCATED: u32 = u32::MAX - 9;
+
+/// The number of ids tried before giving up on ids in use by the runtime.
+const MAX_ATTEMPTS: usize = 8;
+
+/// The allocator of message ids. Its fields are stored under fixed keys, so the ids allocated are
+/// kept when the code of the contract is upgraded, regardless of the layout of its storage.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct MessageIds {
+    /// The id allocated next, unless there are reclaimed ids.
+    next: Lazy<MessageId, ManualKey<0x6d736731>>,
+    /// The ids of completed messages removed from the runtime, allocated again first.
+    free: Lazy<Vec<MessageId>, ManualKey<0x6d736732>>,
+}
+
+impl MessageIds {
+    /// Allocates an id, preferring reclaimed ids. Fails with [`EXHAUSTED`] once the ids ran out.
+    pub fn allocate(&mut self) -> Result<MessageId> {
+        let mut free = self.free.get_or_default();
+        if let Some(id) = free.pop() {
+            self.free.set(&free);
+            return Ok(id);
+        }
+        let id = self.next.get_or_default();
+        let next = id.checked_add(1).ok_or(StatusCode::from(EXHAUSTED))?;
+        self.next.set(&next);
+        Ok(id)
+    }
+
+    /// Sends a message with `send`, using a newly allocated id. Ids the runtime reports to be in
+    /// use, e.g. as they were used before the allocator, are skipped. Returns the id used with the
+    /// result of `send`.
+    pub fn send<T>(
+        &mut self,
+        mut send: impl FnMut(MessageId) -> Result<T>,
+    ) -> Result<(MessageId, T)> {
+        let mut error = StatusCode::from(MESSAGE_EXISTS);
+        for _ in 0..MAX_ATTEMPTS {
+            let id = self.allocate()?;
+            match send(id) {
+                Ok(result) => return Ok((id, result)),
+                // The id stays allocated, as it is in use.
+                Err(e) if e.0 == MESSAGE_EXISTS => error = e,
+                Err(e) => {
+                    self.release(id);
+                    return Err(e);
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Reclaims the ids of completed messages of `account`, removing the messages from the
+    /// runtime if they were not removed after their callback already. Fails if any of the
+    /// messages is still pending.
+    pub fn reclaim(&mut self, account: AccountId, ids: Vec<MessageId>) -> Result<()> {
+        let next = self.next.get_or_default();
+        let mut free = self.free.get_or_default();
+        let mut stored = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            if *id >= next || free.contains(id) || ids[..i].contains(id) {
+                return Err(NOT_ALLOCATED.into());
+            }
+            if api::poll((account, *id))?.is_some() {
+                stored.push(*id);
+            }
+        }
+        if !stored.is_empty() {
+            api::remove(stored)?;
+        }
+        free.extend(ids);
+        self.free.set(&free);
+        Ok(())
+    }
+
+    // Releases an id which was not used.
+    fn release(&mut self, id: MessageId) {
+        let mut free = self.free.get_or_default();
+        free.push(id);
+        self.free.set(&free);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[ink::test]
+    fn allocate_works() {
+        let mut ids = MessageIds::default();
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(0));
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(1));
+        ids.release(0);
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(0));
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(2));
+    }
+
+    #[ink::test]
+    fn allocate_fails_on_overflow() {
+        let mut ids = MessageIds::default();
+        ids.next.set(&(MessageId::MAX - 1));
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(MessageId::MAX - 1));
+        assert_eq!(ids.allocate().map_err(|e| e.0), Err(EXHAUSTED));
+        // Reclaimed ids can still be allocated.
+        ids.release(3);
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(3));
+    }
+
+    #[ink::test]
+    fn send_skips_ids_in_use() {
+        let mut ids = MessageIds::default();
+        let sent = ids.send(|id| match id {
+            0 | 1 => Err(MESSAGE_EXISTS.into()),
+            id => Ok(id * 10),
+        });
+        assert_eq!(sent.map_err(|e| e.0), Ok((2, 20)));
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(3));
+
+        // Gives up once all attempts are in use.
+        assert_eq!(
+            ids.send(|_| Err::<(), _>(MESSAGE_EXISTS.into()))
+                .map_err(|e| e.0),
+            Err(MESSAGE_EXISTS)
+        );
+        assert_eq!(
+            ids.allocate().map_err(|e| e.0),
+            Ok(4 + MAX_ATTEMPTS as MessageId)
+        );
+    }
+
+    #[ink::test]
+    fn send_releases_ids_on_failure() {
+        let mut ids = MessageIds::default();
+        assert_eq!(
+            ids.send(|_| Err::<(), _>(u32::MAX.into())).map_err(|e| e.0),
+            Err(u32::MAX)
+        );
+        assert_eq!(ids.allocate().map_err(|e| e.0), Ok(0));
+    }
+}
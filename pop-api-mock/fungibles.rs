@@ -0,0 +1,230 @@
+//! A mock of the fungibles API, keeping the balances and allowances of each token.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use ink::{
+    env::{test, DefaultEnvironment},
+    primitives::AccountId,
+    scale::{Decode, Encode},
+};
+
+use crate::{module_error, Module, DISPATCH, READ_STATE};
+
+/// The module index of the fungibles API.
+pub const FUNGIBLES: u8 = 150;
+/// The index of the assets pallet on Pop, raising the errors of the fungibles API.
+const ASSETS: u8 = 52;
+
+// Dispatchables of the fungibles API.
+const TRANSFER: u8 = 3;
+const TRANSFER_FROM: u8 = 4;
+const APPROVE: u8 = 5;
+const CREATE: u8 = 11;
+const MINT: u8 = 19;
+const BURN: u8 = 20;
+
+// State reads of the fungibles API.
+const TOTAL_SUPPLY: u8 = 0;
+const BALANCE_OF: u8 = 1;
+const ALLOWANCE: u8 = 2;
+const TOKEN_EXISTS: u8 = 18;
+
+/// The balance is too low.
+pub const BALANCE_LOW: u32 = module_error(ASSETS, 0);
+/// The caller is not permitted to manage the token.
+pub const NO_PERMISSION: u32 = module_error(ASSETS, 2);
+/// The token does not exist.
+pub const UNKNOWN: u32 = module_error(ASSETS, 3);
+/// The token id is in use.
+pub const IN_USE: u32 = module_error(ASSETS, 5);
+/// The allowance is too low.
+pub const UNAPPROVED: u32 = module_error(ASSETS, 10);
+
+pub type TokenId = u32;
+pub type Balance = u128;
+
+#[derive(Default)]
+struct State {
+    owners: BTreeMap<TokenId, AccountId>,
+    balances: BTreeMap<(TokenId, AccountId), Balance>,
+    allowances: BTreeMap<(TokenId, AccountId, AccountId), Balance>,
+}
+
+/// A mock of the fungibles API, shared between a test and the chain extension.
+#[derive(Clone, Default)]
+pub struct Fungibles(Rc<RefCell<State>>);
+
+impl Fungibles {
+    /// Creates the mock and registers it as the fungibles chain extension.
+    pub fn register() -> Self {
+        let fungibles = Self::default();
+        fungibles.register_extension();
+        fungibles
+    }
+
+    /// Creates `token` owned by `owner`.
+    pub fn create(&self, token: TokenId, owner: AccountId) {
+        self.0.borrow_mut().owners.insert(token, owner);
+    }
+
+    /// Mints `value` of `token` to `account`, regardless of the owner of the token.
+    pub fn mint(&self, token: TokenId, account: AccountId, value: Balance) {
+        let mut state = self.0.borrow_mut();
+        let balance = state.balances.entry((token, account)).or_default();
+        *balance = balance.saturating_add(value);
+    }
+
+    /// Returns the balance of `account` in `token`.
+    pub fn balance_of(&self, token: TokenId, account: AccountId) -> Balance {
+        self.0.borrow().balance_of(token, account)
+    }
+
+    /// Returns the total supply of `token`.
+    pub fn total_supply(&self, token: TokenId) -> Balance {
+        self.0.borrow().total_supply(token)
+    }
+
+    /// Returns the amount of `token` that `spender` may transfer from `owner`.
+    pub fn allowance(&self, token: TokenId, owner: AccountId, spender: AccountId) -> Balance {
+        self.0.borrow().allowance(token, owner, spender)
+    }
+}
+
+impl Module for Fungibles {
+    const MODULE: u8 = FUNGIBLES;
+    const INDICES: u8 = BURN + 1;
+
+    fn handle(&self, function: u8, index: u8, mut input: &[u8], output: &mut Vec<u8>) -> u32 {
+        let origin = test::callee::<DefaultEnvironment>();
+        let mut state = self.0.borrow_mut();
+        match (function, index) {
+            (DISPATCH, TRANSFER) => {
+                let (token, to, value) =
+                    <(TokenId, AccountId, Balance)>::decode(&mut input).expect("valid transfer");
+                state.transfer(token, origin, to, value)
+            }
+            (DISPATCH, TRANSFER_FROM) => {
+                let (token, from, to, value) =
+                    <(TokenId, AccountId, AccountId, Balance)>::decode(&mut input)
+                        .expect("valid transfer");
+                let allowance = state.allowance(token, from, origin);
+                if allowance < value {
+                    return UNAPPROVED;
+                }
+                let status = state.transfer(token, from, to, value);
+                if status == 0 {
+                    state
+                        .allowances
+                        .insert((token, from, origin), allowance - value);
+                }
+                status
+            }
+            (DISPATCH, APPROVE) => {
+                let (token, spender, value) =
+                    <(TokenId, AccountId, Balance)>::decode(&mut input).expect("valid approval");
+                if !state.owners.contains_key(&token) {
+                    return UNKNOWN;
+                }
+                state.allowances.insert((token, origin, spender), value);
+                0
+            }
+            (DISPATCH, CREATE) => {
+                let (token, admin, _min_balance) =
+                    <(TokenId, AccountId, Balance)>::decode(&mut input).expect("valid token");
+                if state.owners.contains_key(&token) {
+                    return IN_USE;
+                }
+                state.owners.insert(token, admin);
+                0
+            }
+            (DISPATCH, MINT) => {
+                let (token, account, value) =
+                    <(TokenId, AccountId, Balance)>::decode(&mut input).expect("valid mint");
+                match state.owners.get(&token) {
+                    None => return UNKNOWN,
+                    Some(owner) if *owner != origin => return NO_PERMISSION,
+                    Some(_) => {}
+                }
+                let balance = state.balances.entry((token, account)).or_default();
+                *balance = balance.saturating_add(value);
+                0
+            }
+            (DISPATCH, BURN) => {
+                let (token, account, value) =
+                    <(TokenId, AccountId, Balance)>::decode(&mut input).expect("valid burn");
+                match state.owners.get(&token) {
+                    None => return UNKNOWN,
+                    Some(owner) if *owner != origin => return NO_PERMISSION,
+                    Some(_) => {}
+                }
+                let balance = state.balance_of(token, account);
+                if balance < value {
+                    return BALANCE_LOW;
+                }
+                state.balances.insert((token, account), balance - value);
+                0
+            }
+            (READ_STATE, TOTAL_SUPPLY) => {
+                let token = TokenId::decode(&mut input).expect("valid token");
+                state.total_supply(token).encode_to(output);
+                0
+            }
+            (READ_STATE, BALANCE_OF) => {
+                let (token, owner) =
+                    <(TokenId, AccountId)>::decode(&mut input).expect("valid balance");
+                state.balance_of(token, owner).encode_to(output);
+                0
+            }
+            (READ_STATE, ALLOWANCE) => {
+                let (token, owner, spender) =
+                    <(TokenId, AccountId, AccountId)>::decode(&mut input).expect("valid allowance");
+                state.allowance(token, owner, spender).encode_to(output);
+                0
+            }
+            (READ_STATE, TOKEN_EXISTS) => {
+                let token = TokenId::decode(&mut input).expect("valid token");
+                state.owners.contains_key(&token).encode_to(output);
+                0
+            }
+            _ => panic!("function {function} of fungibles api {index} is not mocked"),
+        }
+    }
+}
+
+impl State {
+    fn balance_of(&self, token: TokenId, account: AccountId) -> Balance {
+        self.balances
+            .get(&(token, account))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn total_supply(&self, token: TokenId) -> Balance {
+        self.balances
+            .iter()
+            .filter(|((t, _), _)| *t == token)
+            .map(|(_, balance)| balance)
+            .sum()
+    }
+
+    fn allowance(&self, token: TokenId, owner: AccountId, spender: AccountId) -> Balance {
+        self.allowances
+            .get(&(token, owner, spender))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn transfer(&mut self, token: TokenId, from: AccountId, to: AccountId, value: Balance) -> u32 {
+        if !self.owners.contains_key(&token) {
+            return UNKNOWN;
+        }
+        let balance = self.balance_of(token, from);
+        if balance < value {
+            return BALANCE_LOW;
+        }
+        self.balances.insert((token, from), balance - value);
+        let balance = self.balances.entry((token, to)).or_default();
+        *balance = balance.saturating_add(value);
+        0
+    }
+}
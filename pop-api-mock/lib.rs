@@ -0,0 +1,114 @@
+//! Mocks of Pop's API for testing contracts off-chain.
+//!
+//! Every module of the API is mocked in-process and registered as chain extension of ink's
+//! off-chain environment, so messages using `pop_api` can be tested with assertions on storage and
+//! events without a node. [`Sandbox::register`] registers all of them, each mock can also be
+//! registered on its own.
+//!
+//! The mocks are not tied to the off-chain environment: [`Module::call`] takes the raw chain
+//! extension id and input, so they can also back the chain extension of a sandboxed runtime.
+
+use ink::env::test;
+
+pub mod fungibles;
+pub mod messaging;
+pub mod nonfungibles;
+
+use fungibles::Fungibles;
+use messaging::MockHost;
+use nonfungibles::Nonfungibles;
+
+// Functions of the chain extension.
+pub(crate) const DISPATCH: u8 = 0;
+pub(crate) const READ_STATE: u8 = 1;
+
+/// Encodes `error` of the pallet at index `pallet` as status code, like Pop does for module errors.
+pub const fn module_error(pallet: u8, error: u8) -> u32 {
+    u32::from_le_bytes([3, pallet, error, 0])
+}
+
+/// A module of the API handling its dispatchables and state reads.
+pub trait Module: Clone + 'static {
+    /// The index of the module in the chain extension id.
+    const MODULE: u8;
+    /// The number of dispatchable and state read indices the module uses.
+    const INDICES: u8;
+
+    /// Handles `function` at `index` of the module, returning its status code.
+    fn handle(&self, function: u8, index: u8, input: &[u8], output: &mut Vec<u8>) -> u32;
+
+    /// Handles a call of the chain extension, returning its status code.
+    fn call(&self, id: u32, input: &[u8], output: &mut Vec<u8>) -> u32 {
+        let [function, _version, module, index] = id.to_le_bytes();
+        assert_eq!(
+            module,
+            Self::MODULE,
+            "call of module {module} is not mocked"
+        );
+        self.handle(function, index, input, output)
+    }
+
+    /// Registers the module as chain extension of the off-chain environment.
+    fn register_extension(&self) {
+        for index in 0..Self::INDICES {
+            test::register_chain_extension(Extension {
+                module: self.clone(),
+                index,
+            });
+        }
+    }
+}
+
+/// Routes one dispatchable or state read index of a module to its mock.
+struct Extension<M> {
+    module: M,
+    index: u8,
+}
+
+impl<M: Module> test::ChainExtension for Extension<M> {
+    fn ext_id(&self) -> u16 {
+        u16::from_le_bytes([M::MODULE, self.index])
+    }
+
+    fn call(&mut self, func_id: u16, input: &[u8], output: &mut Vec<u8>) -> u32 {
+        let [function, version] = func_id.to_le_bytes();
+        self.module.call(
+            u32::from_le_bytes([function, version, M::MODULE, self.index]),
+            input,
+            output,
+        )
+    }
+}
+
+/// Mocks of all modules of the API.
+#[derive(Clone, Default)]
+pub struct Sandbox {
+    pub messaging: MockHost,
+    pub fungibles: Fungibles,
+    pub nonfungibles: Nonfungibles,
+}
+
+impl Sandbox {
+    /// Creates the mocks and registers them as chain extensions.
+    pub fn register() -> Self {
+        let sandbox = Self::default();
+        sandbox.messaging.register_extension();
+        sandbox.fungibles.register_extension();
+        sandbox.nonfungibles.register_extension();
+        sandbox
+    }
+}
+
+/// Runs `f` as called by `contract` itself, like the runtime executing a callback.
+pub(crate) fn as_contract<R>(contract: ink::primitives::AccountId, f: impl FnOnce() -> R) -> R {
+    use ink::env::DefaultEnvironment;
+
+    let caller = ink::env::caller::<DefaultEnvironment>();
+    let callee = test::callee::<DefaultEnvironment>();
+    test::set_callee::<DefaultEnvironment>(contract);
+    test::set_caller::<DefaultEnvironment>(contract);
+    let result = f();
+    test::set_caller::<DefaultEnvironment>(caller);
+    test::set_callee::<DefaultEnvironment>(callee);
+    result
+}
@@ -0,0 +1,485 @@
+//! A mock of the messaging API.
+//!
+//! ISMP requests and XCM queries made by a contract are recorded instead of being sent and the
+//! test acts as relayer, responding to a message by its commitment. Like on Pop, a response is
+//! delivered to the callback registered with the message, or stored to be polled if there is none
+//! or it fails. XCM programs executed or sent are recorded as well.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use ink::{
+    env::{
+        hash::{Blake2x256, HashOutput, Keccak256},
+        test, DefaultEnvironment,
+    },
+    primitives::AccountId,
+    scale::{Decode, Encode},
+    xcm::{
+        prelude::{Location, QueryId, Weight, XcmHash},
+        VersionedLocation, VersionedXcm,
+    },
+};
+use pop_api::messaging::{
+    ismp::{Get, OnGetResponse, OnPostResponse, Post, StorageValue},
+    xcm::{OnResponse, Response},
+    MessageId,
+};
+
+use crate::{as_contract, module_error, Module, DISPATCH, READ_STATE};
+
+/// The module index of the messaging API.
+pub const MESSAGING: u8 = 152;
+
+// Dispatchables of the messaging API.
+const ISMP_GET: u8 = 1;
+const ISMP_POST: u8 = 2;
+const XCM_NEW_QUERY: u8 = 3;
+const REMOVE: u8 = 5;
+const XCM_EXECUTE: u8 = 6;
+const XCM_SEND: u8 = 7;
+
+// State reads of the messaging API.
+const POLL: u8 = 0;
+const GET: u8 = 1;
+const QUERY_ID: u8 = 2;
+
+/// The selector of `OnGetResponse::on_response`.
+pub const ON_GET_RESPONSE: u32 = 0x57ad942b;
+/// The selector of `OnPostResponse::on_response`.
+pub const ON_POST_RESPONSE: u32 = 0xcfb0a1d2;
+/// The selector of `xcm::OnResponse::on_response`.
+pub const ON_XCM_RESPONSE: u32 = 0x641b0b03;
+
+/// The message id is already in use by the contract.
+pub const MESSAGE_EXISTS: u32 = module_error(MESSAGING, 0);
+/// The message does not exist.
+pub const MESSAGE_NOT_FOUND: u32 = module_error(MESSAGING, 1);
+/// The message is still awaiting a response and can't be removed.
+pub const REQUEST_PENDING: u32 = module_error(MESSAGING, 2);
+
+/// The commitment of a message, identifying it to the relayer.
+pub type Commitment = [u8; 32];
+
+/// The kind of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Get,
+    Post,
+    XcmQuery,
+}
+
+/// The status of a message, encoded like the status returned by `api::poll`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode)]
+pub enum Status {
+    Pending,
+    TimedOut,
+    Complete,
+}
+
+/// A callback registered with a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode)]
+pub struct Callback {
+    pub selector: [u8; 4],
+    pub weight: Weight,
+}
+
+/// A message of a contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    /// The contract sending the message.
+    pub origin: AccountId,
+    pub id: MessageId,
+    pub kind: Kind,
+    /// The encoded request, or the responder and timeout of an XCM query.
+    pub request: Vec<u8>,
+    pub fee: u128,
+    pub callback: Option<Callback>,
+    pub commitment: Commitment,
+    /// The id of the XCM query.
+    pub query_id: Option<QueryId>,
+    pub status: Status,
+    /// The encoded response, once stored.
+    pub response: Option<Vec<u8>>,
+}
+
+impl Message {
+    /// Returns the request if it is a get request.
+    pub fn get(&self) -> Option<Get> {
+        if self.kind != Kind::Get {
+            return None;
+        }
+        Get::decode(&mut &self.request[..]).ok()
+    }
+
+    /// Returns the request if it is a post request.
+    pub fn post(&self) -> Option<Post> {
+        if self.kind != Kind::Post {
+            return None;
+        }
+        Post::decode(&mut &self.request[..]).ok()
+    }
+
+    /// Returns the responder and timeout if it is an XCM query.
+    pub fn query(&self) -> Option<(Location, u32)> {
+        if self.kind != Kind::XcmQuery {
+            return None;
+        }
+        <(Location, u32)>::decode(&mut &self.request[..]).ok()
+    }
+}
+
+/// The outcome of delivering a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// No callback is registered, the response is stored.
+    Stored,
+    /// The callback succeeded consuming `weight`, the message is removed.
+    Executed { weight: Weight },
+    /// The callback failed or ran out of weight, the response is stored.
+    Failed { weight: Weight },
+}
+
+/// Misuse of the mock by a test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// No message has the commitment.
+    UnknownCommitment,
+    /// The message is not awaiting a response.
+    NotPending,
+    /// The response doesn't match the kind of message.
+    UnexpectedKind,
+    /// The callback selector doesn't match the response.
+    UnexpectedSelector,
+}
+
+#[derive(Default)]
+struct State {
+    messages: BTreeMap<(AccountId, MessageId), Message>,
+    requests: Vec<Message>,
+    next_query_id: QueryId,
+    executed: Vec<(AccountId, VersionedXcm<()>)>,
+    sent: Vec<(AccountId, VersionedLocation, VersionedXcm<()>)>,
+    callback_weight: Option<Weight>,
+    weight_consumed: Weight,
+}
+
+/// A mock of the messaging host, shared between a test and the chain extension.
+#[derive(Clone, Default)]
+pub struct MockHost(Rc<RefCell<State>>);
+
+impl MockHost {
+    /// Creates a host and registers it as the messaging chain extension.
+    pub fn register() -> Self {
+        let host = Self::default();
+        host.register_extension();
+        host
+    }
+
+    /// Returns every message sent, in order, including those since removed.
+    pub fn requests(&self) -> Vec<Message> {
+        self.0.borrow().requests.clone()
+    }
+
+    /// Returns the message `id` of `origin`, if it exists.
+    pub fn message(&self, origin: AccountId, id: MessageId) -> Option<Message> {
+        self.0.borrow().messages.get(&(origin, id)).cloned()
+    }
+
+    /// Returns the commitment of the message `id` of the contract under test.
+    pub fn commitment(&self, id: MessageId) -> Option<Commitment> {
+        self.message(test::callee::<DefaultEnvironment>(), id)
+            .map(|m| m.commitment)
+    }
+
+    /// Returns the XCM programs executed, in order, with the contract executing them.
+    pub fn executed(&self) -> Vec<(AccountId, VersionedXcm<()>)> {
+        self.0.borrow().executed.clone()
+    }
+
+    /// Returns the XCM programs sent, in order, with the contract sending them.
+    pub fn sent(&self) -> Vec<(AccountId, VersionedLocation, VersionedXcm<()>)> {
+        self.0.borrow().sent.clone()
+    }
+
+    /// Sets the weight consumed by callbacks, by default they consume the weight registered.
+    ///
+    /// A callback consuming more than its registered weight runs out of weight and fails.
+    pub fn set_callback_weight(&self, weight: Option<Weight>) {
+        self.0.borrow_mut().callback_weight = weight;
+    }
+
+    /// Returns the weight consumed by all callbacks.
+    pub fn weight_consumed(&self) -> Weight {
+        self.0.borrow().weight_consumed
+    }
+
+    /// Responds to the get request with `commitment`, delivering `values` to `contract`.
+    pub fn respond_get<C: OnGetResponse>(
+        &self,
+        contract: &mut C,
+        commitment: &Commitment,
+        values: Vec<StorageValue>,
+    ) -> Result<Delivery, Error> {
+        self.respond_get_with(commitment, values, |id, values| {
+            contract.on_response(id, values)
+        })
+    }
+
+    /// Responds to the get request with `commitment`, delivering `values` to `callback`.
+    ///
+    /// Used for contracts handling responses with a message of their own at the selector.
+    pub fn respond_get_with<E>(
+        &self,
+        commitment: &Commitment,
+        values: Vec<StorageValue>,
+        callback: impl FnOnce(MessageId, Vec<StorageValue>) -> Result<(), E>,
+    ) -> Result<Delivery, Error> {
+        let response = values.encode();
+        self.respond(commitment, Kind::Get, ON_GET_RESPONSE, response, |id| {
+            callback(id, values).is_ok()
+        })
+    }
+
+    /// Responds to the post request with `commitment`, delivering `response` to `contract`.
+    pub fn respond_post<C: OnPostResponse>(
+        &self,
+        contract: &mut C,
+        commitment: &Commitment,
+        response: Vec<u8>,
+    ) -> Result<Delivery, Error> {
+        self.respond(
+            commitment,
+            Kind::Post,
+            ON_POST_RESPONSE,
+            response.clone(),
+            |id| contract.on_response(id, response).is_ok(),
+        )
+    }
+
+    /// Responds to the XCM query with `commitment`, delivering `response` to `contract`.
+    pub fn respond_xcm<C: OnResponse>(
+        &self,
+        contract: &mut C,
+        commitment: &Commitment,
+        response: Response,
+    ) -> Result<Delivery, Error> {
+        self.respond_xcm_with(commitment, response, |id, response| {
+            contract.on_response(id, response)
+        })
+    }
+
+    /// Responds to the XCM query with `commitment`, delivering `response` to `callback`.
+    pub fn respond_xcm_with<E>(
+        &self,
+        commitment: &Commitment,
+        response: Response,
+        callback: impl FnOnce(MessageId, Response) -> Result<(), E>,
+    ) -> Result<Delivery, Error> {
+        let encoded = response.encode();
+        self.respond(commitment, Kind::XcmQuery, ON_XCM_RESPONSE, encoded, |id| {
+            callback(id, response).is_ok()
+        })
+    }
+
+    /// Times out the message with `commitment`.
+    pub fn time_out(&self, commitment: &Commitment) -> Result<(), Error> {
+        let mut state = self.0.borrow_mut();
+        let message = state
+            .messages
+            .values_mut()
+            .find(|m| m.commitment == *commitment)
+            .ok_or(Error::UnknownCommitment)?;
+        if message.status != Status::Pending {
+            return Err(Error::NotPending);
+        }
+        message.status = Status::TimedOut;
+        Ok(())
+    }
+
+    fn respond(
+        &self,
+        commitment: &Commitment,
+        kind: Kind,
+        selector: u32,
+        response: Vec<u8>,
+        callback: impl FnOnce(MessageId) -> bool,
+    ) -> Result<Delivery, Error> {
+        let (key, registered, callback_weight) = {
+            let state = self.0.borrow();
+            let (key, message) = state
+                .messages
+                .iter()
+                .find(|(_, m)| m.commitment == *commitment)
+                .ok_or(Error::UnknownCommitment)?;
+            if message.kind != kind {
+                return Err(Error::UnexpectedKind);
+            }
+            if message.status != Status::Pending {
+                return Err(Error::NotPending);
+            }
+            (*key, message.callback, state.callback_weight)
+        };
+
+        // The state is not borrowed while the callback runs, as it may use the messaging api.
+        let delivery = match registered {
+            None => Delivery::Stored,
+            Some(registered) => {
+                if registered.selector != selector.to_be_bytes() {
+                    return Err(Error::UnexpectedSelector);
+                }
+                let weight = callback_weight.unwrap_or(registered.weight);
+                if weight.any_gt(registered.weight) {
+                    Delivery::Failed {
+                        weight: registered.weight,
+                    }
+                } else if as_contract(key.0, || callback(key.1)) {
+                    Delivery::Executed { weight }
+                } else {
+                    Delivery::Failed { weight }
+                }
+            }
+        };
+
+        let mut state = self.0.borrow_mut();
+        if let Delivery::Executed { weight } | Delivery::Failed { weight } = delivery {
+            state.weight_consumed = state.weight_consumed.saturating_add(weight);
+        }
+        if let Delivery::Executed { .. } = delivery {
+            state.messages.remove(&key);
+        } else if let Some(message) = state.messages.get_mut(&key) {
+            message.status = Status::Complete;
+            message.response = Some(response);
+        }
+        Ok(delivery)
+    }
+}
+
+impl Module for MockHost {
+    const MODULE: u8 = MESSAGING;
+    const INDICES: u8 = XCM_SEND + 1;
+
+    fn handle(&self, function: u8, index: u8, mut input: &[u8], output: &mut Vec<u8>) -> u32 {
+        let origin = test::callee::<DefaultEnvironment>();
+        let mut state = self.0.borrow_mut();
+        match (function, index) {
+            (DISPATCH, ISMP_GET) => {
+                let (id, request, fee, callback) =
+                    <(MessageId, Get, u128, Option<Callback>)>::decode(&mut input)
+                        .expect("valid ismp get");
+                state.request(origin, id, Kind::Get, request.encode(), fee, callback)
+            }
+            (DISPATCH, ISMP_POST) => {
+                let (id, request, fee, callback) =
+                    <(MessageId, Post, u128, Option<Callback>)>::decode(&mut input)
+                        .expect("valid ismp post");
+                state.request(origin, id, Kind::Post, request.encode(), fee, callback)
+            }
+            (DISPATCH, XCM_NEW_QUERY) => {
+                let (id, responder, timeout, callback) =
+                    <(MessageId, Location, u32, Option<Callback>)>::decode(&mut input)
+                        .expect("valid xcm query");
+                let request = (responder, timeout).encode();
+                state.request(origin, id, Kind::XcmQuery, request, 0, callback)
+            }
+            (DISPATCH, REMOVE) => {
+                let ids = Vec::<MessageId>::decode(&mut input).expect("valid message ids");
+                state.remove(origin, ids)
+            }
+            (DISPATCH, XCM_EXECUTE) => {
+                let message = VersionedXcm::decode(&mut input).expect("valid xcm");
+                state.executed.push((origin, message));
+                0
+            }
+            (DISPATCH, XCM_SEND) => {
+                let (dest, message) =
+                    <(VersionedLocation, VersionedXcm<()>)>::decode(&mut input).expect("valid xcm");
+                let mut hash = XcmHash::default();
+                ink::env::hash_bytes::<Blake2x256>(&message.encode(), &mut hash);
+                state.sent.push((origin, dest, message));
+                hash.encode_to(output);
+                0
+            }
+            (READ_STATE, POLL) => {
+                let key = <(AccountId, MessageId)>::decode(&mut input).expect("valid key");
+                state.messages.get(&key).map(|m| m.status).encode_to(output);
+                0
+            }
+            (READ_STATE, GET) => {
+                let key = <(AccountId, MessageId)>::decode(&mut input).expect("valid key");
+                state
+                    .messages
+                    .get(&key)
+                    .and_then(|m| m.response.clone())
+                    .encode_to(output);
+                0
+            }
+            (READ_STATE, QUERY_ID) => {
+                let key = <(AccountId, MessageId)>::decode(&mut input).expect("valid key");
+                state
+                    .messages
+                    .get(&key)
+                    .and_then(|m| m.query_id)
+                    .encode_to(output);
+                0
+            }
+            _ => panic!("function {function} of messaging api {index} is not mocked"),
+        }
+    }
+}
+
+impl State {
+    fn request(
+        &mut self,
+        origin: AccountId,
+        id: MessageId,
+        kind: Kind,
+        request: Vec<u8>,
+        fee: u128,
+        callback: Option<Callback>,
+    ) -> u32 {
+        if self.messages.contains_key(&(origin, id)) {
+            return MESSAGE_EXISTS;
+        }
+        let mut commitment = <Keccak256 as HashOutput>::Type::default();
+        ink::env::hash_bytes::<Keccak256>(
+            &(origin, id, kind as u8, &request).encode(),
+            &mut commitment,
+        );
+        let query_id = (kind == Kind::XcmQuery).then(|| {
+            let query_id = self.next_query_id;
+            self.next_query_id = self.next_query_id.saturating_add(1);
+            query_id
+        });
+        let message = Message {
+            origin,
+            id,
+            kind,
+            request,
+            fee,
+            callback,
+            commitment,
+            query_id,
+            status: Status::Pending,
+            response: None,
+        };
+        self.requests.push(message.clone());
+        self.messages.insert((origin, id), message);
+        0
+    }
+
+    fn remove(&mut self, origin: AccountId, ids: Vec<MessageId>) -> u32 {
+        for id in &ids {
+            match self.messages.get(&(origin, *id)) {
+                None => return MESSAGE_NOT_FOUND,
+                Some(message) if message.status == Status::Pending => return REQUEST_PENDING,
+                Some(_) => {}
+            }
+        }
+        for id in ids {
+            self.messages.remove(&(origin, id));
+        }
+        0
+    }
+}
@@ -0,0 +1,182 @@
+//! A mock of the nonfungibles API, keeping the collections and the owner of each item.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use ink::{
+    env::{test, DefaultEnvironment},
+    primitives::AccountId,
+    scale::{Decode, Encode},
+};
+
+use crate::{module_error, Module, DISPATCH, READ_STATE};
+
+/// The module index of the nonfungibles API.
+pub const NONFUNGIBLES: u8 = 151;
+/// The index of the NFTs pallet on Pop, raising the errors of the nonfungibles API.
+const NFTS: u8 = 50;
+
+// Dispatchables of the nonfungibles API.
+const TRANSFER: u8 = 3;
+const CREATE: u8 = 7;
+const MINT: u8 = 18;
+const BURN: u8 = 19;
+
+// State reads of the nonfungibles API.
+const BALANCE_OF: u8 = 0;
+const OWNER_OF: u8 = 1;
+const TOTAL_SUPPLY: u8 = 5;
+const NEXT_COLLECTION_ID: u8 = 20;
+
+/// The caller is not permitted to manage the collection or item.
+pub const NO_PERMISSION: u32 = module_error(NFTS, 0);
+/// The collection does not exist.
+pub const UNKNOWN_COLLECTION: u32 = module_error(NFTS, 1);
+/// The item already exists.
+pub const ALREADY_EXISTS: u32 = module_error(NFTS, 2);
+/// The item does not exist.
+pub const UNKNOWN_ITEM: u32 = module_error(NFTS, 20);
+
+pub type CollectionId = u32;
+pub type ItemId = u32;
+
+#[derive(Default)]
+struct State {
+    admins: BTreeMap<CollectionId, AccountId>,
+    next_collection_id: CollectionId,
+    owners: BTreeMap<(CollectionId, ItemId), AccountId>,
+}
+
+/// A mock of the nonfungibles API, shared between a test and the chain extension.
+#[derive(Clone, Default)]
+pub struct Nonfungibles(Rc<RefCell<State>>);
+
+impl Nonfungibles {
+    /// Creates the mock and registers it as the nonfungibles chain extension.
+    pub fn register() -> Self {
+        let nonfungibles = Self::default();
+        nonfungibles.register_extension();
+        nonfungibles
+    }
+
+    /// Creates a collection administered by `admin`, returning its id.
+    pub fn create(&self, admin: AccountId) -> CollectionId {
+        self.0.borrow_mut().create(admin)
+    }
+
+    /// Returns the admin of `collection`, if it exists.
+    pub fn admin(&self, collection: CollectionId) -> Option<AccountId> {
+        self.0.borrow().admins.get(&collection).copied()
+    }
+
+    /// Returns the owner of `item` in `collection`, if it exists.
+    pub fn owner_of(&self, collection: CollectionId, item: ItemId) -> Option<AccountId> {
+        self.0.borrow().owners.get(&(collection, item)).copied()
+    }
+
+    /// Returns the items of `collection` with their owners.
+    pub fn items(&self, collection: CollectionId) -> Vec<(ItemId, AccountId)> {
+        self.0
+            .borrow()
+            .owners
+            .iter()
+            .filter(|((c, _), _)| *c == collection)
+            .map(|((_, item), owner)| (*item, *owner))
+            .collect()
+    }
+}
+
+impl Module for Nonfungibles {
+    const MODULE: u8 = NONFUNGIBLES;
+    const INDICES: u8 = NEXT_COLLECTION_ID + 1;
+
+    fn handle(&self, function: u8, index: u8, mut input: &[u8], output: &mut Vec<u8>) -> u32 {
+        let origin = test::callee::<DefaultEnvironment>();
+        let mut state = self.0.borrow_mut();
+        match (function, index) {
+            (DISPATCH, TRANSFER) => {
+                let (collection, item, to) =
+                    <(CollectionId, ItemId, AccountId)>::decode(&mut input)
+                        .expect("valid transfer");
+                match state.owners.get(&(collection, item)) {
+                    None => return UNKNOWN_ITEM,
+                    Some(owner) if *owner != origin => return NO_PERMISSION,
+                    Some(_) => {}
+                }
+                state.owners.insert((collection, item), to);
+                0
+            }
+            (DISPATCH, CREATE) => {
+                // The collection config following the admin is not mocked.
+                let admin = AccountId::decode(&mut input).expect("valid collection");
+                state.create(admin);
+                0
+            }
+            (DISPATCH, MINT) => {
+                // The mint witness following the item is not mocked.
+                let (to, collection, item) =
+                    <(AccountId, CollectionId, ItemId)>::decode(&mut input).expect("valid mint");
+                match state.admins.get(&collection) {
+                    None => return UNKNOWN_COLLECTION,
+                    Some(admin) if *admin != origin => return NO_PERMISSION,
+                    Some(_) => {}
+                }
+                if state.owners.contains_key(&(collection, item)) {
+                    return ALREADY_EXISTS;
+                }
+                state.owners.insert((collection, item), to);
+                0
+            }
+            (DISPATCH, BURN) => {
+                let (collection, item) =
+                    <(CollectionId, ItemId)>::decode(&mut input).expect("valid burn");
+                match state.owners.get(&(collection, item)) {
+                    None => return UNKNOWN_ITEM,
+                    Some(owner) if *owner != origin => return NO_PERMISSION,
+                    Some(_) => {}
+                }
+                state.owners.remove(&(collection, item));
+                0
+            }
+            (READ_STATE, BALANCE_OF) => {
+                let (collection, owner) =
+                    <(CollectionId, AccountId)>::decode(&mut input).expect("valid balance");
+                let balance = state
+                    .owners
+                    .iter()
+                    .filter(|((c, _), o)| *c == collection && **o == owner)
+                    .count() as u32;
+                balance.encode_to(output);
+                0
+            }
+            (READ_STATE, OWNER_OF) => {
+                let key = <(CollectionId, ItemId)>::decode(&mut input).expect("valid item");
+                state.owners.get(&key).encode_to(output);
+                0
+            }
+            (READ_STATE, TOTAL_SUPPLY) => {
+                let collection = CollectionId::decode(&mut input).expect("valid collection");
+                let supply = state
+                    .owners
+                    .keys()
+                    .filter(|(c, _)| *c == collection)
+                    .count() as u128;
+                supply.encode_to(output);
+                0
+            }
+            (READ_STATE, NEXT_COLLECTION_ID) => {
+                Some(state.next_collection_id).encode_to(output);
+                0
+            }
+            _ => panic!("function {function} of nonfungibles api {index} is not mocked"),
+        }
+    }
+}
+
+impl State {
+    fn create(&mut self, admin: AccountId) -> CollectionId {
+        let collection = self.next_collection_id;
+        self.next_collection_id = self.next_collection_id.saturating_add(1);
+        self.admins.insert(collection, admin);
+        collection
+    }
+}
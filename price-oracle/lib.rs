@@ -0,0 +1,562 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+use ink::prelude::vec::Vec;
+use ismp_heights::{Height, Heights};
+use message_ids::MessageIds;
+use pop_api::{
+    messaging::{
+        self as api,
+        ismp::{Get, StorageValue},
+        Callback, MessageId, ParaId,
+    },
+    StatusCode,
+};
+use twap::Observation;
+
+pub mod twap;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Identifies an asset within the oracle.
+pub type AssetId = u32;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    StatusCode(u32),
+    Unauthorized,
+    UnknownFeed,
+    /// The value transferred does not cover the fee of the feed.
+    InsufficientFee,
+    /// The height is not at least the minimum spacing of the feed after the height of the last
+    /// refresh of the pair.
+    StaleHeight,
+    UnknownRequest,
+    /// A reserve is missing from the response or could not be decoded.
+    InvalidReserve,
+    /// The price could not be calculated from the reserves.
+    InvalidPrice,
+    /// No price has been observed for the pair.
+    NoPrice,
+    /// The last observation is older than the maximum age.
+    Outdated,
+    /// No height of the chain of the feed was reported yet.
+    UnknownHeight,
+    /// The height is ahead of the latest height reported, so the refresh would never be answered.
+    HeightAhead,
+    /// The surplus over the fee of the feed could not be refunded.
+    TransferFailed,
+}
+
+impl From<StatusCode> for Error {
+    fn from(value: StatusCode) -> Self {
+        Error::StatusCode(value.0)
+    }
+}
+
+impl From<ismp_heights::Error> for Error {
+    fn from(value: ismp_heights::Error) -> Self {
+        match value {
+            ismp_heights::Error::UnknownHeight => Error::UnknownHeight,
+            ismp_heights::Error::HeightAhead => Error::HeightAhead,
+        }
+    }
+}
+
+/// The storage of a reserve of a pool on another chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub struct Reserve {
+    /// The storage key of the balance of the pool account.
+    pub key: Vec<u8>,
+    /// The offset of the balance, a `u128`, within the value, e.g. `16` for `System::Account`
+    /// and `0` for `Assets::Account` or `Tokens::Accounts`.
+    pub offset: u32,
+}
+
+impl Reserve {
+    /// Decodes the reserve from `values`.
+    fn decode(&self, values: &[StorageValue]) -> Option<u128> {
+        let value = values.iter().find(|value| value.key == self.key)?;
+        let offset = self.offset as usize;
+        let bytes = value.value.as_ref()?.get(offset..offset.checked_add(16)?)?;
+        Some(u128::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// The pool on another chain the price of a pair is read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub struct Feed {
+    pub dest: ParaId,
+    /// The reserves of the base and the quote asset.
+    pub reserves: (Reserve, Reserve),
+    /// The ISMP fee of a refresh, paid by the caller.
+    pub fee: u128,
+    /// The minimum number of blocks of `dest` between the heights of refreshes, so the
+    /// observations kept span at least `twap::MAX_OBSERVATIONS - 1` times as many blocks.
+    pub min_spacing: u32,
+}
+
+/// The price of the base asset in the quote asset, with a precision of [`twap::PRECISION`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub struct Price {
+    /// The time-weighted average over the observations kept.
+    pub twap: u128,
+    /// The price of the last observation.
+    pub spot: u128,
+    /// The height of the storage proof of the last observation.
+    pub height: u32,
+    /// The block on Pop the last observation was made at, once the response arrived.
+    pub updated_at: u32,
+}
+
+#[ink::contract]
+mod price_oracle {
+    use ink::{storage::Mapping, xcm::prelude::Weight};
+
+    use super::*;
+    use crate::Error::*;
+
+    /// The callback was not called by the runtime.
+    const UNAUTHORIZED: u32 = u32::MAX;
+    /// The response could not be observed, see `observe`.
+    const INVALID_RESPONSE: u32 = u32::MAX - 1;
+
+    #[ink::event]
+    pub struct RefreshRequested {
+        #[ink(topic)]
+        pub id: MessageId,
+        pub pair: (AssetId, AssetId),
+        pub height: u32,
+    }
+
+    #[ink::event]
+    pub struct FeeRefunded {
+        #[ink(topic)]
+        pub id: MessageId,
+        #[ink(topic)]
+        pub payer: AccountId,
+        pub amount: Balance,
+    }
+
+    #[ink::event]
+    pub struct PriceUpdated {
+        #[ink(topic)]
+        pub pair: (AssetId, AssetId),
+        pub spot: u128,
+        pub twap: u128,
+        pub height: u32,
+    }
+
+    #[ink(storage)]
+    #[derive(Default)]
+    pub struct PriceOracle {
+        owner: AccountId,
+        feeds: Mapping<(AssetId, AssetId), Feed>,
+        observations: Mapping<(AssetId, AssetId), Vec<Observation>>,
+        /// The block on Pop each pair was last observed at.
+        updated_at: Mapping<(AssetId, AssetId), BlockNumber>,
+        /// The pair and height of pending refreshes.
+        requests: Mapping<MessageId, ((AssetId, AssetId), u32)>,
+        /// The ids of the messages of refreshes.
+        ids: MessageIds,
+        /// The height of the last refresh of each pair, pending or observed.
+        refreshed: Mapping<(AssetId, AssetId), u32>,
+        /// The latest heights of the chains of feeds with a state commitment on Pop, reported by
+        /// the owner.
+        heights: Heights,
+    }
+
+    impl PriceOracle {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                owner: Self::env().caller(),
+                ..Default::default()
+            }
+        }
+
+        /// Sets the pool the price of `base` in `quote` is read from, keeping any observations.
+        #[ink(message)]
+        pub fn set_feed(&mut self, base: AssetId, quote: AssetId, feed: Feed) -> Result<()> {
+            self.ensure_owner()?;
+            self.feeds.insert((base, quote), &feed);
+            Ok(())
+        }
+
+        /// Removes the feed of `base` in `quote` and its observations.
+        #[ink(message)]
+        pub fn remove_feed(&mut self, base: AssetId, quote: AssetId) -> Result<()> {
+            self.ensure_owner()?;
+            self.feeds.take((base, quote)).ok_or(UnknownFeed)?;
+            self.observations.remove((base, quote));
+            self.updated_at.remove((base, quote));
+            self.refreshed.remove((base, quote));
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn feed(&self, base: AssetId, quote: AssetId) -> Option<Feed> {
+            self.feeds.get((base, quote))
+        }
+
+        /// Returns the latest height of `dest` reported by the owner.
+        #[ink(message)]
+        pub fn latest_height(&self, dest: ParaId) -> Option<u32> {
+            self.heights.latest(dest)
+        }
+
+        /// Reports the latest height of `dest` with a state commitment on Pop, as seen in Pop's
+        /// `StateMachineUpdated` events. The contract can not read the commitments itself, so
+        /// heights refreshed at are only checked against this report. Lower heights are ignored.
+        #[ink(message)]
+        pub fn update_height(&mut self, dest: ParaId, height: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.heights.update(dest, height);
+            Ok(())
+        }
+
+        /// Reads the reserves of the pool of `base` in `quote` at `height`, observing their price
+        /// once the response arrives. Anyone can refresh a feed by paying its fee. The height must
+        /// not be ahead of the latest height reported for the chain of the feed, and at least the
+        /// minimum spacing of the feed after the last refresh, so refreshes can not flush the
+        /// observations kept. Any surplus transferred over the fee is refunded to the caller.
+        #[ink(message, payable)]
+        pub fn refresh(&mut self, base: AssetId, quote: AssetId, height: Height) -> Result<()> {
+            let pair = (base, quote);
+            let feed = self.feeds.get(pair).ok_or(UnknownFeed)?;
+            let surplus = self
+                .env()
+                .transferred_value()
+                .checked_sub(feed.fee)
+                .ok_or(InsufficientFee)?;
+            let height = self.heights.resolve(feed.dest, height)?;
+            if let Some(refreshed) = self.refreshed.get(pair) {
+                if height < refreshed.saturating_add(feed.min_spacing.max(1)) {
+                    return Err(StaleHeight);
+                }
+            }
+
+            let keys = Vec::from([feed.reserves.0.key, feed.reserves.1.key]);
+            let (id, ()) = self.ids.send(|id| {
+                api::ismp::get(
+                    id,
+                    Get::new(feed.dest, height, 0, Vec::default(), keys.clone()),
+                    feed.fee,
+                    Some(Callback::to(
+                        0x57ad942b,
+                        Weight::from_parts(800_000_000, 200_000),
+                    )),
+                )
+            })?;
+            self.requests.insert(id, &(pair, height));
+            self.refreshed.insert(pair, &height);
+            self.env().emit_event(RefreshRequested { id, pair, height });
+            if surplus > 0 {
+                let payer = self.env().caller();
+                self.env()
+                    .transfer(payer, surplus)
+                    .map_err(|_| TransferFailed)?;
+                self.env().emit_event(FeeRefunded {
+                    id,
+                    payer,
+                    amount: surplus,
+                });
+            }
+            Ok(())
+        }
+
+        /// Returns the price of `base` in `quote`, failing if it was last observed more than
+        /// `max_age` blocks ago. The price of the inverse pair is inverted if `base` in `quote`
+        /// has no feed.
+        ///
+        /// The age is measured in blocks of Pop since the response of the last observation
+        /// arrived, `updated_at`, not since its proof `height` on the chain of the pool, which may
+        /// lag behind by as long as the refresh was pending.
+        #[ink(message)]
+        pub fn price(&self, base: AssetId, quote: AssetId, max_age: BlockNumber) -> Result<Price> {
+            let (pair, inverse) = if self.feeds.contains((base, quote)) {
+                ((base, quote), false)
+            } else if self.feeds.contains((quote, base)) {
+                ((quote, base), true)
+            } else {
+                return Err(UnknownFeed);
+            };
+            let observations = self.observations.get(pair).unwrap_or_default();
+            let last = observations.last().ok_or(NoPrice)?;
+            let updated_at = self.updated_at.get(pair).ok_or(NoPrice)?;
+            if self.env().block_number().saturating_sub(updated_at) > max_age {
+                return Err(Outdated);
+            }
+            let mut price = Price {
+                twap: twap::twap(&observations).ok_or(InvalidPrice)?,
+                spot: last.spot,
+                height: last.height,
+                updated_at,
+            };
+            if inverse {
+                price.twap = twap::invert(price.twap).ok_or(InvalidPrice)?;
+                price.spot = twap::invert(price.spot).ok_or(InvalidPrice)?;
+            }
+            Ok(price)
+        }
+
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Unauthorized);
+            }
+            Ok(())
+        }
+    }
+
+    impl api::ismp::OnGetResponse for PriceOracle {
+        #[ink(message)]
+        fn on_response(&mut self, id: MessageId, values: Vec<StorageValue>) -> pop_api::Result<()> {
+            if self.env().caller() != self.env().account_id() {
+                return Err(UNAUTHORIZED.into());
+            }
+            // A failed callback leaves the response to be retrieved from the runtime.
+            self.observe(id, values)
+                .map_err(|_| INVALID_RESPONSE.into())
+        }
+    }
+
+    impl PriceOracle {
+        // Observes the price of the pair of the refresh with `id` from the reserves in `values`.
+        fn observe(&mut self, id: MessageId, values: Vec<StorageValue>) -> Result<()> {
+            let (pair, height) = self.requests.take(id).ok_or(UnknownRequest)?;
+            // The feed may have been changed or removed while the refresh was pending.
+            let feed = self.feeds.get(pair).ok_or(UnknownFeed)?;
+            let base = feed.reserves.0.decode(&values).ok_or(InvalidReserve)?;
+            let quote = feed.reserves.1.decode(&values).ok_or(InvalidReserve)?;
+            let spot = twap::spot(base, quote).ok_or(InvalidPrice)?;
+
+            let mut observations = self.observations.get(pair).unwrap_or_default();
+            twap::observe(&mut observations, height, spot).ok_or(StaleHeight)?;
+            let twap = twap::twap(&observations).ok_or(InvalidPrice)?;
+            self.observations.insert(pair, &observations);
+            self.updated_at.insert(pair, &self.env().block_number());
+            self.env().emit_event(PriceUpdated {
+                pair,
+                spot,
+                twap,
+                height,
+            });
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+        use pop_api_mock::messaging::{Delivery, MockHost};
+        use twap::PRECISION;
+
+        const FEE: Balance = 100;
+
+        fn feed() -> Feed {
+            Feed {
+                dest: 2034,
+                reserves: (
+                    Reserve {
+                        key: b"base".to_vec(),
+                        offset: 0,
+                    },
+                    // Like `System::Account`, with the free balance after four counters.
+                    Reserve {
+                        key: b"quote".to_vec(),
+                        offset: 16,
+                    },
+                ),
+                fee: FEE,
+                min_spacing: 10,
+            }
+        }
+
+        fn reserves(base: u128, quote: u128) -> Vec<StorageValue> {
+            let mut account = [0u8; 16].to_vec();
+            account.extend(quote.to_le_bytes());
+            Vec::from([
+                StorageValue {
+                    key: b"base".to_vec(),
+                    value: Some(base.to_le_bytes().to_vec()),
+                },
+                StorageValue {
+                    key: b"quote".to_vec(),
+                    value: Some(account),
+                },
+            ])
+        }
+
+        fn oracle() -> PriceOracle {
+            let mut oracle = PriceOracle::new();
+            assert_eq!(oracle.set_feed(1, 2, feed()), Ok(()));
+            assert_eq!(oracle.update_height(2034, 1_000), Ok(()));
+            test::set_value_transferred::<Environment>(FEE);
+            oracle
+        }
+
+        fn refresh(
+            host: &MockHost,
+            oracle: &mut PriceOracle,
+            height: u32,
+            values: Vec<StorageValue>,
+        ) -> Delivery {
+            assert_eq!(oracle.refresh(1, 2, Height::At(height)), Ok(()));
+            let request = host.requests().pop().unwrap();
+            host.respond_get(oracle, &request.commitment, values)
+                .unwrap()
+        }
+
+        #[ink::test]
+        fn refresh_works() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            assert_eq!(oracle.refresh(1, 2, Height::At(10)), Ok(()));
+            let request = &host.requests()[0];
+            let get = request.get().unwrap();
+            assert_eq!((get.dest, get.height, request.fee), (2034, 10, FEE));
+            assert_eq!(get.keys, [b"base".to_vec(), b"quote".to_vec()]);
+            assert_eq!(oracle.price(1, 2, 0), Err(NoPrice));
+
+            assert!(matches!(
+                host.respond_get(&mut oracle, &request.commitment, reserves(100, 250)),
+                Ok(Delivery::Executed { .. })
+            ));
+            let spot = 2 * PRECISION + PRECISION / 2;
+            assert_eq!(
+                oracle.price(1, 2, 0),
+                Ok(Price {
+                    twap: spot,
+                    spot,
+                    height: 10,
+                    updated_at: 0,
+                })
+            );
+            // The inverse pair is derived from the feed.
+            assert_eq!(
+                oracle.price(2, 1, 0).map(|price| price.spot),
+                Ok(PRECISION * 2 / 5)
+            );
+            assert_eq!(oracle.price(1, 3, 0), Err(UnknownFeed));
+        }
+
+        #[ink::test]
+        fn price_is_time_weighted() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            refresh(&host, &mut oracle, 10, reserves(100, 100));
+            refresh(&host, &mut oracle, 40, reserves(100, 200));
+            refresh(&host, &mut oracle, 50, reserves(100, 1_000));
+            // A price of 1 held for 30 blocks and a price of 2 for 10 blocks.
+            let price = oracle.price(1, 2, 0).unwrap();
+            assert_eq!(
+                (price.twap, price.spot, price.height),
+                (PRECISION * 5 / 4, 10 * PRECISION, 50)
+            );
+        }
+
+        #[ink::test]
+        fn price_rejects_outdated_observations() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            refresh(&host, &mut oracle, 10, reserves(100, 100));
+            test::advance_block::<Environment>();
+            test::advance_block::<Environment>();
+            assert_eq!(oracle.price(1, 2, 1), Err(Outdated));
+            assert!(oracle.price(1, 2, 2).is_ok());
+        }
+
+        #[ink::test]
+        fn refresh_rejects_invalid_requests() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            assert_eq!(oracle.refresh(2, 1, Height::At(10)), Err(UnknownFeed));
+            test::set_value_transferred::<Environment>(FEE - 1);
+            assert_eq!(oracle.refresh(1, 2, Height::At(10)), Err(InsufficientFee));
+            test::set_value_transferred::<Environment>(FEE);
+            assert_eq!(oracle.refresh(1, 2, Height::At(1_001)), Err(HeightAhead));
+            refresh(&host, &mut oracle, 10, reserves(100, 100));
+            assert_eq!(oracle.refresh(1, 2, Height::At(10)), Err(StaleHeight));
+        }
+
+        #[ink::test]
+        fn refresh_refunds_surplus() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            let alice = test::default_accounts::<Environment>().alice;
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 1_000);
+            let balance = test::get_account_balance::<Environment>(alice).unwrap();
+            test::set_value_transferred::<Environment>(FEE + 50);
+            assert_eq!(oracle.refresh(1, 2, Height::At(10)), Ok(()));
+            // Only the fee of the feed is paid for the request.
+            assert_eq!(host.requests()[0].fee, FEE);
+            assert_eq!(
+                test::get_account_balance::<Environment>(alice),
+                Ok(balance + 50)
+            );
+            assert_eq!(test::recorded_events().count(), 2);
+        }
+
+        #[ink::test]
+        fn refresh_is_spaced() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            assert_eq!(oracle.refresh(1, 2, Height::At(10)), Ok(()));
+            // Pending refreshes count too, so the observations kept can't be flushed.
+            assert_eq!(oracle.refresh(1, 2, Height::At(19)), Err(StaleHeight));
+            assert_eq!(oracle.refresh(1, 2, Height::At(20)), Ok(()));
+            assert_eq!(oracle.refresh(1, 2, Height::Latest), Ok(()));
+            assert_eq!(oracle.refresh(1, 2, Height::Latest), Err(StaleHeight));
+            let heights: Vec<_> = host
+                .requests()
+                .iter()
+                .map(|request| request.get().unwrap().height)
+                .collect();
+            assert_eq!(heights, [10, 20, 1_000]);
+        }
+
+        #[ink::test]
+        fn refresh_requires_reported_height() {
+            let _host = MockHost::register();
+            let mut oracle = PriceOracle::new();
+            assert_eq!(oracle.set_feed(1, 2, feed()), Ok(()));
+            test::set_value_transferred::<Environment>(FEE);
+            assert_eq!(oracle.refresh(1, 2, Height::Latest), Err(UnknownHeight));
+            assert_eq!(oracle.update_height(2034, 10), Ok(()));
+            assert_eq!(oracle.latest_height(2034), Some(10));
+            assert_eq!(oracle.refresh(1, 2, Height::Latest), Ok(()));
+        }
+
+        #[ink::test]
+        fn invalid_reserves_are_not_observed() {
+            let host = MockHost::register();
+            let mut oracle = oracle();
+            let mut values = reserves(100, 100);
+            values.pop();
+            assert!(matches!(
+                refresh(&host, &mut oracle, 10, values),
+                Delivery::Failed { .. }
+            ));
+            assert!(matches!(
+                refresh(&host, &mut oracle, 20, reserves(0, 100)),
+                Delivery::Failed { .. }
+            ));
+            assert_eq!(oracle.price(1, 2, 0), Err(NoPrice));
+        }
+
+        #[ink::test]
+        fn feeds_are_managed_by_owner() {
+            let mut oracle = PriceOracle::new();
+            test::set_caller::<Environment>(test::default_accounts::<Environment>().bob);
+            assert_eq!(oracle.set_feed(1, 2, feed()), Err(Unauthorized));
+            assert_eq!(oracle.remove_feed(1, 2), Err(Unauthorized));
+            assert_eq!(oracle.update_height(2034, 10), Err(Unauthorized));
+        }
+    }
+}
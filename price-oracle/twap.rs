@@ -0,0 +1,108 @@
+use ink::prelude::vec::Vec;
+
+/// The precision of prices: a price of `PRECISION` means one unit of the base asset is worth one
+/// unit of the quote asset.
+pub const PRECISION: u128 = 1_000_000_000_000_000_000;
+/// The number of observations kept per pair, bounding the window of the time-weighted average.
+pub const MAX_OBSERVATIONS: usize = 8;
+
+/// A spot price observed at a height of the chain the reserves were read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+pub struct Observation {
+    /// The height of the storage proof of the reserves.
+    pub height: u32,
+    pub spot: u128,
+    /// The sum of the spot prices of the previous observations, each weighted by the number of
+    /// blocks until the next observation.
+    pub cumulative: u128,
+}
+
+/// Returns the price of the base asset in the quote asset, given the reserves of both in a pool.
+pub fn spot(base_reserve: u128, quote_reserve: u128) -> Option<u128> {
+    if base_reserve == 0 {
+        return None;
+    }
+    quote_reserve
+        .checked_mul(PRECISION)?
+        .checked_div(base_reserve)
+}
+
+/// Returns the price of the quote asset in the base asset, given the price of the base asset.
+pub fn invert(price: u128) -> Option<u128> {
+    (PRECISION * PRECISION).checked_div(price)
+}
+
+/// Appends the spot price observed at `height`, dropping the oldest observation once
+/// [`MAX_OBSERVATIONS`] are kept. Returns `None` if `height` is not after the last observation.
+pub fn observe(observations: &mut Vec<Observation>, height: u32, spot: u128) -> Option<()> {
+    let cumulative = match observations.last() {
+        None => 0,
+        Some(last) if height <= last.height => return None,
+        Some(last) => last
+            .spot
+            .checked_mul((height - last.height).into())?
+            .checked_add(last.cumulative)?,
+    };
+    if observations.len() == MAX_OBSERVATIONS {
+        observations.remove(0);
+    }
+    observations.push(Observation {
+        height,
+        spot,
+        cumulative,
+    });
+    Some(())
+}
+
+/// Returns the average of the spot prices between the first and last observation, weighted by the
+/// number of blocks each of them held.
+pub fn twap(observations: &[Observation]) -> Option<u128> {
+    let (first, last) = (observations.first()?, observations.last()?);
+    if first.height == last.height {
+        return Some(last.spot);
+    }
+    (last.cumulative - first.cumulative).checked_div((last.height - first.height).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_works() {
+        assert_eq!(spot(100, 250), Some(2 * PRECISION + PRECISION / 2));
+        assert_eq!(spot(0, 250), None);
+        assert_eq!(spot(1, u128::MAX), None);
+        assert_eq!(invert(2 * PRECISION), Some(PRECISION / 2));
+        assert_eq!(invert(0), None);
+    }
+
+    #[test]
+    fn twap_weights_prices_by_blocks() {
+        let mut observations = Vec::new();
+        assert_eq!(twap(&observations), None);
+        assert_eq!(observe(&mut observations, 10, 100), Some(()));
+        assert_eq!(twap(&observations), Some(100));
+        // The price of 100 held for 30 blocks, the price of 200 for 10 blocks.
+        assert_eq!(observe(&mut observations, 40, 200), Some(()));
+        assert_eq!(observe(&mut observations, 50, 1_000), Some(()));
+        assert_eq!(twap(&observations), Some(125));
+        // Observations must advance.
+        assert_eq!(observe(&mut observations, 50, 1), None);
+        assert_eq!(observations.len(), 3);
+    }
+
+    #[test]
+    fn observations_are_bounded() {
+        let mut observations = Vec::new();
+        for height in 1..=MAX_OBSERVATIONS as u32 + 2 {
+            assert_eq!(observe(&mut observations, height, height.into()), Some(()));
+        }
+        assert_eq!(observations.len(), MAX_OBSERVATIONS);
+        assert_eq!(observations[0].height, 3);
+        // The prices from height 3 until 9, each held for one block.
+        assert_eq!(twap(&observations), Some(6));
+    }
+}
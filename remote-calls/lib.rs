@@ -0,0 +1,381 @@
+//! Encoding of calls dispatched on other chains by `Transact`, so they can be constructed from
+//! typed arguments rather than copied from polkadot.js.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{
+    env::hash::{Blake2x256, CryptoHash},
+    prelude::vec::Vec,
+    scale::{Compact, Encode},
+};
+
+/// The pallet indices of a chain used to wrap calls.
+pub struct Chain {
+    pub para_id: u32,
+    pub utility: u8,
+    pub proxy: u8,
+}
+
+impl Chain {
+    /// Returns `pallet_utility`, batching calls and dispatching them by derived accounts.
+    pub fn utility_pallet(&self) -> Pallet {
+        Pallet {
+            module_name: b"pallet_utility",
+            index: self.utility,
+        }
+    }
+
+    /// Returns `pallet_proxy`, dispatching calls on behalf of other accounts.
+    pub fn proxy_pallet(&self) -> Pallet {
+        Pallet {
+            module_name: b"pallet_proxy",
+            index: self.proxy,
+        }
+    }
+}
+
+/// A pallet whose calls are encoded, identified by the name of its crate as queried with
+/// `QueryPallet`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pallet {
+    pub module_name: &'static [u8],
+    /// The index of the pallet in the runtime the calls are encoded for.
+    pub index: u8,
+}
+
+pub const HYDRATION: Chain = Chain {
+    para_id: 2034,
+    utility: 13,
+    proxy: 29,
+};
+
+pub const ASSET_HUB: Chain = Chain {
+    para_id: 1000,
+    utility: 40,
+    proxy: 42,
+};
+
+// Call indices of `pallet_utility` and `pallet_proxy`.
+const AS_DERIVATIVE: u8 = 1;
+const BATCH_ALL: u8 = 2;
+const PROXY: u8 = 0;
+
+/// How calls are dispatched on the destination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Dispatch {
+    /// By the origin of the `Transact`, e.g. the sovereign account of the contract.
+    Direct,
+    /// On behalf of the account, which the origin of the `Transact` is a proxy of.
+    Proxy([u8; 32]),
+    /// By the account derived from the origin of the `Transact` with the index, see
+    /// [`derivative_account`].
+    Derivative(u16),
+}
+
+/// Encodes `account` as `MultiAddress::Id`, the lookup of accounts used by calls.
+fn multi_address(account: [u8; 32]) -> (u8, [u8; 32]) {
+    (0, account)
+}
+
+/// Calls of Hydration.
+pub mod hydration {
+    use super::*;
+
+    pub type AssetId = u32;
+
+    pub const BALANCES: Pallet = Pallet {
+        module_name: b"pallet_balances",
+        index: 7,
+    };
+    pub const OMNIPOOL: Pallet = Pallet {
+        module_name: b"pallet_omnipool",
+        index: 59,
+    };
+
+    /// Encodes `balances.transfer_keep_alive(dest, value)`, transferring HDX.
+    pub fn transfer_keep_alive(dest: [u8; 32], value: u128) -> Vec<u8> {
+        (BALANCES.index, 3u8, multi_address(dest), Compact(value)).encode()
+    }
+
+    /// Encodes `omnipool.sell(asset_in, asset_out, amount, min_buy_amount)`, selling `amount` of
+    /// `asset_in`.
+    pub fn sell(
+        asset_in: AssetId,
+        asset_out: AssetId,
+        amount: u128,
+        min_buy_amount: u128,
+    ) -> Vec<u8> {
+        (
+            OMNIPOOL.index,
+            4u8,
+            asset_in,
+            asset_out,
+            amount,
+            min_buy_amount,
+        )
+            .encode()
+    }
+
+    /// Encodes `omnipool.buy(asset_out, asset_in, amount, max_sell_amount)`, buying `amount` of
+    /// `asset_out`.
+    pub fn buy(
+        asset_out: AssetId,
+        asset_in: AssetId,
+        amount: u128,
+        max_sell_amount: u128,
+    ) -> Vec<u8> {
+        (
+            OMNIPOOL.index,
+            5u8,
+            asset_out,
+            asset_in,
+            amount,
+            max_sell_amount,
+        )
+            .encode()
+    }
+}
+
+/// Calls of Asset Hub.
+pub mod asset_hub {
+    use super::*;
+
+    pub type AssetId = u32;
+    pub type CollectionId = u32;
+    pub type ItemId = u32;
+
+    const BALANCES: u8 = 10;
+    const ASSETS: u8 = 50;
+    const NFTS: u8 = 52;
+
+    /// Encodes `balances.transfer_keep_alive(dest, value)`, transferring the native asset.
+    pub fn transfer_keep_alive(dest: [u8; 32], value: u128) -> Vec<u8> {
+        (BALANCES, 3u8, multi_address(dest), Compact(value)).encode()
+    }
+
+    /// Encodes `assets.transfer(id, target, amount)`.
+    pub fn assets_transfer(id: AssetId, target: [u8; 32], amount: u128) -> Vec<u8> {
+        (
+            ASSETS,
+            8u8,
+            Compact(id),
+            multi_address(target),
+            Compact(amount),
+        )
+            .encode()
+    }
+
+    /// Encodes `assets.transfer_keep_alive(id, target, amount)`.
+    pub fn assets_transfer_keep_alive(id: AssetId, target: [u8; 32], amount: u128) -> Vec<u8> {
+        (
+            ASSETS,
+            9u8,
+            Compact(id),
+            multi_address(target),
+            Compact(amount),
+        )
+            .encode()
+    }
+
+    /// Encodes `nfts.mint(collection, item, mint_to, None)`, minting as issuer of the collection.
+    pub fn nfts_mint(collection: CollectionId, item: ItemId, mint_to: [u8; 32]) -> Vec<u8> {
+        (NFTS, 3u8, collection, item, multi_address(mint_to), 0u8).encode()
+    }
+
+    /// Encodes `nfts.burn(collection, item)`.
+    pub fn nfts_burn(collection: CollectionId, item: ItemId) -> Vec<u8> {
+        (NFTS, 5u8, collection, item).encode()
+    }
+
+    /// Encodes `nfts.transfer(collection, item, dest)`.
+    pub fn nfts_transfer(collection: CollectionId, item: ItemId, dest: [u8; 32]) -> Vec<u8> {
+        (NFTS, 6u8, collection, item, multi_address(dest)).encode()
+    }
+}
+
+/// Encodes `utility.batch_all(calls)`, dispatching all calls or none of them.
+pub fn batch_all(chain: &Chain, calls: &[Vec<u8>]) -> Vec<u8> {
+    let mut encoded = Vec::from([chain.utility, BATCH_ALL]);
+    Compact(calls.len() as u32).encode_to(&mut encoded);
+    for call in calls {
+        encoded.extend_from_slice(call);
+    }
+    encoded
+}
+
+/// Encodes `proxy.proxy(real, None, call)`, dispatching `call` on behalf of `real` with any proxy
+/// type.
+pub fn proxy(chain: &Chain, real: [u8; 32], call: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::from([chain.proxy, PROXY]);
+    (multi_address(real), None::<u8>).encode_to(&mut encoded);
+    encoded.extend_from_slice(call);
+    encoded
+}
+
+/// Encodes `utility.as_derivative(index, call)`, dispatching `call` by the account derived from
+/// the origin with `index`.
+pub fn as_derivative(chain: &Chain, index: u16, call: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::from([chain.utility, AS_DERIVATIVE]);
+    index.encode_to(&mut encoded);
+    encoded.extend_from_slice(call);
+    encoded
+}
+
+/// Returns the account derived from `account` with `index` by `pallet_utility`, which dispatches
+/// the calls `account` wraps in `utility.as_derivative(index, call)`.
+pub fn derivative_account(account: [u8; 32], index: u16) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    Blake2x256::hash(&(b"modlpy/utilisuba", account, index).encode(), &mut output);
+    output
+}
+
+/// Re-encodes `call` for a runtime in which its pallet has `index`, e.g. after a runtime upgrade
+/// added or removed pallets. Calls are encoded as the index of their pallet followed by the index
+/// and the arguments of the call, which stay the same.
+pub fn reindex(mut call: Vec<u8>, index: u8) -> Vec<u8> {
+    if let Some(pallet) = call.first_mut() {
+        *pallet = index;
+    }
+    call
+}
+
+/// Wraps `calls` into a single call, batched if there are several and dispatched as described by
+/// `dispatch`. Returns `None` if there are no calls.
+pub fn wrap(chain: &Chain, calls: &[Vec<u8>], dispatch: Dispatch) -> Option<Vec<u8>> {
+    let call = match calls {
+        [] => return None,
+        [call] => call.clone(),
+        calls => batch_all(chain, calls),
+    };
+    Some(match dispatch {
+        Dispatch::Direct => call,
+        Dispatch::Proxy(real) => proxy(chain, real, &call),
+        Dispatch::Derivative(index) => as_derivative(chain, index, &call),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `system.remark(0x01)` and `system.remark(0x0203)`.
+    fn calls() -> Vec<Vec<u8>> {
+        Vec::from([Vec::from([0, 0, 4, 1]), Vec::from([0, 0, 8, 2, 3])])
+    }
+
+    fn account() -> Vec<u8> {
+        let mut account = Vec::from([0]);
+        account.extend([1; 32]);
+        account
+    }
+
+    #[test]
+    fn hydration_calls_work() {
+        let mut expected = Vec::from([7, 3]);
+        expected.extend(account());
+        // 10 HDX, compact encoded.
+        expected.extend([0x0b, 0x00, 0xa0, 0x72, 0x4e, 0x18, 0x09]);
+        assert_eq!(
+            hydration::transfer_keep_alive([1; 32], 10_000_000_000_000),
+            expected
+        );
+
+        let mut expected = Vec::from([59, 4, 0, 0, 0, 0, 10, 0, 0, 0]);
+        expected.extend(100u128.to_le_bytes());
+        expected.extend(90u128.to_le_bytes());
+        assert_eq!(hydration::sell(0, 10, 100, 90), expected);
+        expected[1] = 5;
+        assert_eq!(hydration::buy(0, 10, 100, 90), expected);
+    }
+
+    #[test]
+    fn asset_hub_calls_work() {
+        let mut expected = Vec::from([10, 3]);
+        expected.extend(account());
+        expected.push(0x28);
+        assert_eq!(asset_hub::transfer_keep_alive([1; 32], 10), expected);
+
+        // USDT, 1_000_000 compact encoded.
+        let mut expected = Vec::from([50, 8, 0x01, 0x1f]);
+        expected.extend(account());
+        expected.extend([0x02, 0x09, 0x3d, 0x00]);
+        assert_eq!(
+            asset_hub::assets_transfer(1984, [1; 32], 1_000_000),
+            expected
+        );
+        expected[1] = 9;
+        assert_eq!(
+            asset_hub::assets_transfer_keep_alive(1984, [1; 32], 1_000_000),
+            expected
+        );
+
+        let mut expected = Vec::from([52, 3, 1, 0, 0, 0, 2, 0, 0, 0]);
+        expected.extend(account());
+        expected.push(0);
+        assert_eq!(asset_hub::nfts_mint(1, 2, [1; 32]), expected);
+        assert_eq!(asset_hub::nfts_burn(1, 2), [52, 5, 1, 0, 0, 0, 2, 0, 0, 0]);
+        let mut expected = Vec::from([52, 6, 1, 0, 0, 0, 2, 0, 0, 0]);
+        expected.extend(account());
+        assert_eq!(asset_hub::nfts_transfer(1, 2, [1; 32]), expected);
+    }
+
+    #[test]
+    fn batch_all_works() {
+        assert_eq!(
+            batch_all(&HYDRATION, &calls()),
+            [13, 2, 8, 0, 0, 4, 1, 0, 0, 8, 2, 3]
+        );
+    }
+
+    #[test]
+    fn proxy_works() {
+        let mut expected = Vec::from([42, 0, 0]);
+        expected.extend([1; 32]);
+        expected.extend([0, 0, 0, 4, 1]);
+        assert_eq!(proxy(&ASSET_HUB, [1; 32], &calls()[0]), expected);
+    }
+
+    #[test]
+    fn as_derivative_works() {
+        assert_eq!(
+            as_derivative(&HYDRATION, 258, &calls()[0]),
+            [13, 1, 2, 1, 0, 0, 4, 1]
+        );
+        // Accounts are derived for each index.
+        assert_ne!(
+            derivative_account([1; 32], 0),
+            derivative_account([1; 32], 1)
+        );
+    }
+
+    #[test]
+    fn reindex_works() {
+        let call = hydration::sell(0, 10, 100, 90);
+        let reindexed = reindex(call.clone(), 60);
+        assert_eq!(reindexed[0], 60);
+        assert_eq!(reindexed[1..], call[1..]);
+        assert_eq!(reindex(Vec::new(), 60), []);
+    }
+
+    #[test]
+    fn wrap_works() {
+        assert_eq!(wrap(&HYDRATION, &[], Dispatch::Direct), None);
+        assert_eq!(
+            wrap(&HYDRATION, &calls()[..1], Dispatch::Direct),
+            Some(calls()[0].clone())
+        );
+        assert_eq!(
+            wrap(&HYDRATION, &calls(), Dispatch::Proxy([1; 32])),
+            Some(proxy(&HYDRATION, [1; 32], &batch_all(&HYDRATION, &calls())))
+        );
+        assert_eq!(
+            wrap(&HYDRATION, &calls(), Dispatch::Derivative(1)),
+            Some(as_derivative(
+                &HYDRATION,
+                1,
+                &batch_all(&HYDRATION, &calls())
+            ))
+        );
+    }
+}
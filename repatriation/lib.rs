@@ -0,0 +1,117 @@
+//! The program returning the relay asset held by a contract on another chain to Pop, and the
+//! account holding it there, shared by the contracts withdrawing from their accounts on other
+//! chains.
+//!
+//! The relay asset is reserve backed by Asset Hub, so it is transferred via Asset Hub unless
+//! withdrawn from Asset Hub itself.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{
+    env::hash::{Blake2x256, CryptoHash},
+    primitives::AccountId,
+    scale::{Compact, Encode},
+    xcm::prelude::*,
+};
+
+/// The para id of Asset Hub, the reserve of the relay asset.
+pub const ASSET_HUB: u32 = 1_000;
+/// The para id of Pop.
+pub const POP: u32 = 4_001;
+
+/// Returns the program reserve transferring `amount` of the relay asset from the origin on
+/// `from_para` back to `beneficiary` on Pop, via Asset Hub unless `from_para` is Asset Hub.
+/// Whether it failed is reported to `response`.
+///
+/// Execution is bought with up to a quarter of `amount` on every chain. Delivery fees are
+/// withdrawn from the origin, as the holding is transferred.
+pub fn repatriate_xcm(
+    from_para: u32,
+    amount: u128,
+    beneficiary: AccountId,
+    response: QueryResponseInfo,
+) -> Xcm<()> {
+    let asset: Asset = (Location::parent(), amount).into();
+    let fees: Asset = (Location::parent(), amount / 4).into();
+    let pop = Location::new(1, Parachain(POP));
+    let deposit = Xcm::builder_unsafe()
+        .buy_execution(fees.clone(), WeightLimit::Unlimited)
+        .deposit_asset(
+            All.into(),
+            Location::new(
+                0,
+                AccountId32 {
+                    network: None,
+                    id: beneficiary.0,
+                },
+            ),
+        )
+        .build();
+    let message = Xcm::builder_unsafe()
+        .withdraw_asset(asset.into())
+        .buy_execution(fees.clone(), WeightLimit::Unlimited)
+        .set_fees_mode(true)
+        .set_appendix(Xcm::builder_unsafe().report_error(response).build());
+    if from_para == ASSET_HUB {
+        return message
+            .deposit_reserve_asset(All.into(), pop, deposit)
+            .build();
+    }
+    let reserve = Location::new(1, Parachain(ASSET_HUB));
+    let on_reserve = Xcm::builder_unsafe()
+        .buy_execution(fees, WeightLimit::Unlimited)
+        .deposit_reserve_asset(All.into(), pop, deposit)
+        .build();
+    message
+        .initiate_reserve_withdraw(All.into(), reserve, on_reserve)
+        .build()
+}
+
+/// Returns the account of `account_id` on the sibling chain `para_id` on other chains, as derived
+/// by their `HashedDescription<AccountId, DescribeFamily<DescribeAllTerminal>>` location converter.
+pub fn hashed_account(para_id: u32, account_id: AccountId) -> AccountId {
+    let location = (
+        b"SiblingChain",
+        Compact::<u32>::from(para_id),
+        (b"AccountId32", account_id.0).encode(),
+    )
+        .encode();
+    let mut output = [0u8; 32];
+    Blake2x256::hash(&location, &mut output);
+    AccountId::from(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> QueryResponseInfo {
+        QueryResponseInfo {
+            destination: Location::new(1, Parachain(POP)),
+            query_id: 0,
+            max_weight: Weight::zero(),
+        }
+    }
+
+    #[test]
+    fn withdrawals_from_asset_hub_are_deposited_on_pop() {
+        let xcm = repatriate_xcm(ASSET_HUB, 100, AccountId::from([1; 32]), response());
+        assert!(matches!(
+            xcm.0.last(),
+            Some(DepositReserveAsset { dest, .. }) if *dest == Location::new(1, Parachain(POP))
+        ));
+    }
+
+    #[test]
+    fn withdrawals_from_other_chains_are_routed_via_asset_hub() {
+        let xcm = repatriate_xcm(2_034, 100, AccountId::from([1; 32]), response());
+        let Some(InitiateReserveWithdraw { reserve, xcm, .. }) = xcm.0.last() else {
+            panic!("expected a reserve withdrawal")
+        };
+        assert_eq!(*reserve, Location::new(1, Parachain(ASSET_HUB)));
+        assert!(matches!(
+            xcm.0.last(),
+            Some(DepositReserveAsset { dest, .. }) if *dest == Location::new(1, Parachain(POP))
+        ));
+    }
+}
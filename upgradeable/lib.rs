@@ -0,0 +1,183 @@
+//! Upgrades of contracts with `set_code_hash`, so deployed instances keep their storage, balance
+//! and accounts on other chains, e.g. those funded over XCM, when their code evolves.
+//!
+//! A contract keeps a [`StorageVersion`] in its storage, implements [`Migrate`] and implements
+//! [`Upgradeable`] by delegating to them, restricted to its owner. Upgrading only replaces the
+//! code: the storage written by the previous code is migrated by the new code with [`migrate`].
+//!
+//! The root of the storage is decoded by every message, so its packed fields never change between
+//! versions. State added by a later version lives in new fields which are not packed, e.g. a
+//! `Mapping` or `Lazy`, appended to the root. Each version changing the layout increments
+//! [`Migrate::VERSION`] and migrates the state of the previous version in [`Migrate::migrate_from`].
+//!
+//! Only instances deployed with code implementing [`Upgradeable`] can be upgraded. Instances
+//! deployed before their contract implemented it have no `set_code` message and can never be
+//! upgraded, so they have to be replaced by new instances.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{env::DefaultEnvironment, primitives::Hash};
+
+/// The version of the layout of the storage of a contract.
+pub type Version = u32;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// The caller is not the owner.
+    NotOwner,
+    /// No code was uploaded with the code hash.
+    CodeNotFound,
+    /// The storage can't be migrated from the version.
+    UnsupportedVersion(Version),
+}
+
+/// The messages upgrading a contract.
+#[ink::trait_definition]
+pub trait Upgradeable {
+    /// Returns the version of the layout of the storage.
+    #[ink(message)]
+    fn storage_version(&self) -> Version;
+
+    /// Replaces the code of the contract with the code uploaded with `code_hash`, keeping its
+    /// storage. Only callable by the owner, who calls `migrate` on the new code next.
+    #[ink(message)]
+    fn set_code(&mut self, code_hash: Hash) -> Result<()>;
+
+    /// Migrates the storage to the layout of the code. Only callable by the owner.
+    #[ink(message)]
+    fn migrate(&mut self) -> Result<()>;
+}
+
+/// The migration of the storage written by previous code.
+pub trait Migrate {
+    /// The version of the layout of the storage written by the code.
+    const VERSION: Version;
+
+    fn storage_version_mut(&mut self) -> &mut StorageVersion;
+
+    /// Migrates the storage from layout `from` to the next version.
+    fn migrate_from(&mut self, from: Version) -> Result<()>;
+}
+
+#[ink::event]
+pub struct CodeSet {
+    #[ink(topic)]
+    pub code_hash: Hash,
+}
+
+#[ink::event]
+pub struct Migrated {
+    pub from: Version,
+    pub to: Version,
+}
+
+/// The version of the layout of the storage of a contract.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct StorageVersion {
+    version: Version,
+}
+
+impl StorageVersion {
+    pub fn new(version: Version) -> Self {
+        Self { version }
+    }
+
+    pub fn get(&self) -> Version {
+        self.version
+    }
+
+    /// Replaces the code of the contract with the code uploaded with `code_hash`.
+    pub fn set_code(&self, code_hash: Hash) -> Result<()> {
+        ink::env::set_code_hash::<DefaultEnvironment>(&code_hash)
+            .map_err(|_| Error::CodeNotFound)?;
+        emit(CodeSet { code_hash });
+        Ok(())
+    }
+}
+
+/// Migrates the storage of `contract` to [`Migrate::VERSION`], one version at a time. Returns the
+/// version migrated from.
+pub fn migrate<C: Migrate>(contract: &mut C) -> Result<Version> {
+    let from = contract.storage_version_mut().get();
+    if from > C::VERSION {
+        return Err(Error::UnsupportedVersion(from));
+    }
+    for version in from..C::VERSION {
+        contract.migrate_from(version)?;
+    }
+    if from < C::VERSION {
+        contract.storage_version_mut().version = C::VERSION;
+        emit(Migrated {
+            from,
+            to: C::VERSION,
+        });
+    }
+    Ok(from)
+}
+
+fn emit<E: ink::env::Event>(event: E) {
+    ink::env::emit_event::<DefaultEnvironment, E>(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::env::test;
+
+    #[derive(Default)]
+    struct Contract {
+        version: StorageVersion,
+        migrated: ink::prelude::vec::Vec<Version>,
+    }
+
+    impl Migrate for Contract {
+        const VERSION: Version = 3;
+
+        fn storage_version_mut(&mut self) -> &mut StorageVersion {
+            &mut self.version
+        }
+
+        fn migrate_from(&mut self, from: Version) -> Result<()> {
+            if from == 0 {
+                return Err(Error::UnsupportedVersion(from));
+            }
+            self.migrated.push(from);
+            Ok(())
+        }
+    }
+
+    #[ink::test]
+    fn migrate_works() {
+        let mut contract = Contract {
+            version: StorageVersion::new(1),
+            ..Default::default()
+        };
+        assert_eq!(migrate(&mut contract), Ok(1));
+        assert_eq!(contract.migrated, [1, 2]);
+        assert_eq!(contract.version.get(), 3);
+        assert_eq!(test::recorded_events().count(), 1);
+
+        // Migrating the current version does nothing.
+        assert_eq!(migrate(&mut contract), Ok(3));
+        assert_eq!(contract.migrated, [1, 2]);
+        assert_eq!(test::recorded_events().count(), 1);
+    }
+
+    #[ink::test]
+    fn migrate_rejects_unsupported_versions() {
+        let mut contract = Contract::default();
+        assert_eq!(migrate(&mut contract), Err(Error::UnsupportedVersion(0)));
+        assert_eq!(contract.version.get(), 0);
+
+        // Code never migrates storage of later code.
+        let mut contract = Contract {
+            version: StorageVersion::new(4),
+            ..Default::default()
+        };
+        assert_eq!(migrate(&mut contract), Err(Error::UnsupportedVersion(4)));
+    }
+}
@@ -0,0 +1,488 @@
+//! Executes the XCM programs built by the examples against a simulated network.
+//!
+//! The network consists of a relay chain, a Pop-like chain hosting the contract, an Asset Hub-like
+//! chain acting as reserve and a DEX chain exchanging assets like Hydration. The contract is
+//! represented by a plain account on Pop executing and sending the programs it would build.
+
+mod parachain;
+mod relay_chain;
+
+use sp_runtime::{AccountId32, BuildStorage};
+use xcm::prelude::*;
+use xcm_executor::traits::ConvertLocation;
+use xcm_simulator::{decl_test_network, decl_test_parachain, decl_test_relay_chain, TestExt};
+
+pub const ASSET_HUB: u32 = 1000;
+pub const HYDRATION: u32 = 2034;
+pub const POP: u32 = 4001;
+
+/// The account standing in for the contract on Pop.
+pub const CONTRACT: AccountId32 = AccountId32::new([1u8; 32]);
+pub const BOB: AccountId32 = AccountId32::new([2u8; 32]);
+pub const INITIAL_BALANCE: u128 = 1_000_000_000_000_000;
+
+decl_test_parachain! {
+    pub struct Pop {
+        Runtime = parachain::Runtime,
+        XcmpMessageHandler = parachain::MsgQueue,
+        DmpMessageHandler = parachain::MsgQueue,
+        new_ext = para_ext(POP),
+    }
+}
+
+decl_test_parachain! {
+    pub struct AssetHub {
+        Runtime = parachain::Runtime,
+        XcmpMessageHandler = parachain::MsgQueue,
+        DmpMessageHandler = parachain::MsgQueue,
+        new_ext = para_ext(ASSET_HUB),
+    }
+}
+
+decl_test_parachain! {
+    pub struct Hydration {
+        Runtime = parachain::Runtime,
+        XcmpMessageHandler = parachain::MsgQueue,
+        DmpMessageHandler = parachain::MsgQueue,
+        new_ext = para_ext(HYDRATION),
+    }
+}
+
+decl_test_relay_chain! {
+    pub struct Relay {
+        Runtime = relay_chain::Runtime,
+        RuntimeCall = relay_chain::RuntimeCall,
+        RuntimeEvent = relay_chain::RuntimeEvent,
+        XcmConfig = relay_chain::XcmConfig,
+        MessageQueue = relay_chain::MessageQueue,
+        System = relay_chain::System,
+        new_ext = relay_ext(),
+    }
+}
+
+decl_test_network! {
+    pub struct MockNet {
+        relay_chain = Relay,
+        parachains = vec![
+            (ASSET_HUB, AssetHub),
+            (HYDRATION, Hydration),
+            (POP, Pop),
+        ],
+    }
+}
+
+/// Returns the sovereign account of a sibling parachain.
+pub fn sibling_account(para: u32) -> AccountId32 {
+    parachain::LocationToAccountId::convert_location(&Location::new(1, [Parachain(para)]))
+        .expect("sibling converts")
+}
+
+/// Returns the account controlled by the contract on a sibling of Pop.
+pub fn contract_account() -> AccountId32 {
+    let location = Location::new(
+        1,
+        [
+            Parachain(POP),
+            Junction::AccountId32 {
+                network: None,
+                id: CONTRACT.into(),
+            },
+        ],
+    );
+    parachain::LocationToAccountId::convert_location(&location).expect("account converts")
+}
+
+pub fn para_ext(para_id: u32) -> sp_io::TestExternalities {
+    use parachain::{MsgQueue, Runtime, System, USDT};
+
+    let mut t = frame_system::GenesisConfig::<Runtime>::default()
+        .build_storage()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Runtime> {
+        balances: vec![
+            (CONTRACT, INITIAL_BALANCE),
+            (contract_account(), INITIAL_BALANCE),
+            (sibling_account(POP), INITIAL_BALANCE),
+            (sibling_account(HYDRATION), INITIAL_BALANCE),
+        ],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+    pallet_assets::GenesisConfig::<Runtime> {
+        assets: vec![(USDT, CONTRACT, true, 1)],
+        accounts: vec![(USDT, sibling_account(HYDRATION), INITIAL_BALANCE)],
+        ..Default::default()
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+
+    let mut ext = sp_io::TestExternalities::new(t);
+    ext.execute_with(|| {
+        System::set_block_number(1);
+        MsgQueue::set_para_id(para_id.into());
+    });
+    ext
+}
+
+pub fn relay_ext() -> sp_io::TestExternalities {
+    use relay_chain::{Runtime, System};
+
+    let mut t = frame_system::GenesisConfig::<Runtime>::default()
+        .build_storage()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Runtime> {
+        balances: vec![(CONTRACT, INITIAL_BALANCE)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+
+    let mut ext = sp_io::TestExternalities::new(t);
+    ext.execute_with(|| System::set_block_number(1));
+    ext
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::{Decode, Encode};
+    use frame_support::{
+        assert_ok,
+        traits::{fungible::Inspect as _, fungibles::Inspect as _},
+    };
+    use hydra_swap::xcm::{
+        fund_direct_xcm, fund_indirect_xcm, native_asset, transfer_and_swap_xcm, DepositedLocation,
+    };
+    use parachain::{Assets, Balances, PolkadotXcm, RuntimeCall, RuntimeOrigin, USDT};
+
+    const PAS: u128 = 10_000_000_000;
+    const USDT_UNIT: u128 = 1_000_000;
+
+    /// Converts a program built by a contract into the simulator's XCM types.
+    fn program<Call>(xcm: ink::xcm::v4::Xcm<()>) -> VersionedXcm<Call>
+    where
+        VersionedXcm<Call>: Decode,
+    {
+        let encoded = ink::xcm::VersionedXcm::V4(xcm).encode();
+        VersionedXcm::decode(&mut &encoded[..]).expect("contract programs decode")
+    }
+
+    fn ink_account(account: &AccountId32) -> ink::primitives::AccountId {
+        ink::primitives::AccountId::from(*<AccountId32 as AsRef<[u8; 32]>>::as_ref(account))
+    }
+
+    fn usdt(amount: u128) -> ink::xcm::prelude::Asset {
+        (
+            ink::xcm::prelude::Location::new(
+                1,
+                [
+                    ink::xcm::prelude::Parachain(ASSET_HUB),
+                    ink::xcm::prelude::PalletInstance(50),
+                    ink::xcm::prelude::GeneralIndex(USDT.into()),
+                ],
+            ),
+            amount,
+        )
+            .into()
+    }
+
+    /// Returns the response info of query `query_id` of the contract, answered on Pop.
+    fn response(query_id: u64) -> ink::xcm::prelude::QueryResponseInfo {
+        ink::xcm::prelude::QueryResponseInfo {
+            destination: ink::xcm::prelude::Location::new(1, ink::xcm::prelude::Parachain(POP)),
+            query_id,
+            max_weight: ink::xcm::prelude::Weight::from_parts(1_000_000, 5_000),
+        }
+    }
+
+    /// Executes `xcm` on Pop as the contract, like `api::xcm::execute`.
+    fn execute_as_contract(xcm: ink::xcm::v4::Xcm<()>) {
+        Pop::execute_with(|| {
+            assert_ok!(PolkadotXcm::execute(
+                RuntimeOrigin::signed(CONTRACT),
+                Box::new(program(xcm)),
+                Weight::MAX,
+            ));
+        });
+    }
+
+    /// Sends `xcm` from Pop to `dest` as the contract, like `api::xcm::send`.
+    fn send_as_contract(dest: u32, xcm: ink::xcm::v4::Xcm<()>) {
+        Pop::execute_with(|| {
+            assert_ok!(PolkadotXcm::send(
+                RuntimeOrigin::signed(CONTRACT),
+                Box::new(Location::new(1, [Parachain(dest)]).into()),
+                Box::new(program(xcm)),
+            ));
+        });
+    }
+
+    #[test]
+    fn contract_account_matches_hashed_account() {
+        assert_eq!(
+            ink_account(&contract_account()),
+            messaging::hashed_account(POP, ink_account(&CONTRACT))
+        );
+        assert_eq!(
+            ink_account(&contract_account()),
+            hydra_swap::xcm::hashed_account(POP, ink_account(&CONTRACT))
+        );
+    }
+
+    #[test]
+    fn hashed_account_matches_location_converter() {
+        // The accounts of `HashedDescription<DescribeFamily<DescribeAllTerminal>>`, as used by
+        // Asset Hub and Hydration.
+        for para in [ASSET_HUB, HYDRATION, POP] {
+            for account in [CONTRACT, BOB, AccountId32::new([0xd4; 32])] {
+                let location = Location::new(
+                    1,
+                    [
+                        Parachain(para),
+                        Junction::AccountId32 {
+                            network: None,
+                            id: account.clone().into(),
+                        },
+                    ],
+                );
+                let converted = parachain::LocationToAccountId::convert_location(&location)
+                    .expect("account converts");
+                assert_eq!(
+                    ink_account(&converted),
+                    hydra_swap::xcm::hashed_account(para, ink_account(&account))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nested_account_does_not_convert() {
+        // The location a contract descends to for one of its callers has no account, so calls
+        // cannot be dispatched on behalf of callers.
+        let location = Location::new(
+            1,
+            [
+                Parachain(POP),
+                Junction::AccountId32 {
+                    network: None,
+                    id: CONTRACT.into(),
+                },
+                Junction::AccountId32 {
+                    network: None,
+                    id: BOB.into(),
+                },
+            ],
+        );
+        assert_eq!(
+            parachain::LocationToAccountId::convert_location(&location),
+            None
+        );
+    }
+
+    #[test]
+    fn fund_direct_works() {
+        MockNet::reset();
+        let amount = 10 * PAS;
+
+        execute_as_contract(fund_direct_xcm(
+            ink_account(&BOB),
+            POP,
+            ASSET_HUB,
+            false,
+            amount,
+        ));
+
+        Pop::execute_with(|| {
+            assert_eq!(Balances::balance(&CONTRACT), INITIAL_BALANCE - amount);
+        });
+        AssetHub::execute_with(|| {
+            // The reserve is withdrawn from Pop's sovereign account.
+            assert_eq!(
+                Balances::balance(&sibling_account(POP)),
+                INITIAL_BALANCE - amount
+            );
+            // Minus the fees for executing on Asset Hub.
+            let received = Balances::balance(&BOB);
+            assert!(received > amount / 2 && received < amount);
+        });
+    }
+
+    #[test]
+    fn fund_indirect_works() {
+        MockNet::reset();
+        let amount = 10 * PAS;
+
+        execute_as_contract(fund_indirect_xcm(
+            ink_account(&BOB),
+            POP,
+            ASSET_HUB,
+            HYDRATION,
+            false,
+            amount,
+        ));
+
+        let reserved = AssetHub::execute_with(|| {
+            assert_eq!(
+                Balances::balance(&sibling_account(POP)),
+                INITIAL_BALANCE - amount
+            );
+            // The reserve moves to Hydration's sovereign account, minus the fees on Asset Hub.
+            let reserved = Balances::balance(&sibling_account(HYDRATION)) - INITIAL_BALANCE;
+            assert!(reserved > amount / 2 && reserved < amount);
+            reserved
+        });
+        Hydration::execute_with(|| {
+            let received = Balances::balance(&BOB);
+            assert!(received > 0 && received < reserved);
+        });
+    }
+
+    #[test]
+    fn transfer_and_swap_to_hydration_account_works() {
+        MockNet::reset();
+        let (give, fee, want) = (5 * PAS, PAS, 100 * USDT_UNIT);
+
+        execute_as_contract(
+            transfer_and_swap_xcm(
+                POP,
+                ASSET_HUB,
+                native_asset(give),
+                usdt(want),
+                false,
+                native_asset(fee),
+                give + 2 * fee,
+                DepositedLocation::Account(ink_account(&BOB)),
+            )
+            .unwrap(),
+        );
+
+        Pop::execute_with(|| {
+            assert_eq!(
+                Balances::balance(&CONTRACT),
+                INITIAL_BALANCE - give - 2 * fee
+            );
+        });
+        Hydration::execute_with(|| {
+            assert_eq!(Assets::balance(USDT, &BOB), want);
+            // Unused fees are deposited alongside the swapped asset.
+            assert!(Balances::balance(&BOB) > 0);
+        });
+    }
+
+    #[test]
+    fn transfer_and_swap_to_asset_hub_account_works() {
+        MockNet::reset();
+        let (give, fee, want) = (5 * PAS, PAS, 100 * USDT_UNIT);
+
+        execute_as_contract(
+            transfer_and_swap_xcm(
+                POP,
+                ASSET_HUB,
+                native_asset(give),
+                usdt(want),
+                false,
+                native_asset(fee),
+                give + 2 * fee,
+                DepositedLocation::ParachainAccount(ASSET_HUB, ink_account(&BOB)),
+            )
+            .unwrap(),
+        );
+
+        Hydration::execute_with(|| {
+            assert_eq!(Assets::balance(USDT, &BOB), 0);
+        });
+        AssetHub::execute_with(|| {
+            // USDT is withdrawn from Hydration's sovereign account on its reserve.
+            assert_eq!(
+                Assets::balance(USDT, &sibling_account(HYDRATION)),
+                INITIAL_BALANCE - want
+            );
+            // Minus the fees for executing on Asset Hub, paid in USDT.
+            let received = Assets::balance(USDT, &BOB);
+            assert!(received > want / 2 && received < want);
+        });
+    }
+
+    #[test]
+    fn messaging_fund_works() {
+        MockNet::reset();
+        let amount = 10 * PAS;
+
+        execute_as_contract(messaging::fund_xcm(
+            native_asset(amount),
+            ink::xcm::prelude::Location::new(1, ink::xcm::prelude::Parachain(ASSET_HUB)),
+            messaging::hashed_account(POP, ink_account(&CONTRACT)),
+            (response(0), response(1)),
+        ));
+
+        AssetHub::execute_with(|| {
+            let received = Balances::balance(&contract_account()) - INITIAL_BALANCE;
+            assert!(received > 0 && received < amount);
+        });
+    }
+
+    #[test]
+    fn messaging_transact_works() {
+        MockNet::reset();
+        let (value, fees) = (2 * PAS, PAS);
+        let call =
+            RuntimeCall::Balances(pallet_balances::Call::transfer_keep_alive { dest: BOB, value });
+
+        send_as_contract(
+            ASSET_HUB,
+            messaging::transact_xcm(
+                call.encode().into(),
+                ink::xcm::prelude::Weight::from_parts(1_000_000_000, 100_000),
+                native_asset(fees),
+                response(0),
+                (response(1), response(2)),
+                messaging::hashed_account(POP, ink_account(&CONTRACT)),
+            ),
+        );
+
+        AssetHub::execute_with(|| {
+            assert_eq!(Balances::balance(&BOB), value);
+            // The call and the fees are paid by the contract's account, surplus fees are refunded.
+            let spent = INITIAL_BALANCE - Balances::balance(&contract_account());
+            assert!(spent > value && spent < value + fees);
+        });
+    }
+
+    #[test]
+    fn messaging_transact_as_derivative_works() {
+        MockNet::reset();
+        let (value, fees, index) = (2 * PAS, PAS, 3);
+        let derived = AccountId32::new(remote_calls::derivative_account(
+            contract_account().into(),
+            index,
+        ));
+        AssetHub::execute_with(|| {
+            assert_ok!(Balances::force_set_balance(
+                RuntimeOrigin::root(),
+                derived.clone(),
+                INITIAL_BALANCE
+            ));
+        });
+        let call =
+            RuntimeCall::Balances(pallet_balances::Call::transfer_keep_alive { dest: BOB, value });
+
+        send_as_contract(
+            ASSET_HUB,
+            messaging::transact_xcm(
+                remote_calls::as_derivative(&remote_calls::ASSET_HUB, index, &call.encode()).into(),
+                ink::xcm::prelude::Weight::from_parts(1_000_000_000, 100_000),
+                native_asset(fees),
+                response(0),
+                (response(1), response(2)),
+                messaging::hashed_account(POP, ink_account(&CONTRACT)),
+            ),
+        );
+
+        AssetHub::execute_with(|| {
+            assert_eq!(Balances::balance(&BOB), value);
+            // The call is paid by the derived account, only the fees by the contract's account.
+            assert_eq!(Balances::balance(&derived), INITIAL_BALANCE - value);
+            let spent = INITIAL_BALANCE - Balances::balance(&contract_account());
+            assert!(spent > 0 && spent < fees);
+        });
+    }
+}
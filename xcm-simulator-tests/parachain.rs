@@ -0,0 +1,254 @@
+//! A parachain runtime shared by the Pop-like, Asset Hub-like and DEX chains.
+//!
+//! The relay token is the native asset of every chain and USDT lives in `pallet_assets` at
+//! instance 50, mirroring its location on Asset Hub. Asset Hub is trusted as the reserve of both.
+
+use frame_support::{
+    construct_runtime, derive_impl, parameter_types,
+    traits::{AsEnsureOriginWithArg, ConstU32, ContainsPair, Everything, Nothing},
+    weights::Weight,
+};
+use frame_system::{EnsureRoot, EnsureSigned};
+use polkadot_parachain_primitives::primitives::Sibling;
+use sp_runtime::{traits::MaybeEquivalence, AccountId32};
+use xcm::latest::prelude::*;
+use xcm_builder::{
+    AccountId32Aliases, AllowKnownQueryResponses, AllowSubscriptionsFrom,
+    AllowTopLevelPaidExecutionFrom, ConvertedConcreteId, DescribeAllTerminal, DescribeFamily,
+    EnsureDecodableXcm, EnsureXcmOrigin, FixedRateOfFungible, FixedWeightBounds,
+    FrameTransactionalProcessor, FungibleAdapter, FungiblesAdapter, HashedDescription, IsConcrete,
+    NoChecking, ParentIsPreset, SiblingParachainConvertsVia, SignedAccountId32AsNative,
+    SignedToAccountId32, SovereignSignedViaLocation, TakeWeightCredit, WithComputedOrigin,
+    XcmPassthrough,
+};
+use xcm_executor::{
+    traits::{AssetExchange, JustTry},
+    AssetsInHolding, XcmExecutor,
+};
+use xcm_simulator::mock_message_queue;
+
+use crate::ASSET_HUB;
+
+pub type AccountId = AccountId32;
+pub type Balance = u128;
+
+/// The asset id of USDT in `pallet_assets`.
+pub const USDT: u32 = 1984;
+
+type Block = frame_system::mocking::MockBlock<Runtime>;
+
+#[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+impl frame_system::Config for Runtime {
+    type Block = Block;
+    type AccountId = AccountId;
+    type Lookup = sp_runtime::traits::IdentityLookup<Self::AccountId>;
+    type AccountData = pallet_balances::AccountData<Balance>;
+}
+
+#[derive_impl(pallet_balances::config_preludes::TestDefaultConfig)]
+impl pallet_balances::Config for Runtime {
+    type Balance = Balance;
+    type ExistentialDeposit = frame_support::traits::ConstU128<1>;
+    type AccountStore = System;
+}
+
+#[derive_impl(pallet_assets::config_preludes::TestDefaultConfig)]
+impl pallet_assets::Config for Runtime {
+    type Balance = Balance;
+    type Currency = Balances;
+    type CreateOrigin = AsEnsureOriginWithArg<EnsureSigned<AccountId>>;
+    type ForceOrigin = EnsureRoot<AccountId>;
+    type Freezer = ();
+}
+
+impl mock_message_queue::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type XcmExecutor = XcmExecutor<XcmConfig>;
+}
+
+parameter_types! {
+    pub const RelayLocation: Location = Location::parent();
+    pub const RelayNetwork: NetworkId = NetworkId::Polkadot;
+    pub UniversalLocation: InteriorLocation =
+        [GlobalConsensus(RelayNetwork::get()), Parachain(MsgQueue::parachain_id().into())].into();
+    pub const UnitWeightCost: Weight = Weight::from_parts(1_000, 1_000);
+    pub const MaxInstructions: u32 = 100;
+    pub const MaxAssetsIntoHolding: u32 = 64;
+    pub RelayTokensPerSecond: (AssetId, u128, u128) =
+        (AssetId(Location::parent()), 1_000_000_000_000, 1024 * 1024);
+    pub LocalUsdtPerSecond: (AssetId, u128, u128) =
+        (AssetId(Location::new(0, [PalletInstance(50), GeneralIndex(USDT.into())])), 1_000_000, 1024);
+    pub SiblingUsdtPerSecond: (AssetId, u128, u128) =
+        (AssetId(Location::new(1, [Parachain(ASSET_HUB), PalletInstance(50), GeneralIndex(USDT.into())])), 1_000_000, 1024);
+}
+
+/// Converts locations to accounts, including the hashed accounts of contracts on sibling chains.
+pub type LocationToAccountId = (
+    ParentIsPreset<AccountId>,
+    SiblingParachainConvertsVia<Sibling, AccountId>,
+    AccountId32Aliases<RelayNetwork, AccountId>,
+    HashedDescription<AccountId, DescribeFamily<DescribeAllTerminal>>,
+);
+
+/// Matches USDT, either locally on Asset Hub or as seen from a sibling.
+pub struct UsdtId;
+impl MaybeEquivalence<Location, u32> for UsdtId {
+    fn convert(location: &Location) -> Option<u32> {
+        match location.unpack() {
+            (0, [PalletInstance(50), GeneralIndex(id)])
+            | (1, [Parachain(ASSET_HUB), PalletInstance(50), GeneralIndex(id)]) => {
+                (*id).try_into().ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn convert_back(id: &u32) -> Option<Location> {
+        Some(Location::new(
+            1,
+            [
+                Parachain(ASSET_HUB),
+                PalletInstance(50),
+                GeneralIndex((*id).into()),
+            ],
+        ))
+    }
+}
+
+pub type AssetTransactors = (
+    FungibleAdapter<Balances, IsConcrete<RelayLocation>, LocationToAccountId, AccountId, ()>,
+    FungiblesAdapter<
+        Assets,
+        ConvertedConcreteId<u32, Balance, UsdtId, JustTry>,
+        LocationToAccountId,
+        AccountId,
+        NoChecking,
+        (),
+    >,
+);
+
+pub type XcmOriginToCallOrigin = (
+    SovereignSignedViaLocation<LocationToAccountId, RuntimeOrigin>,
+    SignedAccountId32AsNative<RelayNetwork, RuntimeOrigin>,
+    XcmPassthrough<RuntimeOrigin>,
+);
+
+/// Allows paid execution, including of programs sent by accounts such as contracts on siblings.
+pub type Barrier = (
+    TakeWeightCredit,
+    WithComputedOrigin<
+        (
+            AllowTopLevelPaidExecutionFrom<Everything>,
+            AllowSubscriptionsFrom<Everything>,
+        ),
+        UniversalLocation,
+        ConstU32<8>,
+    >,
+    AllowKnownQueryResponses<PolkadotXcm>,
+);
+
+pub type XcmRouter = EnsureDecodableXcm<crate::ParachainXcmRouter<MsgQueue>>;
+
+/// Trusts Asset Hub as the reserve of every asset.
+pub struct TrustedReserves;
+impl ContainsPair<Asset, Location> for TrustedReserves {
+    fn contains(_asset: &Asset, origin: &Location) -> bool {
+        *origin == Location::new(1, [Parachain(ASSET_HUB)])
+    }
+}
+
+/// A DEX consuming everything given and returning exactly what is wanted.
+pub struct MockExchange;
+impl AssetExchange for MockExchange {
+    fn exchange_asset(
+        _origin: Option<&Location>,
+        _give: AssetsInHolding,
+        want: &Assets,
+        _maximal: bool,
+    ) -> Result<AssetsInHolding, AssetsInHolding> {
+        Ok(want.clone().into())
+    }
+}
+
+pub struct XcmConfig;
+impl xcm_executor::Config for XcmConfig {
+    type RuntimeCall = RuntimeCall;
+    type XcmSender = XcmRouter;
+    type AssetTransactor = AssetTransactors;
+    type OriginConverter = XcmOriginToCallOrigin;
+    type IsReserve = TrustedReserves;
+    type IsTeleporter = ();
+    type UniversalLocation = UniversalLocation;
+    type Barrier = Barrier;
+    type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+    type Trader = (
+        FixedRateOfFungible<RelayTokensPerSecond, ()>,
+        FixedRateOfFungible<LocalUsdtPerSecond, ()>,
+        FixedRateOfFungible<SiblingUsdtPerSecond, ()>,
+    );
+    type ResponseHandler = PolkadotXcm;
+    type AssetTrap = PolkadotXcm;
+    type AssetLocker = ();
+    type AssetExchanger = MockExchange;
+    type AssetClaims = PolkadotXcm;
+    type SubscriptionService = PolkadotXcm;
+    type PalletInstancesInfo = AllPalletsWithSystem;
+    type FeeManager = ();
+    type MaxAssetsIntoHolding = MaxAssetsIntoHolding;
+    type MessageExporter = ();
+    type UniversalAliases = Nothing;
+    type CallDispatcher = RuntimeCall;
+    type SafeCallFilter = Everything;
+    type Aliasers = Nothing;
+    type TransactionalProcessor = FrameTransactionalProcessor;
+    type HrmpNewChannelOpenRequestHandler = ();
+    type HrmpChannelAcceptedHandler = ();
+    type HrmpChannelClosingHandler = ();
+    type XcmRecorder = PolkadotXcm;
+}
+
+pub type LocalOriginToLocation = SignedToAccountId32<RuntimeOrigin, AccountId, RelayNetwork>;
+
+impl pallet_xcm::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type SendXcmOrigin = EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+    type XcmRouter = XcmRouter;
+    type ExecuteXcmOrigin = EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+    type XcmExecuteFilter = Everything;
+    type XcmExecutor = XcmExecutor<XcmConfig>;
+    type XcmTeleportFilter = Nothing;
+    type XcmReserveTransferFilter = Everything;
+    type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+    type UniversalLocation = UniversalLocation;
+    type RuntimeOrigin = RuntimeOrigin;
+    type RuntimeCall = RuntimeCall;
+    const VERSION_DISCOVERY_QUEUE_SIZE: u32 = 100;
+    type AdvertisedXcmVersion = pallet_xcm::CurrentXcmVersion;
+    type Currency = Balances;
+    type CurrencyMatcher = ();
+    type TrustedLockers = ();
+    type SovereignAccountOf = LocationToAccountId;
+    type MaxLockers = ConstU32<8>;
+    type MaxRemoteLockConsumers = ConstU32<0>;
+    type RemoteLockConsumerIdentifier = ();
+    type WeightInfo = pallet_xcm::TestWeightInfo;
+    type AdminOrigin = EnsureRoot<AccountId>;
+}
+
+impl pallet_utility::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type RuntimeCall = RuntimeCall;
+    type PalletsOrigin = OriginCaller;
+    type WeightInfo = ();
+}
+
+construct_runtime!(
+    pub enum Runtime {
+        System: frame_system = 0,
+        Balances: pallet_balances = 10,
+        MsgQueue: mock_message_queue = 20,
+        PolkadotXcm: pallet_xcm = 31,
+        Utility: pallet_utility = 40,
+        Assets: pallet_assets = 50,
+    }
+);
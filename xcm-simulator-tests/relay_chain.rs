@@ -0,0 +1,187 @@
+//! A minimal relay chain runtime routing messages between the parachains.
+
+use frame_support::{
+    construct_runtime, derive_impl, parameter_types,
+    traits::{Everything, Nothing, ProcessMessage, ProcessMessageError},
+    weights::{Weight, WeightMeter},
+};
+use frame_system::EnsureRoot;
+use polkadot_parachain_primitives::primitives::Id as ParaId;
+use polkadot_runtime_parachains::{
+    configuration,
+    inclusion::{AggregateMessageOrigin, UmpQueueId},
+    origin, shared,
+};
+use sp_runtime::{traits::IdentityLookup, AccountId32};
+use xcm::latest::prelude::*;
+use xcm_builder::{
+    AccountId32Aliases, AllowTopLevelPaidExecutionFrom, ChildParachainAsNative,
+    ChildParachainConvertsVia, FixedRateOfFungible, FixedWeightBounds, FrameTransactionalProcessor,
+    FungibleAdapter, IsConcrete, ProcessXcmMessage, SignedAccountId32AsNative, SignedToAccountId32,
+    SovereignSignedViaLocation, TakeWeightCredit,
+};
+use xcm_executor::XcmExecutor;
+
+pub type AccountId = AccountId32;
+pub type Balance = u128;
+
+type Block = frame_system::mocking::MockBlock<Runtime>;
+
+#[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+impl frame_system::Config for Runtime {
+    type Block = Block;
+    type AccountId = AccountId;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type AccountData = pallet_balances::AccountData<Balance>;
+}
+
+#[derive_impl(pallet_balances::config_preludes::TestDefaultConfig)]
+impl pallet_balances::Config for Runtime {
+    type Balance = Balance;
+    type ExistentialDeposit = frame_support::traits::ConstU128<1>;
+    type AccountStore = System;
+}
+
+impl shared::Config for Runtime {
+    type DisabledValidators = ();
+}
+
+impl configuration::Config for Runtime {
+    type WeightInfo = configuration::TestWeightInfo;
+}
+
+impl origin::Config for Runtime {}
+
+parameter_types! {
+    pub const TokenLocation: Location = Here.into_location();
+    pub const RelayNetwork: NetworkId = NetworkId::Polkadot;
+    pub UniversalLocation: InteriorLocation = RelayNetwork::get().into();
+    pub const UnitWeightCost: Weight = Weight::from_parts(1_000, 1_000);
+    pub const MaxInstructions: u32 = 100;
+    pub const MaxAssetsIntoHolding: u32 = 64;
+    pub TokensPerSecond: (AssetId, u128, u128) = (AssetId(TokenLocation::get()), 1_000_000_000_000, 1024 * 1024);
+    pub MessageQueueServiceWeight: Weight = Weight::from_parts(1_000_000_000, 1_000_000);
+}
+
+pub type LocationToAccountId = (
+    ChildParachainConvertsVia<ParaId, AccountId>,
+    AccountId32Aliases<RelayNetwork, AccountId>,
+);
+
+pub type LocalOriginConverter = (
+    SovereignSignedViaLocation<LocationToAccountId, RuntimeOrigin>,
+    ChildParachainAsNative<origin::Origin, RuntimeOrigin>,
+    SignedAccountId32AsNative<RelayNetwork, RuntimeOrigin>,
+);
+
+pub type XcmRouter = crate::RelayChainXcmRouter;
+pub type Barrier = (TakeWeightCredit, AllowTopLevelPaidExecutionFrom<Everything>);
+pub type LocalAssetTransactor =
+    FungibleAdapter<Balances, IsConcrete<TokenLocation>, LocationToAccountId, AccountId, ()>;
+
+pub struct XcmConfig;
+impl xcm_executor::Config for XcmConfig {
+    type RuntimeCall = RuntimeCall;
+    type XcmSender = XcmRouter;
+    type AssetTransactor = LocalAssetTransactor;
+    type OriginConverter = LocalOriginConverter;
+    type IsReserve = ();
+    type IsTeleporter = ();
+    type UniversalLocation = UniversalLocation;
+    type Barrier = Barrier;
+    type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+    type Trader = FixedRateOfFungible<TokensPerSecond, ()>;
+    type ResponseHandler = XcmPallet;
+    type AssetTrap = XcmPallet;
+    type AssetLocker = ();
+    type AssetExchanger = ();
+    type AssetClaims = XcmPallet;
+    type SubscriptionService = XcmPallet;
+    type PalletInstancesInfo = AllPalletsWithSystem;
+    type FeeManager = ();
+    type MaxAssetsIntoHolding = MaxAssetsIntoHolding;
+    type MessageExporter = ();
+    type UniversalAliases = Nothing;
+    type CallDispatcher = RuntimeCall;
+    type SafeCallFilter = Everything;
+    type Aliasers = Nothing;
+    type TransactionalProcessor = FrameTransactionalProcessor;
+    type HrmpNewChannelOpenRequestHandler = ();
+    type HrmpChannelAcceptedHandler = ();
+    type HrmpChannelClosingHandler = ();
+    type XcmRecorder = XcmPallet;
+}
+
+pub type LocalOriginToLocation = SignedToAccountId32<RuntimeOrigin, AccountId, RelayNetwork>;
+
+impl pallet_xcm::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type SendXcmOrigin = xcm_builder::EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+    type XcmRouter = XcmRouter;
+    type ExecuteXcmOrigin = xcm_builder::EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+    type XcmExecuteFilter = Nothing;
+    type XcmExecutor = XcmExecutor<XcmConfig>;
+    type XcmTeleportFilter = Nothing;
+    type XcmReserveTransferFilter = Everything;
+    type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+    type UniversalLocation = UniversalLocation;
+    type RuntimeOrigin = RuntimeOrigin;
+    type RuntimeCall = RuntimeCall;
+    const VERSION_DISCOVERY_QUEUE_SIZE: u32 = 100;
+    type AdvertisedXcmVersion = pallet_xcm::CurrentXcmVersion;
+    type Currency = Balances;
+    type CurrencyMatcher = ();
+    type TrustedLockers = ();
+    type SovereignAccountOf = LocationToAccountId;
+    type MaxLockers = frame_support::traits::ConstU32<8>;
+    type MaxRemoteLockConsumers = frame_support::traits::ConstU32<0>;
+    type RemoteLockConsumerIdentifier = ();
+    type WeightInfo = pallet_xcm::TestWeightInfo;
+    type AdminOrigin = EnsureRoot<AccountId>;
+}
+
+/// Processes upward messages with the XCM executor.
+pub struct MessageProcessor;
+impl ProcessMessage for MessageProcessor {
+    type Origin = AggregateMessageOrigin;
+
+    fn process_message(
+        message: &[u8],
+        origin: Self::Origin,
+        meter: &mut WeightMeter,
+        id: &mut [u8; 32],
+    ) -> Result<bool, ProcessMessageError> {
+        let para = match origin {
+            AggregateMessageOrigin::Ump(UmpQueueId::Para(para)) => para,
+        };
+        ProcessXcmMessage::<Junction, XcmExecutor<XcmConfig>, RuntimeCall>::process_message(
+            message,
+            Junction::Parachain(para.into()),
+            meter,
+            id,
+        )
+    }
+}
+
+impl pallet_message_queue::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type Size = u32;
+    type HeapSize = frame_support::traits::ConstU32<{ 64 * 1024 }>;
+    type MaxStale = frame_support::traits::ConstU32<8>;
+    type ServiceWeight = MessageQueueServiceWeight;
+    type IdleMaxServiceWeight = ();
+    type MessageProcessor = MessageProcessor;
+    type QueueChangeHandler = ();
+    type QueuePausedQuery = ();
+    type WeightInfo = ();
+}
+
+construct_runtime!(
+    pub enum Runtime {
+        System: frame_system,
+        Balances: pallet_balances,
+        ParasOrigin: origin,
+        XcmPallet: pallet_xcm,
+        MessageQueue: pallet_message_queue,
+    }
+);
@@ -0,0 +1,198 @@
+//! Negotiation of the XCM version of destinations, so programs are sent in a version they
+//! understand rather than always in the latest.
+//!
+//! A contract registers a query with a destination as responder and sends it [`subscribe_xcm`]
+//! with the id of the query, recording the destination with [`XcmVersions::subscribe`]. The
+//! `Response::Version` received is passed to [`XcmVersions::report`]. Before sending, programs and
+//! their destination are converted to the version negotiated with [`XcmVersions::versioned`];
+//! destinations without a version are sent the latest.
+//!
+//! The version negotiated is the lower of the one reported and [`LATEST`], the latest version of
+//! the `xcm` crate of ink, which destinations supporting later versions, e.g. V5, understand too.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::{
+    env::DefaultEnvironment,
+    storage::Mapping,
+    xcm::{
+        prelude::{Location, QueryId, Weight, Xcm},
+        IntoVersion, Version, VersionedLocation, VersionedXcm,
+    },
+};
+
+/// The id of the message of a query, see `pop_api::messaging::MessageId`.
+pub type MessageId = u64;
+
+/// The latest version programs are sent in.
+pub const LATEST: Version = ink::xcm::latest::VERSION;
+/// The earliest version programs are converted to.
+pub const EARLIEST: Version = 3;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum Error {
+    /// The version negotiated with the destination is earlier than [`EARLIEST`].
+    UnsupportedVersion(Version),
+    /// The program can't be expressed in the version negotiated with the destination.
+    Unconvertible(Version),
+}
+
+#[ink::event]
+pub struct VersionNegotiated {
+    #[ink(topic)]
+    pub dest: Location,
+    pub version: Version,
+}
+
+/// The XCM versions negotiated with destinations.
+#[ink::storage_item]
+#[derive(Default)]
+pub struct XcmVersions {
+    versions: Mapping<Location, Version>,
+    /// The destinations awaiting the report of their version, by the id of the message of the
+    /// query.
+    subscriptions: Mapping<MessageId, Location>,
+}
+
+impl XcmVersions {
+    /// Returns the version negotiated with `dest`, if any.
+    pub fn version_of(&self, dest: &Location) -> Option<Version> {
+        self.versions.get(dest)
+    }
+
+    /// Sets the version of `dest`, e.g. of destinations not permitting subscriptions, returning
+    /// the version negotiated.
+    pub fn set_version(&mut self, dest: Location, version: Version) -> Version {
+        let version = version.min(LATEST);
+        self.versions.insert(&dest, &version);
+        emit(VersionNegotiated { dest, version });
+        version
+    }
+
+    /// Sets the version of `dest` to the version reported to the query of message `id`, once
+    /// reported.
+    pub fn subscribe(&mut self, id: MessageId, dest: Location) {
+        self.subscriptions.insert(id, &dest);
+    }
+
+    /// Stops awaiting the report of message `id`, e.g. once it timed out so its id can be used
+    /// again. Returns the destination which would have been updated.
+    pub fn cancel(&mut self, id: MessageId) -> Option<Location> {
+        self.subscriptions.take(id)
+    }
+
+    /// Sets the version of the destination awaiting the report of message `id` to `version`,
+    /// returning the destination and the version negotiated. Returns `None` if no report is
+    /// awaited.
+    pub fn report(&mut self, id: MessageId, version: Version) -> Option<(Location, Version)> {
+        let dest = self.subscriptions.take(id)?;
+        let version = self.set_version(dest.clone(), version);
+        Some((dest, version))
+    }
+
+    /// Returns the version programs are sent to `dest` in: the version negotiated with it, or the
+    /// latest.
+    pub fn version(&self, dest: &Location) -> Result<Version> {
+        let version = self.version_of(dest).unwrap_or(LATEST);
+        if version < EARLIEST {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        Ok(version)
+    }
+
+    /// Returns `dest` in the version programs are sent to it in.
+    pub fn location(&self, dest: Location) -> Result<VersionedLocation> {
+        let version = self.version(&dest)?;
+        VersionedLocation::from(dest)
+            .into_version(version)
+            .map_err(|_| Error::Unconvertible(version))
+    }
+
+    /// Returns `dest` and `message` in the version programs are sent to `dest` in.
+    pub fn versioned<Call>(
+        &self,
+        dest: Location,
+        message: Xcm<Call>,
+    ) -> Result<(VersionedLocation, VersionedXcm<Call>)> {
+        let version = self.version(&dest)?;
+        let message = VersionedXcm::from(message)
+            .into_version(version)
+            .map_err(|_| Error::Unconvertible(version))?;
+        Ok((self.location(dest)?, message))
+    }
+}
+
+/// Returns the program subscribing to the version of the destination it is sent to, reported to
+/// the query `query_id`.
+pub fn subscribe_xcm(query_id: QueryId, max_response_weight: Weight) -> Xcm<()> {
+    Xcm::builder_unsafe()
+        .subscribe_version(query_id, max_response_weight)
+        .build()
+}
+
+fn emit<E: ink::env::Event>(event: E) {
+    ink::env::emit_event::<DefaultEnvironment, E>(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::{
+        env::test,
+        xcm::prelude::{Asset, Junction::Parachain, WeightLimit},
+    };
+
+    fn program() -> Xcm<()> {
+        let fees: Asset = (Location::parent(), 100).into();
+        Xcm::builder_unsafe()
+            .withdraw_asset(fees.clone().into())
+            .buy_execution(fees, WeightLimit::Unlimited)
+            .build()
+    }
+
+    #[ink::test]
+    fn report_sets_version() {
+        let dest = Location::new(1, Parachain(1_000));
+        let mut versions = XcmVersions::default();
+        assert_eq!(versions.report(1, 3), None);
+
+        versions.subscribe(1, dest.clone());
+        assert_eq!(versions.report(1, 3), Some((dest.clone(), 3)));
+        assert_eq!(versions.version_of(&dest), Some(3));
+        // Reports are only applied once.
+        assert_eq!(versions.report(1, 4), None);
+
+        // Later versions than the latest known are negotiated down.
+        versions.subscribe(2, dest.clone());
+        assert_eq!(versions.report(2, LATEST + 1), Some((dest.clone(), LATEST)));
+
+        versions.subscribe(3, dest.clone());
+        assert_eq!(versions.cancel(3), Some(dest.clone()));
+        assert_eq!(versions.report(3, 3), None);
+        assert_eq!(versions.version_of(&dest), Some(LATEST));
+        assert_eq!(test::recorded_events().count(), 2);
+    }
+
+    #[ink::test]
+    fn versioned_converts_to_negotiated_version() {
+        let dest = Location::new(1, Parachain(1_000));
+        let mut versions = XcmVersions::default();
+        let (location, message) = versions.versioned(dest.clone(), program()).unwrap();
+        assert!(matches!(location, VersionedLocation::V4(_)));
+        assert!(matches!(message, VersionedXcm::V4(_)));
+
+        versions.set_version(dest.clone(), 3);
+        let (location, message) = versions.versioned(dest.clone(), program()).unwrap();
+        assert!(matches!(location, VersionedLocation::V3(_)));
+        assert!(matches!(message, VersionedXcm::V3(ref xcm) if xcm.0.len() == 2));
+
+        versions.set_version(dest.clone(), 2);
+        assert_eq!(
+            versions.versioned(dest, program()),
+            Err(Error::UnsupportedVersion(2))
+        );
+    }
+}